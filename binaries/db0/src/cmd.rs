@@ -1,7 +1,12 @@
 mod error;
 mod init;
+mod recover;
 
 #[cfg(test)]
 mod tests;
 
-pub use {error::Error, init::create_meta_tables};
+pub use {
+    error::Error,
+    init::{create_meta_tables, create_meta_tables_with_options},
+    recover::recover,
+};
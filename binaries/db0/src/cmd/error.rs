@@ -1,6 +1,10 @@
 use {
-    access::btree::error::Error as BTreeError, snafu::prelude::*,
-    storage::buffer::Error as StorageError,
+    access::{btree::error::Error as BTreeError, codec::Error as CodecError},
+    def::meta::error::Error as MetaDataError,
+    snafu::{prelude::*, Backtrace},
+    std::path::PathBuf,
+    storage::{buffer::Error as StorageError, control::Error as ControlError},
+    wal::Error as WalError,
 };
 
 #[derive(Debug, Snafu)]
@@ -17,6 +21,36 @@ pub enum Error {
         #[snafu(backtrace)]
         source: StorageError,
     },
+
+    #[snafu(display("Failed with decoding a record, source: {}", source))]
+    Decoding {
+        #[snafu(backtrace)]
+        source: CodecError,
+    },
+
+    #[snafu(display("Failed with reading metadata, source: {}", source))]
+    MetaData {
+        #[snafu(backtrace)]
+        source: MetaDataError,
+    },
+
+    #[snafu(display("Failed with writing the control file, source: {}", source))]
+    Control {
+        #[snafu(backtrace)]
+        source: ControlError,
+    },
+
+    #[snafu(display("The data directory {} is initialized already", data_dir.display()))]
+    Initialized {
+        backtrace: Backtrace,
+        data_dir: PathBuf,
+    },
+
+    #[snafu(display("Failed with logging, source: {}", source))]
+    Wal {
+        #[snafu(backtrace)]
+        source: WalError,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,34 +1,58 @@
 use {
     super::error::{self, Result},
-    access::{BTree, Codec},
+    access::{btree::DEFAULT_FILL_FACTOR, BTree, Codec},
+    common::durability,
     def::{
         meta::{self, MetaTable},
         storage::Encoder,
         DatabaseId, Value,
     },
     snafu::prelude::*,
-    std::{fs, path::Path},
+    std::path::Path,
     storage::{
         buffer::{BufferManager, FileNode},
-        DEFAULT_PAGE_SIZE,
+        control::ControlFile,
+        store::FileOptions,
     },
+    wal::Xid,
 };
 
-pub fn create_meta_tables(data_dir: &Path) -> Result<()> {
+/// Initializes the data directory, which is refused if it has a control file already: its
+/// relations are written with the page size and checksums of that file.
+pub fn create_meta_tables(data_dir: &Path, control: &ControlFile) -> Result<()> {
+    create_meta_tables_with_options(data_dir, control, FileOptions::default())
+}
+
+/// Initializes the data directory, whose files are accessed and synced as `options` say.
+pub fn create_meta_tables_with_options(
+    data_dir: &Path,
+    control: &ControlFile,
+    options: FileOptions,
+) -> Result<()> {
+    let existing = ControlFile::read(data_dir).context(error::ControlSnafu)?;
+    ensure!(existing.is_none(), error::InitializedSnafu { data_dir });
+
+    control.write(data_dir).context(error::ControlSnafu)?;
+
     let capacity = 100;
-    let manager = BufferManager::new(capacity, DEFAULT_PAGE_SIZE, data_dir.to_path_buf());
+    let manager = BufferManager::with_options(capacity, data_dir.to_path_buf(), options)
+        .context(error::StorageSnafu)?;
+    let xid = manager.wal().begin().context(error::WalSnafu)?;
 
     let database_id = 1;
 
     let file_node = FileNode::global_meta(meta::Database::TABLE_ID);
     create_directory(data_dir, &file_node)?;
-    create_global_tables(&manager, database_id, file_node)?;
+    create_global_tables(&manager, xid, database_id, file_node)?;
+    create_tablespace_table(&manager, xid)?;
 
     let file_node = FileNode::new(meta::TABLESPACE_ID_DEFAULT, database_id, 1);
     create_directory(data_dir, &file_node)?;
-    init_database(&manager, database_id)?;
+    init_database(&manager, xid, database_id)?;
 
-    manager.flush_pages().context(error::StorageSnafu)?;
+    manager.wal().commit(xid).context(error::WalSnafu)?;
+    // the data directory is durable once initialized, without a log to replay
+    manager.checkpoint().context(error::StorageSnafu)?;
 
     Ok(())
 }
@@ -37,13 +61,14 @@ fn create_directory(data_dir: &Path, file_node: &FileNode) -> Result<()> {
     let mut path = data_dir.to_path_buf();
     path.extend(file_node.file_path().parent().unwrap());
 
-    fs::create_dir_all(&path).unwrap();
+    durability::create_dirs_synced(&path).unwrap();
 
     Ok(())
 }
 
 fn create_global_tables(
     manager: &BufferManager,
+    xid: Xid,
     database_id: DatabaseId,
     file_node: FileNode,
 ) -> Result<()> {
@@ -62,17 +87,50 @@ fn create_global_tables(
     let key_codec = Codec::new(k_columns);
     let values_codec = Codec::new(v_columns);
 
-    BTree::<Codec>::init(file_node, manager).context(error::AccessSnafu)?;
-    let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+    BTree::<Codec>::init(xid, file_node, manager).context(error::AccessSnafu)?;
+    let mut btree = BTree::new(key_codec, file_node, manager);
 
     let values = values_codec.encode(&values).unwrap();
 
-    btree.insert(&key, &values).unwrap();
+    btree
+        .bulk_load(xid, [(key, values)], DEFAULT_FILL_FACTOR)
+        .context(error::AccessSnafu)
+}
 
-    Ok(())
+fn create_tablespace_table(manager: &BufferManager, xid: Xid) -> Result<()> {
+    // in the order of ids, as the entries are loaded
+    let tablespaces = [
+        (meta::TABLESPACE_ID_DEFAULT, meta::TABLESPACE_NAME_DEFAULT),
+        (meta::TABLESPACE_ID_GLOBAL, meta::TABLESPACE_NAME_GLOBAL),
+    ];
+
+    let file_node = FileNode::global_meta(meta::Tablespace::TABLE_ID);
+
+    let mut columns = meta::Tablespace::columns();
+    let v_columns = columns.split_off(1);
+    let k_columns = columns;
+    let key_codec = Codec::new(k_columns);
+    let values_codec = Codec::new(v_columns);
+
+    BTree::<Codec>::init(xid, file_node, manager).context(error::AccessSnafu)?;
+    let mut btree = BTree::new(key_codec, file_node, manager);
+
+    let entries = tablespaces.map(|(id, name)| {
+        // the built-in tablespaces are in the data directory
+        let tablespace = meta::Tablespace::new(id, name.to_string(), String::new());
+        let mut kv: Vec<Value> = tablespace.into();
+        let values = kv.split_off(1);
+        let key = kv;
+
+        (key, values_codec.encode(&values).unwrap())
+    });
+
+    btree
+        .bulk_load(xid, entries, DEFAULT_FILL_FACTOR)
+        .context(error::AccessSnafu)
 }
 
-fn init_database(manager: &BufferManager, database_id: DatabaseId) -> Result<()> {
+fn init_database(manager: &BufferManager, xid: Xid, database_id: DatabaseId) -> Result<()> {
     {
         let tables = [meta::Table::table(), meta::Column::table()];
 
@@ -90,17 +148,20 @@ fn init_database(manager: &BufferManager, database_id: DatabaseId) -> Result<()>
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        BTree::<Codec>::init(file_node, manager).context(error::AccessSnafu)?;
-        let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        BTree::<Codec>::init(xid, file_node, manager).context(error::AccessSnafu)?;
+        let mut btree = BTree::new(key_codec, file_node, manager);
 
-        for table in tables {
+        let entries = tables.map(|table| {
             let mut kv: Vec<Value> = table.into();
             let values = kv.split_off(1);
             let key = kv;
 
-            let values = values_codec.encode(&values).unwrap();
-            btree.insert(&key, &values).unwrap();
-        }
+            (key, values_codec.encode(&values).unwrap())
+        });
+
+        btree
+            .bulk_load(xid, entries, DEFAULT_FILL_FACTOR)
+            .context(error::AccessSnafu)?;
     }
 
     {
@@ -120,17 +181,20 @@ fn init_database(manager: &BufferManager, database_id: DatabaseId) -> Result<()>
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        BTree::<Codec>::init(file_node, manager).context(error::AccessSnafu)?;
-        let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        BTree::<Codec>::init(xid, file_node, manager).context(error::AccessSnafu)?;
+        let mut btree = BTree::new(key_codec, file_node, manager);
 
-        columns.into_iter().flatten().for_each(|column| {
+        let entries = columns.into_iter().flatten().map(|column| {
             let mut kv: Vec<Value> = column.into();
             let values = kv.split_off(2);
             let key = kv;
 
-            let values = values_codec.encode(&values).unwrap();
-            btree.insert(&key, &values).unwrap();
+            (key, values_codec.encode(&values).unwrap())
         });
+
+        btree
+            .bulk_load(xid, entries, DEFAULT_FILL_FACTOR)
+            .context(error::AccessSnafu)?;
     }
 
     Ok(())
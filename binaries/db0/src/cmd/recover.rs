@@ -0,0 +1,79 @@
+use {
+    super::error::{self, Result},
+    access::{BTree, Codec},
+    def::{
+        meta::{self, MetaTable},
+        storage::Decoder,
+        DatabaseId, TableId, Value,
+    },
+    snafu::prelude::*,
+    storage::buffer::{BufferManager, FileNode},
+};
+
+/// Recovers the data directory from a crash, must be done before the catalog is read.
+/// The B-trees of the catalog and of every table in it are checked after the log is
+/// replayed, so that a data directory of another format is refused early.
+pub fn recover(manager: &BufferManager) -> Result<()> {
+    manager.recover().context(error::StorageSnafu)?;
+
+    let file_node = FileNode::global_meta(meta::Database::TABLE_ID);
+    BTree::<Codec>::check(file_node, manager).context(error::AccessSnafu)?;
+
+    let databases: Vec<meta::Database> =
+        load_records(manager, file_node, vec![Value::Uint(DatabaseId::MIN)])?;
+
+    let file_node = FileNode::global_meta(meta::Tablespace::TABLE_ID);
+    BTree::<Codec>::check(file_node, manager).context(error::AccessSnafu)?;
+
+    for database in databases {
+        let file_node = FileNode::new(
+            meta::TABLESPACE_ID_DEFAULT,
+            database.id,
+            meta::Table::TABLE_ID,
+        );
+        BTree::<Codec>::check(file_node, manager).context(error::AccessSnafu)?;
+
+        let tables: Vec<meta::Table> =
+            load_records(manager, file_node, vec![Value::Uint(TableId::MIN)])?;
+
+        for table in tables {
+            let file_node = FileNode::new(table.space_id, database.id, table.id);
+            BTree::<Codec>::check(file_node, manager).context(error::AccessSnafu)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the records of a catalog table keyed by its first column.
+fn load_records<T>(
+    manager: &BufferManager,
+    file_node: FileNode,
+    min_key: Vec<Value>,
+) -> Result<Vec<T>>
+where
+    T: MetaTable + TryFrom<Vec<Value>, Error = meta::error::Error>,
+{
+    let (key_codec, values_codec) = {
+        let mut columns = T::columns();
+        let v_columns = columns.split_off(1);
+        let k_columns = columns;
+
+        (Codec::new(k_columns), Codec::new(v_columns))
+    };
+
+    let btree = BTree::new(key_codec, file_node, manager);
+
+    let Some((cursor, _)) = btree.cursor(&min_key).context(error::AccessSnafu)? else {
+        return Ok(vec![]);
+    };
+
+    cursor
+        .map(|entry| {
+            let (key, values) = entry.context(error::AccessSnafu)?;
+            let (values, _) = values_codec.decode(&values).context(error::DecodingSnafu)?;
+
+            T::try_from([key, values].concat()).context(error::MetaDataSnafu)
+        })
+        .collect()
+}
@@ -1,14 +1,15 @@
 use {
-    crate::cmd::{create_meta_tables, Error},
+    crate::cmd::{create_meta_tables, create_meta_tables_with_options, Error},
     access::{BTree, Codec},
     def::{
         meta::{self, MetaTable},
-        storage::{Decoder, Encoder},
+        storage::Decoder,
         Value,
     },
     storage::{
         buffer::{BufferManager, FileNode},
-        DEFAULT_PAGE_SIZE,
+        control::ControlFile,
+        store::{FileOptions, IoMode, SyncPolicy},
     },
     tempfile::tempdir,
 };
@@ -19,9 +20,9 @@ fn it_works() -> Result<(), Error> {
     let temp_dir = tempdir().unwrap();
     let path = temp_dir.path();
 
-    create_meta_tables(path).unwrap();
+    create_meta_tables(path, &ControlFile::default()).unwrap();
 
-    let manager = BufferManager::new(100, DEFAULT_PAGE_SIZE, path.to_path_buf());
+    let manager = BufferManager::new(100, path.to_path_buf()).unwrap();
 
     let file_node = FileNode::new(meta::TABLESPACE_ID_DEFAULT, 1, meta::Table::TABLE_ID);
 
@@ -33,12 +34,12 @@ fn it_works() -> Result<(), Error> {
         (Codec::new(k_columns), Codec::new(v_columns))
     };
 
-    let btree = BTree::new(key_codec, values_codec.max_size(), file_node, &manager);
+    let btree = BTree::new(key_codec, file_node, &manager);
 
     let key = vec![Value::Uint(meta::Column::TABLE_ID)];
     let (mut cursor, is_matched) = btree.cursor(&key).unwrap().unwrap();
 
-    let (_, values) = cursor.next().unwrap();
+    let (_, values) = cursor.next().unwrap().unwrap();
 
     assert!(is_matched);
 
@@ -51,3 +52,37 @@ fn it_works() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn initialized_data_directory() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    // the settings the relations were written with are kept
+    let control = ControlFile {
+        checksums: true,
+        page_size: 1 << 14,
+    };
+    assert!(matches!(
+        create_meta_tables(path, &control),
+        Err(Error::Initialized { .. })
+    ));
+    assert_eq!(ControlFile::open(path).unwrap(), ControlFile::default());
+}
+
+#[test]
+fn file_options() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    let options = FileOptions {
+        io_mode: IoMode::Buffered,
+        sync_policy: SyncPolicy::Always,
+    };
+    create_meta_tables_with_options(path, &ControlFile::default(), options).unwrap();
+
+    let manager = BufferManager::with_options(100, path.to_path_buf(), options).unwrap();
+    crate::cmd::recover(&manager).unwrap();
+}
@@ -1,8 +1,13 @@
 use {
-    clap::{arg, Command},
+    clap::{arg, Arg, ArgMatches, Command},
     db0::cmd::{self, Error as ExecutionError},
     snafu::prelude::*,
     std::{env, path::PathBuf, process, str::FromStr},
+    storage::{
+        buffer::{BufferManager, Error as StorageError},
+        control::ControlFile,
+        store::{FileOptions, IoMode, SyncPolicy},
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -14,6 +19,12 @@ pub enum Error {
         #[snafu(backtrace)]
         source: ExecutionError,
     },
+
+    #[snafu(display("Failed with storage error, source: {}", source))]
+    Storage {
+        #[snafu(backtrace)]
+        source: StorageError,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -21,6 +32,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 const DB0_DATADIR: &str = "DB0_DATADIR";
 
 const INIT_DATABASE: &str = "initdb";
+const RECOVER: &str = "recover";
 
 fn cli() -> Command {
     let pkg_name = env!("CARGO_PKG_NAME");
@@ -32,10 +44,39 @@ fn cli() -> Command {
         .subcommand(
             Command::new(INIT_DATABASE)
                 .about("initialize the data directory")
-                .arg(arg!(-d --data_dir <PATH> "data directory")),
+                .arg(arg!(-d --data_dir <PATH> "data directory"))
+                .arg(arg!(-k --data_checksums "checksum pages to detect corruption"))
+                .arg(
+                    arg!(-p --page_size <KIB> "page size in KiB")
+                        .value_parser(["4", "8", "16", "32"])
+                        .default_value("4"),
+                )
+                .arg(io_mode_arg())
+                .arg(sync_arg()),
+        )
+        .subcommand(
+            Command::new(RECOVER)
+                .about("recover the data directory after a crash")
+                .arg(arg!(-d --data_dir <PATH> "data directory"))
+                .arg(io_mode_arg())
+                .arg(sync_arg()),
         )
 }
 
+/// How data files are read and written, taken by every command opening the data directory.
+fn io_mode_arg() -> Arg {
+    arg!(-i --io_mode <MODE> "how data files are read and written")
+        .value_parser(["auto", "direct", "buffered"])
+        .default_value("auto")
+}
+
+/// When writes are synced, taken by every command opening the data directory.
+fn sync_arg() -> Arg {
+    arg!(-s --sync <POLICY> "when writes are synced to disk")
+        .value_parser(["always", "commit", "never"])
+        .default_value("commit")
+}
+
 fn main() {
     if let Err(err) = try_main() {
         eprintln!("{}", err);
@@ -47,17 +88,58 @@ fn try_main() -> Result<()> {
     let matches = cli().get_matches();
     match matches.subcommand() {
         Some((INIT_DATABASE, sub_matches)) => {
-            let data_dir = sub_matches.get_one::<String>("data_dir");
-            let data_dir = match data_dir {
-                Some(dir) => dir.into(),
-                None => env::var(DB0_DATADIR).map_err(|_| Error::NoDataDirectory)?,
+            let data_dir = data_dir(sub_matches)?;
+
+            let page_size = sub_matches.get_one::<String>("page_size").unwrap();
+            let control = ControlFile {
+                checksums: sub_matches.get_flag("data_checksums"),
+                page_size: page_size.parse::<usize>().unwrap() << 10,
             };
 
-            cmd::create_meta_tables(PathBuf::from_str(&data_dir).unwrap().as_path())
-                .context(ExecuteCommandSnafu)?;
+            cmd::create_meta_tables_with_options(
+                data_dir.as_path(),
+                &control,
+                options(sub_matches),
+            )
+            .context(ExecuteCommandSnafu)?;
+        }
+        Some((RECOVER, sub_matches)) => {
+            let data_dir = data_dir(sub_matches)?;
+
+            let manager = BufferManager::with_options(100, data_dir, options(sub_matches))
+                .context(StorageSnafu)?;
+            cmd::recover(&manager).context(ExecuteCommandSnafu)?;
         }
         _ => unreachable!(),
     }
 
     Ok(())
 }
+
+fn data_dir(matches: &ArgMatches) -> Result<PathBuf> {
+    let data_dir = match matches.get_one::<String>("data_dir") {
+        Some(dir) => dir.into(),
+        None => env::var(DB0_DATADIR).map_err(|_| Error::NoDataDirectory)?,
+    };
+
+    Ok(PathBuf::from_str(&data_dir).unwrap())
+}
+
+fn options(matches: &ArgMatches) -> FileOptions {
+    let io_mode = match matches.get_one::<String>("io_mode").unwrap().as_str() {
+        "direct" => IoMode::Direct,
+        "buffered" => IoMode::Buffered,
+        _ => IoMode::Auto,
+    };
+
+    let sync_policy = match matches.get_one::<String>("sync").unwrap().as_str() {
+        "always" => SyncPolicy::Always,
+        "never" => SyncPolicy::Never,
+        _ => SyncPolicy::Commit,
+    };
+
+    FileOptions {
+        io_mode,
+        sync_policy,
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    binder::Binder,
+    def::Value,
+    executor::Executor,
+    parser::Parser,
+    semantic_analyzer::Analyzer,
+    std::{
+        env,
+        io::{BufRead, BufReader, Read, Write},
+        path::Path,
+        process::{Command, Stdio},
+        sync::{Arc, RwLock},
+        thread,
+        time::Duration,
+    },
+    storage::{buffer::BufferManager, control::ControlFile},
+    tempfile::tempdir,
+};
+
+const DATA_DIR: &str = "DB0_CRASH_DATA_DIR";
+const FIRST_STATEMENT: &str = "DB0_CRASH_FIRST_STATEMENT";
+const ROWS_PER_STATEMENT: usize = 1000;
+const STATEMENTS_BEFORE_KILL: usize = 3;
+const CRASHES: u64 = 6;
+
+fn execute(manager: &BufferManager, sql: &str) -> Vec<Vec<Value>> {
+    let binder = Binder::new(1, manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    Parser::parse(sql)
+        .unwrap()
+        .into_iter()
+        .flat_map(|stmt| {
+            let stmt = analyzer.analyze(stmt).unwrap();
+            executor.execute(stmt, manager).unwrap()
+        })
+        .collect()
+}
+
+/// Inserts rows until it is killed, the index of each committed statement is printed.
+#[test]
+#[ignore = "run by `crash_during_inserts`"]
+fn insert_until_killed() {
+    let (Ok(data_dir), Ok(first)) = (env::var(DATA_DIR), env::var(FIRST_STATEMENT)) else {
+        return;
+    };
+    let first: usize = first.parse().unwrap();
+
+    // a small pool, so that pages of unfinished statements are written out
+    let manager = BufferManager::new(8, data_dir.into()).unwrap();
+
+    for i in first.. {
+        let rows = (i * ROWS_PER_STATEMENT..(i + 1) * ROWS_PER_STATEMENT)
+            .map(|a| format!("({}, {})", a, a % 2 == 0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        execute(
+            &manager,
+            &format!("INSERT INTO abc (a, b) VALUES {};", rows),
+        );
+
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", i).unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+/// Runs `insert_until_killed` from the `first` statement, and kills it some time after it
+/// has committed a few statements.
+fn spawn_and_kill(data_dir: &Path, first: usize, delay: Duration) {
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args([
+            "crash::insert_until_killed",
+            "--exact",
+            "--ignored",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(DATA_DIR, data_dir)
+        .env(FIRST_STATEMENT, first.to_string())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the pipe is kept open until the process is killed, otherwise its writes fail
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let committed = stdout
+        .by_ref()
+        .lines()
+        .map(|line| line.unwrap())
+        .filter(|line| line.parse::<usize>().is_ok())
+        .take(STATEMENTS_BEFORE_KILL)
+        .count();
+    assert_eq!(committed, STATEMENTS_BEFORE_KILL);
+
+    thread::sleep(delay);
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn crash_during_inserts() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(
+        path,
+        &ControlFile {
+            checksums: true,
+            page_size: 1 << 13,
+        },
+    )
+    .unwrap();
+    {
+        let manager = BufferManager::new(100, path.to_path_buf()).unwrap();
+        execute(&manager, "CREATE TABLE abc (a int PRIMARY KEY, b boolean);");
+        manager.flush_pages().unwrap();
+    }
+
+    let mut committed = 0;
+    // the process is killed at a different point of a statement each time
+    for crash in 0..CRASHES {
+        spawn_and_kill(path, committed, Duration::from_millis(crash * 20));
+
+        let manager = BufferManager::new(100, path.to_path_buf()).unwrap();
+        db0::cmd::recover(&manager).unwrap();
+
+        let rows = execute(&manager, "SELECT a FROM abc;");
+
+        // only whole statements survive, and every committed one does
+        assert_eq!(rows.len() % ROWS_PER_STATEMENT, 0);
+        assert!(rows.len() / ROWS_PER_STATEMENT >= committed + STATEMENTS_BEFORE_KILL);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row, &vec![Value::Int(i as i32)]);
+        }
+
+        committed = rows.len() / ROWS_PER_STATEMENT;
+    }
+
+    temp_dir.close().unwrap()
+}
@@ -0,0 +1,368 @@
+use {
+    access::{BTree, Codec},
+    binder::Binder,
+    def::{meta::Column, SqlType, Value},
+    executor::Executor,
+    parser::Parser,
+    semantic_analyzer::Analyzer,
+    std::{
+        collections::BTreeSet,
+        ops::Range,
+        path::PathBuf,
+        sync::{Arc, RwLock},
+    },
+    storage::{
+        buffer::{BufferManager, FileNode, WAL_DIR},
+        control::ControlFile,
+        store::{Fault, FaultyStore, FileStore},
+        PageNum,
+    },
+    tempfile::tempdir,
+    wal::Wal,
+};
+
+/// A small pool, so that pages are written out in the middle of statements.
+const POOL_SIZE: usize = 8;
+
+type Written = Vec<(PathBuf, PageNum)>;
+
+/// A workload made of statements, each of which either commits or is stopped by a fault.
+trait Workload {
+    fn steps(&self) -> usize;
+
+    /// Runs a statement, fails if a fault is hit.
+    fn run(&self, manager: &BufferManager, step: usize) -> Result<(), String>;
+
+    /// Checks the data directory once recovered, the first `committed` statements
+    /// have committed, and no other one has.
+    fn check(&self, manager: &BufferManager, committed: usize);
+}
+
+/// Replays the workload on a new data directory, with the faults injected into the writes
+/// of pages. The store crashes at the first fault hit, and the data directory is then
+/// reopened, recovered and checked. Returns the pages written until the crash.
+fn replay(workload: &impl Workload, schedule: &[(usize, Fault)]) -> Written {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    let control = ControlFile {
+        checksums: true,
+        ..Default::default()
+    };
+    db0::cmd::create_meta_tables(path, &control).unwrap();
+
+    let written = {
+        let store = Arc::new(FaultyStore::new(
+            FileStore::new(path.to_path_buf(), control.page_size),
+            schedule.iter().copied(),
+        ));
+        let wal = Wal::open(&path.join(WAL_DIR)).unwrap();
+        let manager = BufferManager::with_store(POOL_SIZE, Box::new(store.clone()), wal, &control);
+
+        let mut committed = 0;
+        for step in 0..workload.steps() {
+            if workload.run(&manager, step).is_err() {
+                break;
+            }
+            committed += 1;
+
+            // the checkpoint syncs the writes held back
+            if manager.checkpoint().is_err() {
+                break;
+            }
+        }
+
+        store.crash();
+
+        let manager = BufferManager::new(POOL_SIZE, path.to_path_buf()).unwrap();
+        db0::cmd::recover(&manager).unwrap();
+        workload.check(&manager, committed);
+
+        store.written()
+    };
+
+    temp_dir.close().unwrap();
+
+    written
+}
+
+/// The faults tried at a write: the write fails, it tears the page, or it is lost by
+/// a crash after later writes have reached the disk.
+fn faults_at(write: usize, page_size: usize) -> [Vec<(usize, Fault)>; 3] {
+    [
+        vec![(write, Fault::Fail)],
+        vec![(write, Fault::ShortWrite(page_size / 2))],
+        vec![(write, Fault::Delay), (write + 2, Fault::Fail)],
+    ]
+}
+
+struct SqlWorkload;
+
+const SQL_STATEMENTS: usize = 4;
+const ROWS_PER_STATEMENT: usize = 100;
+
+impl SqlWorkload {
+    fn execute(manager: &BufferManager, sql: &str) -> Result<Vec<Vec<Value>>, String> {
+        let binder = Binder::new(1, manager).map_err(|e| format!("{:?}", e))?;
+        let binder = Arc::new(RwLock::new(binder));
+        let analyzer = Analyzer::new(binder.clone());
+        let executor = Executor::new(1, binder);
+
+        let mut rows = vec![];
+        for stmt in Parser::parse(sql).unwrap() {
+            let stmt = analyzer.analyze(stmt).map_err(|e| format!("{:?}", e))?;
+            rows.extend(
+                executor
+                    .execute(stmt, manager)
+                    .map_err(|e| format!("{:?}", e))?,
+            );
+        }
+
+        Ok(rows)
+    }
+}
+
+impl Workload for SqlWorkload {
+    fn steps(&self) -> usize {
+        1 + SQL_STATEMENTS
+    }
+
+    fn run(&self, manager: &BufferManager, step: usize) -> Result<(), String> {
+        if step == 0 {
+            return Self::execute(manager, "CREATE TABLE abc (a int PRIMARY KEY, b boolean);")
+                .map(drop);
+        }
+
+        let first = (step - 1) * ROWS_PER_STATEMENT;
+        let rows = (first..first + ROWS_PER_STATEMENT)
+            .map(|a| format!("({}, {})", a, a % 2 == 0))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Self::execute(manager, &format!("INSERT INTO abc (a, b) VALUES {};", rows)).map(drop)
+    }
+
+    fn check(&self, manager: &BufferManager, committed: usize) {
+        let rows = Self::execute(manager, "SELECT a FROM abc;");
+        if committed <= 1 {
+            // the table may never have been created
+            assert!(rows.map_or(true, |rows| rows.is_empty()));
+            return;
+        }
+
+        let rows = rows.unwrap();
+        assert_eq!(rows.len(), (committed - 1) * ROWS_PER_STATEMENT);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row, &vec![Value::Int(i as i32)]);
+        }
+    }
+}
+
+/// Inserts into a B-tree directly, with values big enough for few entries to fit in a
+/// node, so that the root is split several times.
+struct BTreeWorkload {
+    file_node: FileNode,
+}
+
+const BTREE_STATEMENTS: usize = 8;
+const KEYS_PER_STATEMENT: u32 = 100;
+const VALUE_SIZE: usize = 100;
+
+impl BTreeWorkload {
+    fn new() -> Self {
+        Self {
+            file_node: FileNode::new(1, 2, 3),
+        }
+    }
+
+    fn btree<'a>(&self, manager: &'a BufferManager) -> BTree<'a, Codec> {
+        let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+
+        BTree::new(Codec::new(vec![column]), self.file_node, manager)
+    }
+
+    /// The keys inserted by a statement.
+    fn keys(step: usize) -> Range<u32> {
+        let first = (step as u32 - 1) * KEYS_PER_STATEMENT;
+
+        first..first + KEYS_PER_STATEMENT
+    }
+
+    fn value(key: u32) -> Vec<u8> {
+        vec![key as u8; VALUE_SIZE]
+    }
+}
+
+impl Workload for BTreeWorkload {
+    fn steps(&self) -> usize {
+        1 + BTREE_STATEMENTS
+    }
+
+    fn run(&self, manager: &BufferManager, step: usize) -> Result<(), String> {
+        let xid = manager.wal().begin().map_err(|e| format!("{:?}", e))?;
+
+        if step == 0 {
+            BTree::<Codec>::init(xid, self.file_node, manager).map_err(|e| format!("{:?}", e))?;
+        } else {
+            let mut btree = self.btree(manager);
+            for key in Self::keys(step) {
+                btree
+                    .insert(xid, &vec![Value::Uint(key)], &Self::value(key))
+                    .map_err(|e| format!("{:?}", e))?;
+            }
+        }
+
+        manager.wal().commit(xid).map_err(|e| format!("{:?}", e))
+    }
+
+    fn check(&self, manager: &BufferManager, committed: usize) {
+        // nothing has been inserted
+        if committed <= 1 {
+            return;
+        }
+        BTree::<Codec>::check(self.file_node, manager).unwrap();
+
+        let btree = self.btree(manager);
+        let keys = (1..committed).flat_map(Self::keys).collect::<Vec<_>>();
+
+        for &key in &keys {
+            let (mut cursor, is_matched) = btree.cursor(&vec![Value::Uint(key)]).unwrap().unwrap();
+            assert!(is_matched);
+            assert_eq!(cursor.next().unwrap().unwrap().1, Self::value(key));
+        }
+
+        let (cursor, _) = btree.cursor(&vec![Value::Uint(0)]).unwrap().unwrap();
+        let scanned = cursor
+            .map(|entry| {
+                let (key, _) = entry.unwrap();
+                match key[..] {
+                    [Value::Uint(key)] => key,
+                    _ => panic!("unexpected key {:?}", key),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(scanned, keys);
+    }
+}
+
+#[test]
+fn faults_during_sql_statements() {
+    let workload = SqlWorkload;
+
+    let written = replay(&workload, &[]);
+
+    // a sample of the writes, throughout the workload
+    let stride = written.len() / 8 + 1;
+    for write in (0..written.len()).step_by(stride) {
+        for schedule in faults_at(write, ControlFile::default().page_size) {
+            replay(&workload, &schedule);
+        }
+    }
+}
+
+#[test]
+fn faults_during_root_splits() {
+    let workload = BTreeWorkload::new();
+    let path = workload.file_node.file_path();
+
+    let written = replay(&workload, &[]);
+
+    // root splits write a new root and the meta page, so the writes around every write
+    // of the meta page are hit
+    let meta_writes = written
+        .iter()
+        .enumerate()
+        .filter(|(_, (file_path, page_num))| file_path == &path && *page_num == 0)
+        .map(|(write, _)| write)
+        .collect::<Vec<_>>();
+    assert!(meta_writes.len() > 2);
+
+    let writes = meta_writes
+        .into_iter()
+        .flat_map(|write| write.saturating_sub(1)..=write + 1)
+        .collect::<BTreeSet<_>>();
+    for write in writes {
+        for schedule in faults_at(write, ControlFile::default().page_size) {
+            replay(&workload, &schedule);
+        }
+    }
+}
+
+/// Runs a statement which a fault may stop, then one changing the same leaves, without
+/// a crash in between. Returns the count of writes before the first statement and after
+/// it, and the rows committed before the data directory is reopened and after.
+fn fail_statement(schedule: &[(usize, Fault)]) -> ((usize, usize), [Vec<Vec<Value>>; 2]) {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    let control = ControlFile::default();
+    db0::cmd::create_meta_tables(path, &control).unwrap();
+
+    let insert = |manager: &BufferManager, keys: &mut dyn Iterator<Item = usize>| {
+        let rows = keys
+            .map(|a| format!("({}, '{}')", a, "b".repeat(200)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        SqlWorkload::execute(manager, &format!("INSERT INTO abc (a, b) VALUES {};", rows))
+    };
+    let select = |manager: &BufferManager| SqlWorkload::execute(manager, "SELECT a FROM abc;");
+
+    let store = Arc::new(FaultyStore::new(
+        FileStore::new(path.to_path_buf(), control.page_size),
+        schedule.iter().copied(),
+    ));
+    let (writes, rows) = {
+        let wal = Wal::open(&path.join(WAL_DIR)).unwrap();
+        let manager = BufferManager::with_store(POOL_SIZE, Box::new(store.clone()), wal, &control);
+
+        SqlWorkload::execute(
+            &manager,
+            "CREATE TABLE abc (a int PRIMARY KEY, b varchar(250));",
+        )
+        .unwrap();
+        insert(&manager, &mut (0..200).step_by(2)).unwrap();
+        manager.checkpoint().unwrap();
+
+        let before = store.written().len();
+        let is_failed = insert(&manager, &mut (1..200).step_by(2)).is_err();
+        let after = store.written().len();
+        assert_eq!(is_failed, !schedule.is_empty());
+
+        if is_failed {
+            // the keys are free again once the failed statement is undone
+            insert(&manager, &mut [1, 199].into_iter()).unwrap();
+        }
+
+        ((before, after), select(&manager).unwrap())
+    };
+    store.crash();
+
+    let manager = BufferManager::new(POOL_SIZE, path.to_path_buf()).unwrap();
+    db0::cmd::recover(&manager).unwrap();
+    let recovered = select(&manager).unwrap();
+
+    drop(manager);
+    temp_dir.close().unwrap();
+
+    (writes, [rows, recovered])
+}
+
+#[test]
+fn failed_statement_is_undone() {
+    let ((before, after), _) = fail_statement(&[]);
+    assert!(after - before > 2);
+
+    // the statement fails halfway, its rows are undone without touching those of the next
+    // statement, in the leaves both have changed
+    let (_, rows) = fail_statement(&[((before + after) / 2, Fault::Fail)]);
+
+    let keys = (0..200).step_by(2).chain([1, 199]).collect::<BTreeSet<_>>();
+    for rows in rows {
+        assert_eq!(
+            rows,
+            keys.iter()
+                .map(|&a| vec![Value::Int(a)])
+                .collect::<Vec<_>>()
+        );
+    }
+}
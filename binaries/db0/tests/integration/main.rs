@@ -1,11 +1,22 @@
+mod crash;
+mod faults;
+
 use {
     binder::Binder,
+    bound_ast::{ComparisonOp, OrderBy, Predicate, QueryTarget, Statement},
     db0,
+    def::{
+        meta::{MetaTableId, TABLESPACE_ID_DEFAULT},
+        Value,
+    },
     executor::Executor,
     parser::Parser,
     semantic_analyzer::Analyzer,
     std::sync::{Arc, RwLock},
-    storage::{buffer::BufferManager, DEFAULT_PAGE_SIZE},
+    storage::{
+        buffer::{BufferManager, FileNode},
+        control::ControlFile,
+    },
     tempfile::tempdir,
 };
 
@@ -14,9 +25,9 @@ fn insert_and_query() {
     let temp_dir = tempdir().unwrap();
     let path = temp_dir.path();
 
-    db0::cmd::create_meta_tables(path).unwrap();
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
 
-    let manager = BufferManager::new(100, DEFAULT_PAGE_SIZE, path.to_path_buf());
+    let manager = BufferManager::new(100, path.to_path_buf()).unwrap();
     let binder = Binder::new(1, &manager).unwrap();
     let binder = Arc::new(RwLock::new(binder));
     let analyzer = Analyzer::new(binder.clone());
@@ -38,3 +49,515 @@ fn insert_and_query() {
 
     temp_dir.close().unwrap()
 }
+
+#[test]
+fn query_buffer_pool() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    let sql = "
+        CREATE TABLE abc (a int PRIMARY KEY, b boolean);
+        INSERT INTO abc (a, b) VALUES (1, true);
+        SELECT a FROM abc;
+        SELECT buffer_id, table_id, pin_count FROM db0_buffers;
+        SELECT table_id, hits, misses FROM db0_buffer_usage;
+    ";
+    let mut results = Parser::parse(sql)
+        .unwrap()
+        .into_iter()
+        .map(|stmt| {
+            let stmt = analyzer.analyze(stmt).unwrap();
+            executor.execute(stmt, &manager).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // the first table created takes the first id after the reserved ones
+    let table_id = Value::Uint(MetaTableId::Reserved as u32 + 1);
+    let usage = results.pop().unwrap();
+    let buffers = results.pop().unwrap();
+
+    // a row for every frame, none of which is pinned between statements
+    assert_eq!(buffers.len(), 16);
+    for (i, row) in buffers.iter().enumerate() {
+        assert_eq!(row[0], Value::Uint(i as u32));
+        assert_eq!(row[2], Value::Uint(0));
+    }
+    assert!(buffers.iter().any(|row| row[1] == table_id));
+
+    let table_usage = usage.iter().find(|row| row[0] == table_id).unwrap();
+    assert!(matches!(table_usage[1], Value::BigUint(hits) if hits > 0));
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn large_values() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    let document = "lorem ipsum ".repeat(1500);
+    let sql = format!(
+        "
+        CREATE TABLE docs (a int PRIMARY KEY, b varchar(20000), c varchar(20));
+        INSERT INTO docs (a, b, c) VALUES (1, '{document}', 'first');
+        INSERT INTO docs (a, b, c) VALUES (2, 'short', 'second');
+        SELECT a, b, c FROM docs;
+    "
+    );
+    let results = Parser::parse(&sql)
+        .unwrap()
+        .into_iter()
+        .map(|stmt| {
+            let stmt = analyzer.analyze(stmt).unwrap();
+            executor.execute(stmt, &manager).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        results[3],
+        [
+            [
+                Value::Int(1),
+                Value::String(document),
+                Value::String("first".to_string())
+            ],
+            [
+                Value::Int(2),
+                Value::String("short".to_string()),
+                Value::String("second".to_string())
+            ],
+        ]
+    );
+}
+
+#[test]
+fn broken_overflow_pages() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    let control = ControlFile {
+        checksums: true,
+        ..Default::default()
+    };
+    db0::cmd::create_meta_tables(path, &control).unwrap();
+
+    let execute = |manager: &BufferManager, sql: &str| {
+        let binder = Arc::new(RwLock::new(Binder::new(1, manager).unwrap()));
+        let analyzer = Analyzer::new(binder.clone());
+        let executor = Executor::new(1, binder.clone());
+
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        let rows = executor.execute(analyzer.analyze(stmt).unwrap(), manager);
+        let table = binder.read().unwrap().resolve_table("docs".to_string());
+
+        (rows, table)
+    };
+
+    let table = {
+        let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+        execute(
+            &manager,
+            "CREATE TABLE docs (a int PRIMARY KEY, b varchar(20000))",
+        )
+        .0
+        .unwrap();
+        let document = "lorem ipsum ".repeat(1500);
+        let sql = format!("INSERT INTO docs (a, b) VALUES (1, '{document}')");
+        let (rows, table) = execute(&manager, &sql);
+        rows.unwrap();
+        manager.checkpoint().unwrap();
+
+        table.unwrap()
+    };
+
+    // the value is written out of line before the root leaf is made, from the first page
+    // after the meta page on
+    let file_path = path.join(FileNode::new(TABLESPACE_ID_DEFAULT, 1, table).file_path());
+    let mut data = std::fs::read(&file_path).unwrap();
+    data[control.page_size + control.page_size / 2] ^= 0xff;
+    std::fs::write(&file_path, data).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    assert!(execute(&manager, "SELECT a, b FROM docs").0.is_err());
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn duplicate_keys() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    let execute = |sql: &str| {
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        executor.execute(analyzer.analyze(stmt).unwrap(), &manager)
+    };
+
+    execute("CREATE TABLE abc (a int PRIMARY KEY, b boolean)").unwrap();
+    execute("INSERT INTO abc (a, b) VALUES (1, true)").unwrap();
+
+    // a key taken by a row, or by another row of the statement
+    for sql in [
+        "INSERT INTO abc (a, b) VALUES (2, false), (1, false)",
+        "INSERT INTO abc (a, b) VALUES (3, false), (3, true)",
+    ] {
+        let error = execute(sql).unwrap_err();
+        assert!(matches!(error, executor::Error::Insert { .. }));
+        assert!(std::error::Error::source(&error)
+            .unwrap()
+            .to_string()
+            .starts_with("duplicate key"));
+    }
+
+    // and nothing is written by the statements failed
+    assert_eq!(
+        execute("SELECT a, b FROM abc").unwrap(),
+        [[Value::Int(1), Value::Boolean(true)]]
+    );
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn conditions_and_order() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    let execute = |sql: &str| {
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        executor.execute(analyzer.analyze(stmt).unwrap(), &manager)
+    };
+    let keys = |sql: &str| {
+        execute(sql)
+            .unwrap()
+            .into_iter()
+            .map(|row| match row[0] {
+                Value::Int(a) => a,
+                _ => panic!("unexpected row {:?}", row),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    execute("CREATE TABLE abc (a int PRIMARY KEY, b int)").unwrap();
+    let rows = (0..300)
+        .map(|a| format!("({a}, {})", a % 7))
+        .collect::<Vec<_>>()
+        .join(", ");
+    execute(&format!("INSERT INTO abc (a, b) VALUES {rows}")).unwrap();
+
+    assert_eq!(
+        keys("SELECT a FROM abc WHERE a >= 100 AND a < 110"),
+        (100..110).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys("SELECT a FROM abc WHERE 290 < a ORDER BY a DESC"),
+        (291..300).rev().collect::<Vec<_>>()
+    );
+    assert_eq!(keys("SELECT a FROM abc WHERE a = 42 AND a <= 42"), [42]);
+    assert!(keys("SELECT a FROM abc WHERE a > 50 AND a < 40").is_empty());
+
+    // conditions and orders on other columns apply to the rows read
+    assert_eq!(
+        keys("SELECT a FROM abc WHERE a < 20 AND b = 3 ORDER BY a DESC"),
+        [17, 10, 3]
+    );
+    assert_eq!(
+        keys("SELECT a FROM abc WHERE a < 10 ORDER BY b DESC, a"),
+        [6, 5, 4, 3, 2, 9, 1, 8, 0, 7]
+    );
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn bind_conditions_and_order() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder.clone());
+
+    let analyze = |sql: &str| analyzer.analyze(Parser::parse(sql).unwrap().pop().unwrap());
+
+    let stmt = analyze("CREATE TABLE abc (a int PRIMARY KEY, b int)").unwrap();
+    executor.execute(stmt, &manager).unwrap();
+    let table = binder
+        .read()
+        .unwrap()
+        .resolve_table("abc".to_string())
+        .unwrap();
+    let target = |column| QueryTarget { table, column };
+
+    let Statement::Select(query) =
+        analyze("SELECT a FROM abc WHERE a >= 100 AND 7 > b ORDER BY b DESC, a").unwrap()
+    else {
+        panic!("not a query");
+    };
+    // the literal of the second comparison is moved to the right
+    assert_eq!(
+        query.predicates,
+        [
+            Predicate {
+                target: target(1),
+                op: ComparisonOp::GreaterThanOrEqual,
+                value: Value::Int(100),
+            },
+            Predicate {
+                target: target(2),
+                op: ComparisonOp::LessThan,
+                value: Value::Int(7),
+            },
+        ]
+    );
+    assert_eq!(
+        query.order_by,
+        [
+            OrderBy {
+                target: target(2),
+                desc: true,
+            },
+            OrderBy {
+                target: target(1),
+                desc: false,
+            },
+        ]
+    );
+
+    // only comparisons of columns with literals, joined by AND, are bound
+    for sql in [
+        "SELECT a FROM abc WHERE a = 1 OR a = 2",
+        "SELECT a FROM abc WHERE a = b",
+        "SELECT a FROM abc ORDER BY a + 1",
+    ] {
+        let error = analyze(sql).unwrap_err();
+        assert!(format!("{:?}", error).contains("Unsupported"), "{}", sql);
+    }
+
+    // the literal must fit the type of the column
+    let error = analyze("SELECT a FROM abc WHERE b = 'x'").unwrap_err();
+    assert!(format!("{:?}", error).contains("InvalidValue"));
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn resize_buffer_pool() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder);
+
+    let sql = "
+        CREATE TABLE abc (a int PRIMARY KEY, b boolean);
+        INSERT INTO abc (a, b) VALUES (1, true);
+        ALTER SYSTEM SET buffer_pool_size = 32;
+        SELECT buffer_id FROM db0_buffers;
+        ALTER SYSTEM SET buffer_pool_size = 4;
+        SELECT buffer_id FROM db0_buffers;
+        SELECT a, b FROM abc;
+    ";
+    let results = Parser::parse(sql)
+        .unwrap()
+        .into_iter()
+        .map(|stmt| {
+            let stmt = analyzer.analyze(stmt).unwrap();
+            executor.execute(stmt, &manager).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(results[3].len(), 32);
+    assert_eq!(results[5].len(), 4);
+    assert_eq!(results[6], [[Value::Int(1), Value::Boolean(true)]]);
+
+    for sql in [
+        "ALTER SYSTEM SET buffer_pool = 8",
+        "ALTER SYSTEM SET buffer_pool_size = 0",
+    ] {
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        assert!(analyzer.analyze(stmt).is_err());
+    }
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn tablespaces() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+    let location = tempdir().unwrap();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder.clone());
+
+    let sql = format!(
+        "
+        CREATE TABLESPACE fast LOCATION '{}';
+        CREATE TABLE hot (a int PRIMARY KEY, b boolean) TABLESPACE fast;
+        INSERT INTO hot (a, b) VALUES (1, true);
+        SELECT a, b FROM hot;
+        CREATE TABLESPACE spare LOCATION '{}';
+        DROP TABLESPACE spare;
+    ",
+        location.path().display(),
+        location.path().join("spare").display(),
+    );
+    let results = Parser::parse(&sql)
+        .unwrap()
+        .into_iter()
+        .map(|stmt| {
+            let stmt = analyzer.analyze(stmt).unwrap();
+            executor.execute(stmt, &manager).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(results[3], [[Value::Int(1), Value::Boolean(true)]]);
+
+    // the table is kept at the location, under the database
+    manager.flush_pages().unwrap();
+    let table_id = binder
+        .read()
+        .unwrap()
+        .resolve_table("hot".to_string())
+        .unwrap();
+    assert!(location
+        .path()
+        .join("1")
+        .join(table_id.to_string())
+        .exists());
+    // the location of a tablespace dropped is left, empty
+    assert!(!path.join(FileNode::tablespace_dir(3)).exists());
+    assert!(location
+        .path()
+        .join("spare")
+        .read_dir()
+        .unwrap()
+        .next()
+        .is_none());
+
+    // a tablespace with tables in it is not dropped
+    let stmt = Parser::parse("DROP TABLESPACE fast")
+        .unwrap()
+        .pop()
+        .unwrap();
+    let stmt = analyzer.analyze(stmt).unwrap();
+    assert!(executor.execute(stmt, &manager).is_err());
+
+    for sql in [
+        "CREATE TABLESPACE relative LOCATION 'tablespaces/relative'",
+        "CREATE TABLE cold (a int PRIMARY KEY) TABLESPACE missing",
+        "DROP TABLESPACE db0_default",
+    ] {
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        assert!(analyzer.analyze(stmt).is_err());
+    }
+
+    // tablespaces are loaded along with the catalog
+    let binder = Binder::new(1, &manager).unwrap();
+    assert!(binder.get_tablespace("fast").is_some());
+    assert!(binder.get_tablespace("spare").is_none());
+
+    temp_dir.close().unwrap()
+}
+
+#[test]
+fn failed_create_table() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path();
+    let location = tempdir().unwrap();
+
+    db0::cmd::create_meta_tables(path, &ControlFile::default()).unwrap();
+
+    let manager = BufferManager::new(16, path.to_path_buf()).unwrap();
+    let binder = Binder::new(1, &manager).unwrap();
+    let binder = Arc::new(RwLock::new(binder));
+    let analyzer = Analyzer::new(binder.clone());
+    let executor = Executor::new(1, binder.clone());
+
+    let execute = |sql: &str| {
+        let stmt = Parser::parse(sql).unwrap().pop().unwrap();
+        executor.execute(analyzer.analyze(stmt).unwrap(), &manager)
+    };
+
+    execute(&format!(
+        "CREATE TABLESPACE broken LOCATION '{}'",
+        location.path().display()
+    ))
+    .unwrap();
+
+    // the directory of the database is taken by a file, so the file of the table cannot
+    // be created
+    let database_dir = location.path().join("1");
+    if database_dir.exists() {
+        std::fs::remove_dir_all(&database_dir).unwrap();
+    }
+    std::fs::write(&database_dir, b"").unwrap();
+
+    assert!(execute("CREATE TABLE abc (a int PRIMARY KEY) TABLESPACE broken").is_err());
+    assert!(binder
+        .read()
+        .unwrap()
+        .resolve_table("abc".to_string())
+        .is_none());
+
+    execute("CREATE TABLE abc (a int PRIMARY KEY, b boolean)").unwrap();
+    execute("INSERT INTO abc (a, b) VALUES (1, true)").unwrap();
+    assert_eq!(
+        execute("SELECT a, b FROM abc").unwrap(),
+        [[Value::Int(1), Value::Boolean(true)]]
+    );
+
+    // nor is the table of the failed statement in the catalog
+    let binder = Binder::new(1, &manager).unwrap();
+    let table = binder.resolve_table("abc".to_string()).unwrap();
+    assert_eq!(binder.get_columns(table).len(), 2);
+
+    temp_dir.close().unwrap()
+}
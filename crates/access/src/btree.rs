@@ -1,30 +1,39 @@
 mod branch;
+mod bulk;
 mod cursor;
 pub mod error;
+mod free_list;
 mod leaf;
 mod meta;
 mod node;
+mod overflow;
 
 #[cfg(test)]
 mod tests;
 
-pub use cursor::Cursor;
 use {
     self::{
-        branch::Branch,
-        leaf::Leaf,
-        meta::Meta,
-        node::{InsertEffect, Node},
+        branch::{Branch, BranchView},
+        free_list::FreeList,
+        leaf::{Leaf, LeafView},
+        meta::{Meta, MAGIC, VERSION},
+        node::{InsertEffect, Node, NodeView},
     },
+    crate::codec::Codec,
     def::storage::{Decoder, Encoder},
     error::Result,
-    snafu::ResultExt,
-    std::collections::VecDeque,
+    snafu::{ensure, OptionExt, ResultExt},
+    std::{collections::VecDeque, fmt::Debug},
     storage::{
-        buffer::{BufferManager, BufferRef, FileNode, PageTag},
-        PageNum, DEFAULT_PAGE_SIZE,
+        buffer::{
+            BufferManager, BufferReadGuard, BufferRef, BufferRing, BufferWriteGuard, FileNode,
+            PageTag,
+        },
+        PageNum,
     },
+    wal::{Lsn, PageOp, Xid},
 };
+pub use {bulk::DEFAULT_FILL_FACTOR, cursor::Cursor, overflow::OverflowPointer};
 
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
@@ -33,13 +42,15 @@ pub enum PageType {
     Meta,
     Branch,
     Leaf,
+    Overflow,
 }
 
 const META_PAGE_NUM: PageNum = 0;
 
 pub struct BTree<'a, C> {
     key_codec: C,
-    node_capacity: usize,
+    /// finds the overflow pages of the values deleted or replaced, to free them
+    values_codec: Option<Codec>,
     file_node: FileNode,
 
     manager: &'a BufferManager,
@@ -50,90 +61,148 @@ where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    pub fn new(
-        key_codec: C,
-        max_value_size: usize,
-        // page_size: usize,
-        file_node: FileNode,
-        manager: &'a BufferManager,
-    ) -> Self {
-        let max_key_size = key_codec.max_size();
-        let max_entry_size = max_key_size + max_value_size;
-
-        let leaf_capacity = Leaf::<C>::capacity(DEFAULT_PAGE_SIZE, max_entry_size);
-        let branch_capacity = Branch::<C>::capacity(DEFAULT_PAGE_SIZE, max_key_size);
-
-        let node_capacity = leaf_capacity.min(branch_capacity);
-
+    /// A tree in the file, whose nodes split once an entry does not fit in the space left
+    /// in them.
+    pub fn new(key_codec: C, file_node: FileNode, manager: &'a BufferManager) -> Self {
         Self {
             key_codec,
-            node_capacity,
+            values_codec: None,
             file_node,
             manager,
         }
     }
 
-    pub fn init(file_node: FileNode, manager: &BufferManager) -> Result<()> {
-        let mut meta_page_ref = manager.new_page(&file_node).context(error::BufferSnafu)?;
-        let meta = Meta::from_bytes_mut(meta_page_ref.as_slice_mut());
+    /// Frees the overflow pages of the values deleted or replaced, which are encoded by the
+    /// codec. Without it, values are taken as bytes holding no overflow pointers. The pages
+    /// of a value returned by `update` or `upsert` are freed already.
+    pub fn with_overflow(mut self, values_codec: Codec) -> Self {
+        self.values_codec = Some(values_codec);
+        self
+    }
+
+    pub fn init(xid: Xid, file_node: FileNode, manager: &BufferManager) -> Result<()> {
+        let meta_page_ref = manager.new_page(&file_node).context(error::BufferSnafu)?;
+        let mut meta_page = meta_page_ref.write();
+        let before = meta_page.to_vec();
+        let meta = Meta::from_bytes_mut(&mut meta_page);
+        meta.init();
         meta.root = 0;
 
+        manager
+            .log_page(xid, PageOp::MetaUpdate, &mut meta_page, &before)
+            .context(error::BufferSnafu)?;
         meta_page_ref.set_dirty();
 
         Ok(())
     }
 
-    fn create_root_page(&mut self) -> Result<PageNum> {
-        let mut root_page_ref = self
-            .manager
-            .new_page(&self.file_node)
+    /// Checks that the file is a B-tree written in the current format.
+    pub fn check(file_node: FileNode, manager: &BufferManager) -> Result<()> {
+        let meta_page_ref = manager
+            .fetch_page(PageTag {
+                file_node,
+                page_num: META_PAGE_NUM,
+            })
             .context(error::BufferSnafu)?;
+        let meta_page = meta_page_ref.read();
+        let meta = Meta::from_bytes(&meta_page);
 
-        let mut root = Leaf::new(&mut root_page_ref, self.node_capacity, &self.key_codec);
+        let (magic, version) = (meta.magic, meta.version);
+        ensure!(
+            magic == MAGIC && version == VERSION,
+            error::InvalidMetaSnafu { magic, version }
+        );
+
+        Ok(())
+    }
+
+    fn create_root_page(&mut self, xid: Xid) -> Result<PageNum> {
+        let root_page_ref = self.free_list().allocate(xid)?;
+        let mut root_page = root_page_ref.write();
+        let before = root_page.to_vec();
+
+        let mut root = Leaf::new(&mut root_page, &self.key_codec);
         root.init(0, 0);
+        self.log_page(xid, PageOp::NewRoot, &mut root_page, &before)?;
         root_page_ref.set_dirty();
 
-        let page_num = root_page_ref.page_num();
+        let page_num = root_page.page_num();
 
-        let mut meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
-        let meta = Meta::from_bytes_mut(meta_page_ref.as_slice_mut());
+        let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+        let mut meta_page = meta_page_ref.write();
+        let before = meta_page.to_vec();
+        let meta = Meta::from_bytes_mut(&mut meta_page);
         meta.init();
         meta.root = page_num;
         meta.level = 1;
+        self.log_page(xid, PageOp::MetaUpdate, &mut meta_page, &before)?;
         meta_page_ref.set_dirty();
 
         Ok(page_num)
     }
 
     fn root_page_num(&self) -> Result<PageNum> {
-        let mut meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
-        Ok(Meta::from_bytes(meta_page_ref.as_slice_mut()).root)
+        let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+        let meta_page = meta_page_ref.read();
+        Ok(Meta::from_bytes(&meta_page).root)
     }
 
-    pub fn insert(&mut self, key: &K, value: &[u8]) -> Result<()> {
-        let mut page_num = self.root_page_num()?;
-        if page_num == 0 {
-            page_num = self.create_root_page()?;
+    /// Inserts the key with its value, fails with `DuplicateKey` if the key is in the tree
+    /// already.
+    pub fn insert(&mut self, xid: Xid, key: &K, value: &[u8]) -> Result<()> {
+        if self.root_page_num()? == 0 {
+            self.create_root_page(xid)?;
         }
 
+        let (stack, _) = self.search(key)?;
+        self.write_leaf(xid, stack, PageOp::LeafInsert, |leaf| {
+            leaf.insert(xid, key, value, self.manager, &self.free_list())
+        })
+    }
+
+    /// Writes into the leaf at the bottom of the stack with `f`, logged as `leaf_op` unless
+    /// the leaf is split. A split is carried up to the parent, and so on up to the root.
+    fn write_leaf(
+        &self,
+        xid: Xid,
+        mut stack: VecDeque<StackNode>,
+        leaf_op: PageOp,
+        f: impl FnOnce(&mut Leaf<'_, '_, C>) -> Result<Option<InsertEffect>>,
+    ) -> Result<()> {
+        let mut f = Some(f);
         let mut insert_effect = None;
-        let (mut stack, _) = self.search(key)?;
+        let mut split_leaf = None;
+        let mut page_num = 0;
 
         while let Some(node) = stack.pop_back() {
-            let StackNode { page_num, slot_num } = node;
-            let mut page_ref = self.fetch_page(page_num)?;
-            let node = Node::new(&mut page_ref, self.node_capacity, &self.key_codec)?;
-
-            insert_effect = match (node, insert_effect.take()) {
-                (Node::Leaf(mut leaf), _) => leaf
-                    .insert(key, value, self.manager, &self.file_node)
-                    .unwrap(),
+            let slot_num = node.slot_num;
+            page_num = node.page_num;
+            let page_ref = self.fetch_page(page_num)?;
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+            let node = Node::new(&mut page, &self.key_codec)?;
+
+            let op;
+            (op, insert_effect) = match (node, insert_effect.take()) {
+                (Node::Leaf(mut leaf), _) => {
+                    let f = f.take().context(error::InvalidTreeStructSnafu)?;
+                    let effect = f(&mut leaf)?;
+                    match effect {
+                        Some(InsertEffect::Split {
+                            splited_page_num, ..
+                        }) => {
+                            split_leaf = Some(splited_page_num);
+                            (PageOp::LeafSplit, effect)
+                        }
+                        _ => (leaf_op, effect),
+                    }
+                }
 
                 (Node::Branch(mut branch), Some(InsertEffect::UpdateHighKey(high_key))) => {
                     if branch.is_right_most_slot(slot_num) {
                         branch.update_high_key(&high_key);
                     }
-                    None
+                    (PageOp::BranchUpdate, None)
                 }
 
                 (
@@ -143,111 +212,506 @@ where
                         raw_high_key,
                         splited_page_num,
                     }),
-                ) => branch
-                    .insert(
+                ) => {
+                    let effect = branch.insert(
+                        xid,
                         &raw_new_key,
                         splited_page_num,
                         slot_num,
                         raw_high_key,
                         self.manager,
-                        &self.file_node,
-                    )
-                    .unwrap(),
+                        &self.free_list(),
+                    )?;
+                    match effect {
+                        Some(InsertEffect::Split { .. }) => (PageOp::BranchSplit, effect),
+                        _ => (PageOp::BranchInsert, effect),
+                    }
+                }
                 (Node::Branch(_), None) => break,
             };
 
-            match insert_effect {
-                Some(_) => page_ref.set_dirty(),
-                None => return Ok(()),
-            };
+            self.log_page(xid, op, &mut page, &before)?;
+            page_ref.set_dirty();
+
+            // the leaf after the split one is relinked once the split is logged, so that
+            // the split is undone if relinking fails
+            if let Some(splited_page_num) = split_leaf.take() {
+                let next_page_num = self.next_leaf(splited_page_num)?;
+                if next_page_num != 0 {
+                    self.link_prev_leaf(xid, PageOp::LeafSplit, next_page_num, splited_page_num)?;
+                }
+            }
+
+            if insert_effect.is_none() {
+                return Ok(());
+            }
         }
 
         // split the root
-        if stack.is_empty() && let Some(InsertEffect::Split {
-            raw_new_key,
-            raw_high_key,
-            splited_page_num,
-        }) = insert_effect
+        if stack.is_empty()
+            && let Some(InsertEffect::Split {
+                raw_new_key,
+                raw_high_key,
+                splited_page_num,
+            }) = insert_effect
         {
-            let mut new_root_page = self
-                .manager
-                .new_page(&self.file_node)
-                .context(error::BufferSnafu)?;
-            let mut new_root = Branch::new(
-                new_root_page.as_slice_mut(),
-                self.node_capacity,
-                &self.key_codec,
-            );
+            let new_root_page_ref = self.free_list().allocate(xid)?;
+            let mut new_root_page = new_root_page_ref.write();
+            let before = new_root_page.to_vec();
+            let mut new_root = Branch::new(&mut new_root_page, &self.key_codec);
             new_root.init(&raw_new_key, &raw_high_key, page_num, splited_page_num, 0);
-            new_root_page.set_dirty();
+            self.log_page(xid, PageOp::NewRoot, &mut new_root_page, &before)?;
+            new_root_page_ref.set_dirty();
 
-            let mut meta_page = self.fetch_page(META_PAGE_NUM)?;
-            let meta = Meta::from_bytes_mut(meta_page.as_slice_mut());
+            let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+            let mut meta_page = meta_page_ref.write();
+            let before = meta_page.to_vec();
+            let meta = Meta::from_bytes_mut(&mut meta_page);
             meta.root = new_root_page.page_num();
             meta.level += 1;
-            meta_page.set_dirty();
+            self.log_page(xid, PageOp::MetaUpdate, &mut meta_page, &before)?;
+            meta_page_ref.set_dirty();
         }
 
         Ok(())
     }
 
+    /// Replaces the value of the key, returns the value replaced. Fails with `KeyNotFound`
+    /// if the key is not in the tree.
+    pub fn update(&mut self, xid: Xid, key: &K, value: &[u8]) -> Result<Vec<u8>>
+    where
+        K: Debug,
+    {
+        self.replace(xid, key, value)?
+            .with_context(|| error::KeyNotFoundSnafu {
+                key: format!("{:?}", key),
+            })
+    }
+
+    /// Inserts the key with its value, or replaces the value if the key is in the tree
+    /// already. Returns the value replaced, if any.
+    pub fn upsert(&mut self, xid: Xid, key: &K, value: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.replace(xid, key, value)? {
+            Some(old_value) => Ok(Some(old_value)),
+            None => self.insert(xid, key, value).map(|_| None),
+        }
+    }
+
+    /// Replaces the value of the key in its leaf, returns `None` if the key is not in the
+    /// tree. The leaf is split if it has no room for the value.
+    fn replace(&mut self, xid: Xid, key: &K, value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (stack, is_matched) = self.search(key)?;
+        if !is_matched {
+            return Ok(None);
+        }
+
+        let slot_num = stack
+            .back()
+            .context(error::InvalidTreeStructSnafu)?
+            .slot_num;
+
+        let mut old_value = None;
+        self.write_leaf(xid, stack, PageOp::LeafUpdate, |leaf| {
+            let (value, effect) =
+                leaf.replace(xid, slot_num, value, self.manager, &self.free_list())?;
+            old_value = Some(value);
+
+            Ok(effect)
+        })?;
+
+        if let Some(old_value) = &old_value {
+            self.free_value_overflow(xid, old_value)?;
+        }
+
+        Ok(old_value)
+    }
+
+    /// The value of the key, `None` if the key is not in the tree.
+    pub fn get(&self, key: &K) -> Result<Option<Vec<u8>>> {
+        let value = self.descend(
+            Some(key),
+            |branch| branch.search(key).0,
+            |leaf| {
+                let slot_num = leaf.search(key).ok()?;
+                leaf.get_entry(slot_num).map(|(_, value)| value)
+            },
+        )?;
+
+        Ok(value.and_then(|(_, _, _, value)| value))
+    }
+
     fn search<'b, 'c>(&'b self, key: &'c K) -> Result<(VecDeque<StackNode>, bool)> {
-        let (mut page_num, level) = {
-            let meta_page = self.fetch_page(META_PAGE_NUM)?;
-            let meta = Meta::from_bytes(meta_page.as_slice());
+        let Some((mut stack, page_num, _, slot)) = self.descend(
+            Some(key),
+            |branch| branch.search(key).0,
+            |leaf| leaf.search(key),
+        )?
+        else {
+            return Ok((VecDeque::new(), false));
+        };
+
+        let (slot_num, is_matched) = match slot {
+            Ok(i) => (i, true),
+            Err(i) => (i, false),
+        };
+        stack.push_back(StackNode { page_num, slot_num });
+
+        Ok((stack, is_matched))
+    }
+
+    /// Goes down from the root to a leaf, through the child `choose` picks in each branch,
+    /// and reads the leaf. A node stays latched until its child is, so the path is not
+    /// changed under the descent. Returns the branches passed with the slots taken in
+    /// them, the leaf with its LSN, and what is read from it, `None` if the tree is empty.
+    ///
+    /// A leaf is split before its parent learns of the new leaf, so the descent may reach
+    /// a leaf whose entries up to `key` have just moved right. It then moves right along
+    /// the leaves, as long as the next one starts at or before `key`.
+    fn descend<T>(
+        &self,
+        key: Option<&K>,
+        mut choose: impl FnMut(&BranchView<C>) -> usize,
+        read: impl FnOnce(&LeafView<C>) -> T,
+    ) -> Result<Option<Descent<T>>> {
+        let (root, level) = {
+            let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+            let meta_page = meta_page_ref.read();
+            let meta = Meta::from_bytes(&meta_page);
 
             (meta.root, meta.level as usize)
         };
 
+        // the root is not latched along with the meta page, which splits and merges take
+        // after the nodes
+        if root == 0 {
+            return Ok(None);
+        }
+
         let mut stack = VecDeque::with_capacity(level);
-        let mut is_matched = false;
+        let page_ref = self.fetch_page(root)?;
+        let (page_num, lsn, value) =
+            self.descend_from(page_ref.read(), level, key, &mut stack, &mut choose, read)?;
 
-        for i in 0..level {
-            let mut page_ref = self.fetch_page(page_num)?;
+        Ok(Some((stack, page_num, lsn, value)))
+    }
 
-            let node = Node::new(&mut page_ref, self.node_capacity, &self.key_codec)?;
-            let node = match node {
-                Node::Branch(_) if i == level - 1 => Err(error::InvalidTreeStructSnafu.build())?,
-                Node::Leaf(_) if i < level - 1 => Err(error::InvalidTreeStructSnafu.build())?,
+    fn descend_from<T>(
+        &self,
+        page: BufferReadGuard,
+        level: usize,
+        key: Option<&K>,
+        stack: &mut VecDeque<StackNode>,
+        choose: &mut impl FnMut(&BranchView<C>) -> usize,
+        read: impl FnOnce(&LeafView<C>) -> T,
+    ) -> Result<(PageNum, Lsn, T)> {
+        let page_num = page.page_num();
+
+        // a leaf the key may have moved out of is followed by the next leaf, at its level
+        let (child, child_level) = match NodeView::new(&page, &self.key_codec)? {
+            NodeView::Branch(_) if level <= 1 => Err(error::InvalidTreeStructSnafu.build())?,
+            NodeView::Leaf(_) if level > 1 => Err(error::InvalidTreeStructSnafu.build())?,
+
+            NodeView::Branch(branch) => {
+                let slot_num = choose(&branch);
+                stack.push_back(StackNode { page_num, slot_num });
+                (branch.child(slot_num), level - 1)
+            }
+            NodeView::Leaf(leaf) => match key {
+                Some(key) if leaf.is_before(key) => (leaf.next_page_num(), level),
+                _ => return Ok((page_num, page.lsn(), read(&leaf))),
+            },
+        };
 
-                Node::Branch(branch) => {
-                    let (slot_num, child) = branch.search(key);
-                    let node = StackNode { page_num, slot_num };
-                    page_num = child;
-                    node
-                }
-                Node::Leaf(leaf) => {
-                    let (slot_num, matched) = match leaf.search(key) {
-                        Ok(i) => (i, true),
-                        Err(i) => (i, false),
-                    };
+        let child_ref = self.fetch_page(child)?;
+        let child_page = child_ref.read();
+
+        // the next leaf is latched before the leaf is let go, in the order splits take them,
+        // and the leaf is read after all if the key is not in the next one either
+        if child_level == level {
+            let NodeView::Leaf(next) = NodeView::new(&child_page, &self.key_codec)? else {
+                return Err(error::InvalidTreeStructSnafu.build());
+            };
+            if !key.is_some_and(|key| next.starts_at_or_before(key)) {
+                drop(child_page);
+                let NodeView::Leaf(leaf) = NodeView::new(&page, &self.key_codec)? else {
+                    return Err(error::InvalidTreeStructSnafu.build());
+                };
+                return Ok((page_num, page.lsn(), read(&leaf)));
+            }
+        }
+        drop(page);
 
-                    is_matched = matched;
+        self.descend_from(child_page, child_level, key, stack, choose, read)
+    }
+
+    /// The leaves to the right of the leaf holding `key`, under the same parent.
+    fn next_leaves(&self, key: &K) -> Result<Vec<PageNum>> {
+        // the root is a leaf
+        let mut next_leaves = vec![];
+
+        self.descend(
+            Some(key),
+            |branch| {
+                let (slot_num, _) = branch.search(key);
+                next_leaves = branch.children_from(slot_num + 1);
+                slot_num
+            },
+            |_| (),
+        )?;
+
+        Ok(next_leaves)
+    }
+
+    fn prefetch_page(&self, page_num: PageNum) {
+        self.manager.prefetch_page(PageTag {
+            file_node: self.file_node,
+            page_num,
+        })
+    }
 
-                    StackNode { page_num, slot_num }
+    /// Removes the key and its value, returns whether the key was in the tree. A node left
+    /// underfull takes entries from a sibling, or is merged with it if both fit in one
+    /// node, and the root is replaced by its only child.
+    pub fn delete(&mut self, xid: Xid, key: &K) -> Result<bool> {
+        let (mut stack, is_matched) = self.search(key)?;
+        if !is_matched {
+            return Ok(false);
+        }
+
+        let Some(StackNode { page_num, slot_num }) = stack.pop_back() else {
+            return Err(error::InvalidTreeStructSnafu.build());
+        };
+
+        let (mut is_underfull, old_value) = {
+            let page_ref = self.fetch_page(page_num)?;
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+
+            let (is_underfull, old_value) = match Node::new(&mut page, &self.key_codec)? {
+                Node::Leaf(mut leaf) => {
+                    let (_, old_value) = leaf
+                        .view()
+                        .get_entry(slot_num)
+                        .context(error::InvalidTreeStructSnafu)?;
+                    leaf.delete(slot_num)?;
+                    (leaf.is_underfull(), old_value)
                 }
+                Node::Branch(_) => return Err(error::InvalidTreeStructSnafu.build()),
             };
 
-            stack.push_back(node);
+            self.log_page(xid, PageOp::LeafDelete, &mut page, &before)?;
+            page_ref.set_dirty();
+
+            (is_underfull, old_value)
+        };
+        self.free_value_overflow(xid, &old_value)?;
+
+        while is_underfull && let Some(StackNode { page_num, slot_num }) = stack.pop_back() {
+            is_underfull = self.rebalance(xid, page_num, slot_num)?;
         }
 
-        Ok((stack, is_matched))
+        if stack.is_empty() {
+            self.shrink_root(xid)?;
+        }
+
+        Ok(true)
     }
 
-    pub fn cursor<'b, 'c>(&'b self, key: &'c K) -> Result<Option<(Cursor<'a, 'b, C>, bool)>> {
-        let (mut stack, is_matched) = self.search(key)?;
+    /// Rebalances the child in the slot of the branch with a sibling under the same branch,
+    /// once the child uses no more than a quarter of its space. The two are merged into
+    /// the left one if their entries fit in one node, or else entries are moved over until
+    /// both use about as much space. Returns whether the branch is left underfull.
+    fn rebalance(&self, xid: Xid, page_num: PageNum, slot_num: usize) -> Result<bool> {
+        let page_ref = self.fetch_page(page_num)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        let Node::Branch(mut branch) = Node::new(&mut page, &self.key_codec)? else {
+            return Err(error::InvalidTreeStructSnafu.build());
+        };
+
+        let children_count = branch.children_count();
+        // an only child has no sibling to rebalance with
+        if children_count < 2 {
+            return Ok(false);
+        }
+        // moving entries over changes the key bounding the left node, which may be longer
+        let can_redistribute = branch.can_update_key();
+
+        let left_slot = if slot_num + 1 < children_count {
+            slot_num
+        } else {
+            slot_num - 1
+        };
+        let (left_page_num, right_page_num) =
+            (branch.child(left_slot), branch.child(left_slot + 1));
+
+        let left_ref = self.fetch_page(left_page_num)?;
+        let right_ref = self.fetch_page(right_page_num)?;
+        let mut left_page = left_ref.write();
+        let mut right_page = right_ref.write();
+        let (left_before, right_before) = (left_page.to_vec(), right_page.to_vec());
+
+        // the new high key of the left node, if the two are not merged
+        let (new_high_key, next_page_num, is_leaf) = match (
+            Node::new(&mut left_page, &self.key_codec)?,
+            Node::new(&mut right_page, &self.key_codec)?,
+        ) {
+            (Node::Leaf(mut left), Node::Leaf(mut right)) if left.can_merge(&right) => {
+                left.merge(&mut right)?;
+                (None, left.next_page_num(), true)
+            }
+            (Node::Leaf(mut left), Node::Leaf(mut right)) if can_redistribute => {
+                (Some(left.redistribute(&mut right)?), 0, true)
+            }
+            (Node::Branch(mut left), Node::Branch(mut right)) => {
+                // the last key of the left node may only be known to the parent
+                let raw_high_key = branch.raw_key_of(left_slot);
+
+                if left.can_merge(&right, &raw_high_key) {
+                    left.merge(&mut right, &raw_high_key)?;
+                    (None, 0, false)
+                } else if can_redistribute {
+                    (
+                        Some(left.redistribute(&mut right, &raw_high_key)?),
+                        0,
+                        false,
+                    )
+                } else {
+                    return Ok(false);
+                }
+            }
+            // the node is left underfull, until the branch has room for a longer key
+            (Node::Leaf(_), Node::Leaf(_)) => return Ok(false),
+            _ => return Err(error::InvalidTreeStructSnafu.build()),
+        };
+
+        let Some(new_high_key) = new_high_key else {
+            let op = if is_leaf {
+                PageOp::LeafMerge
+            } else {
+                PageOp::BranchMerge
+            };
+            self.log_page(xid, op, &mut left_page, &left_before)?;
+            left_ref.set_dirty();
+            self.log_page(xid, op, &mut right_page, &right_before)?;
+            right_ref.set_dirty();
+
+            if next_page_num != 0 {
+                self.link_prev_leaf(xid, op, next_page_num, left_page_num)?;
+            }
+
+            branch.remove_child(left_slot)?;
+            let is_underfull = branch.is_underfull();
+            self.log_page(xid, PageOp::BranchDelete, &mut page, &before)?;
+            page_ref.set_dirty();
+
+            drop(right_page);
+            self.free_list().free(xid, right_page_num)?;
+
+            return Ok(is_underfull);
+        };
+
+        let op = if is_leaf {
+            PageOp::LeafRedistribute
+        } else {
+            PageOp::BranchRedistribute
+        };
+        self.log_page(xid, op, &mut left_page, &left_before)?;
+        left_ref.set_dirty();
+        self.log_page(xid, op, &mut right_page, &right_before)?;
+        right_ref.set_dirty();
+
+        branch.update_key(left_slot, &new_high_key)?;
+        self.log_page(xid, PageOp::BranchUpdate, &mut page, &before)?;
+        page_ref.set_dirty();
 
-        match stack.pop_back() {
-            Some(StackNode { page_num, slot_num }) => {
-                Ok(Some((Cursor::new(self, page_num, slot_num), is_matched)))
+        Ok(false)
+    }
+
+    fn next_leaf(&self, page_num: PageNum) -> Result<PageNum> {
+        let page_ref = self.fetch_page(page_num)?;
+        let page = page_ref.read();
+
+        match NodeView::new(&page, &self.key_codec)? {
+            NodeView::Leaf(leaf) => Ok(leaf.next_page_num()),
+            NodeView::Branch(_) => Err(error::InvalidTreeStructSnafu.build()),
+        }
+    }
+
+    /// Points the leaf back at the one before it, after a split or a merge has changed it.
+    fn link_prev_leaf(
+        &self,
+        xid: Xid,
+        op: PageOp,
+        page_num: PageNum,
+        prev_page_num: PageNum,
+    ) -> Result<()> {
+        let page_ref = self.fetch_page(page_num)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        match Node::new(&mut page, &self.key_codec)? {
+            Node::Leaf(mut leaf) => leaf.set_prev_page_num(prev_page_num),
+            Node::Branch(_) => return Err(error::InvalidTreeStructSnafu.build()),
+        }
+
+        self.log_page(xid, op, &mut page, &before)?;
+        page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    /// Makes the only child of the root the new root, for as long as the root is a branch
+    /// with a single child.
+    fn shrink_root(&self, xid: Xid) -> Result<()> {
+        loop {
+            let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+            let mut meta_page = meta_page_ref.write();
+            let Meta { root, level, .. } = *Meta::from_bytes(&meta_page);
+            if level < 2 {
+                return Ok(());
             }
-            _ => Err(error::InvalidTreeStructSnafu.build()),
+
+            let child = {
+                let page_ref = self.fetch_page(root)?;
+                let mut page = page_ref.write();
+
+                match Node::new(&mut page, &self.key_codec)? {
+                    Node::Branch(branch) if branch.children_count() == 1 => branch.child(0),
+                    Node::Branch(_) => return Ok(()),
+                    Node::Leaf(_) => return Err(error::InvalidTreeStructSnafu.build()),
+                }
+            };
+
+            let before = meta_page.to_vec();
+            let meta = Meta::from_bytes_mut(&mut meta_page);
+            meta.root = child;
+            meta.level -= 1;
+            self.log_page(xid, PageOp::MetaUpdate, &mut meta_page, &before)?;
+            meta_page_ref.set_dirty();
+
+            // the free list takes the meta page
+            drop(meta_page);
+            self.free_list().free(xid, root)?;
         }
     }
 
-    // pub fn delete(&mut self, key: &K, manager: &BufferManager) -> Result<usize> {
-    //     unimplemented!()
-    // }
+    fn free_list(&self) -> FreeList<'a> {
+        FreeList::new(self.manager, self.file_node)
+    }
+
+    fn log_page(
+        &self,
+        xid: Xid,
+        op: PageOp,
+        page: &mut BufferWriteGuard,
+        before: &[u8],
+    ) -> Result<()> {
+        self.manager
+            .log_page(xid, op, page, before)
+            .context(error::BufferSnafu)
+    }
 
     fn fetch_page(&self, page_num: PageNum) -> Result<BufferRef<'a>> {
         let page_tag = PageTag {
@@ -259,9 +723,28 @@ where
             .fetch_page(page_tag)
             .context(error::BufferSnafu)
     }
+
+    fn fetch_page_with_ring(
+        &self,
+        page_num: PageNum,
+        ring: &mut BufferRing,
+    ) -> Result<BufferRef<'a>> {
+        let page_tag = PageTag {
+            file_node: self.file_node,
+            page_num,
+        };
+
+        self.manager
+            .fetch_page_with_ring(page_tag, ring)
+            .context(error::BufferSnafu)
+    }
 }
 
 struct StackNode {
     page_num: PageNum,
     slot_num: usize,
 }
+
+/// The branches passed from the root with the slots taken in them, and the leaf reached
+/// with its LSN and what is read from it.
+type Descent<T> = (VecDeque<StackNode>, PageNum, Lsn, T);
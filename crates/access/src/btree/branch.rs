@@ -1,14 +1,12 @@
 use {
-    super::{error, node::InsertEffect, PageType, Result},
-    crate::slotted_page::{Slot, SlottedPage},
+    super::{error, free_list::FreeList, node::InsertEffect, PageType, Result},
+    crate::slotted_page::{SlottedPage, SlottedPageView},
     bytemuck::from_bytes_mut,
     core::{mem::size_of, ops::Range},
     def::storage::{Decoder, Encoder},
     snafu::ResultExt,
-    storage::{
-        buffer::{BufferManager, FileNode},
-        PageNum,
-    },
+    storage::{buffer::BufferManager, PageNum},
+    wal::{PageOp, Xid},
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -25,7 +23,6 @@ pub struct Branch<'a, 'b, C> {
     header: &'a mut Header,
     slotted_page: SlottedPage<'a>,
 
-    capacity: usize,
     key_codec: &'b C,
 }
 
@@ -34,7 +31,7 @@ where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    pub fn new(bytes: &'a mut [u8], capacity: usize, key_codec: &'b C) -> Self {
+    pub fn new(bytes: &'a mut [u8], key_codec: &'b C) -> Self {
         let (header, bytes) = bytes.split_at_mut(size_of::<Header>());
 
         let header = from_bytes_mut::<Header>(header);
@@ -43,7 +40,6 @@ where
         Self {
             header,
             slotted_page: SlottedPage::new(bytes),
-            capacity,
             key_codec,
         }
     }
@@ -69,97 +65,121 @@ where
         self.header.right_sibling = sibling;
     }
 
-    pub(super) fn capacity(page_size: usize, key_size: usize) -> usize {
-        const RESERVED: usize = 64;
-        (page_size - size_of::<Header>() - RESERVED)
-            / (size_of::<Slot>() + key_size + size_of::<PageNum>())
+    /// Initializes the branch without children, which are then appended by `push`.
+    pub fn init_empty(&mut self, sibling: PageNum) {
+        self.header.page_type = PageType::Branch;
+        self.slotted_page.init();
+
+        self.header.right_sibling = sibling;
     }
 
-    fn raw_key(&self, range: Range<usize>) -> Vec<u8> {
+    pub(super) fn set_right_sibling(&mut self, page_num: PageNum) {
+        self.header.right_sibling = page_num;
+    }
+
+    /// Appends the child after the last one, bounded by the key.
+    pub fn push(&mut self, raw_key: &[u8], page_num: PageNum) -> Result<()> {
+        let index = self.slotted_page.slot_count();
         self.slotted_page
-            .get_range(range.start..range.end - size_of::<PageNum>())
-            .to_vec()
+            .insert(index, &[raw_key, &page_num.to_le_bytes()])
+            .context(error::SlottedPageSnafu)
+    }
+
+    /// Whether the branch uses so little of its space that it is to be rebalanced.
+    pub(super) fn is_underfull(&self) -> bool {
+        self.slotted_page.is_underfull()
+    }
+
+    /// Whether a child bounded by the raw key fits in the branch, with no more than
+    /// `percent` of its space used after.
+    pub(super) fn fits_within(&self, raw_key: &[u8], percent: u8) -> bool {
+        self.slotted_page
+            .fits_within(raw_key.len() + size_of::<PageNum>(), percent)
+    }
+
+    /// Whether any key fits in place of one in the branch.
+    pub(super) fn can_update_key(&self) -> bool {
+        // the data takes new space if it grows, before its old space is freed
+        self.key_codec.max_size() + size_of::<PageNum>() <= self.slotted_page.free_space()
+    }
+
+    pub(super) fn view(&self) -> BranchView<'_, 'b, C> {
+        BranchView {
+            slotted_page: self.slotted_page.view(),
+            key_codec: self.key_codec,
+        }
     }
 
     fn raw_high_key(&self) -> Vec<u8> {
-        let slot = self.slotted_page.slots().last().unwrap();
-        self.raw_key(slot.range())
+        self.view().raw_high_key()
     }
 
-    fn key(&self, range: Range<usize>) -> Result<K> {
-        let bytes = &self.slotted_page.get_range(range);
-        self.key_codec
-            .decode(bytes)
-            .map(|r| r.0)
-            .map_err(|e| error::Error::Decoding {
-                source: Box::new(e),
-            })
+    /// The key in the slot, as it is stored.
+    pub(super) fn raw_key_of(&self, slot_num: usize) -> Vec<u8> {
+        self.view().raw_key_of(slot_num)
     }
 
-    fn get_page_num(&self, range: Range<usize>) -> PageNum {
-        PageNum::from_le_bytes(
-            self.slotted_page
-                .get_range(range.end - size_of::<PageNum>()..range.end)
-                .try_into()
-                .unwrap(),
-        )
+    pub(super) fn child(&self, slot_num: usize) -> PageNum {
+        self.view().child(slot_num)
+    }
+
+    pub(super) fn children_count(&self) -> usize {
+        self.slotted_page.slot_count()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn insert(
         &mut self,
+        xid: Xid,
         raw_key: &[u8],
         page_num: PageNum,
         index: usize,
         raw_high_key: Vec<u8>,
 
         manager: &BufferManager,
-        file_node: &FileNode,
+        free_list: &FreeList,
     ) -> Result<Option<InsertEffect>> {
-        let update_high_key = index == self.slotted_page.slot_count() - 1;
-        let slot = self.slotted_page.slots()[index];
+        let count = self.slotted_page.slot_count();
+        let update_high_key = index == count - 1;
 
-        let original_page_num = self.get_page_num(slot.range());
-        let original_page_num = original_page_num.to_le_bytes();
-
-        let original_raw_key = self.raw_key(slot.range());
-        let page_num = page_num.to_le_bytes();
-        self.slotted_page
-            .update_slot(index, &[&original_raw_key, &page_num])
-            .unwrap();
+        // a single child is not split from another, the new one fits with it or in no branch
+        if count < 2 || self.slotted_page.fits(raw_key.len() + size_of::<PageNum>()) {
+            self.split_child(index, raw_key, page_num)?;
+            if update_high_key {
+                self.update_high_key(&raw_high_key);
+            }
 
-        self.slotted_page
-            .insert(index, &[raw_key, &original_page_num])
-            .context(error::SlottedPageSnafu)?;
-
-        if update_high_key {
-            self.update_high_key(&raw_high_key);
+            return Ok(update_high_key.then_some(InsertEffect::UpdateHighKey(raw_high_key)));
         }
 
-        if self.slotted_page.slot_count() <= self.capacity {
-            return Ok(if update_high_key {
-                Some(InsertEffect::UpdateHighKey(raw_high_key))
-            } else {
-                None
-            });
-        }
+        // the branch is split by the space its children take, then the child is inserted
+        // into the half it falls in
+        let splited_page_ref = free_list.allocate(xid)?;
+        let mut splited_page = splited_page_ref.write();
+        let before = splited_page.to_vec();
+        let splited_page_num = splited_page.page_num();
+        let mut splited_branch = Branch::new(&mut splited_page, self.key_codec);
+        splited_branch.init_empty(self.header.right_sibling);
 
-        // TODO: rebalance
-        let mut splited_page_ref = manager.new_page(file_node).context(error::BufferSnafu)?;
-        let splited_page_num = splited_page_ref.page_num();
-        let mut splited_branch = Branch::new(
-            splited_page_ref.as_slice_mut(),
-            self.capacity,
-            self.key_codec,
-        );
-        splited_branch.header.right_sibling = self.header.right_sibling;
-        splited_branch.slotted_page.init();
-
-        let slots_count = (self.slotted_page.slot_count() - 1) / 2;
+        let slots_count = self.slotted_page.split_count();
         self.slotted_page
             .split_slots(slots_count, &mut splited_branch.slotted_page);
 
+        let left_count = self.slotted_page.slot_count();
+        if index < left_count {
+            self.split_child(index, raw_key, page_num)?;
+        } else {
+            splited_branch.split_child(index - left_count, raw_key, page_num)?;
+            if update_high_key {
+                splited_branch.update_high_key(&raw_high_key);
+            }
+        }
+
         let raw_high_key = splited_branch.raw_high_key();
 
+        manager
+            .log_page(xid, PageOp::BranchSplit, &mut splited_page, &before)
+            .context(error::BufferSnafu)?;
         splited_page_ref.set_dirty();
 
         self.header.right_sibling = splited_page_num;
@@ -171,10 +191,25 @@ where
         }))
     }
 
+    /// Puts the page split from the child in the slot after it. The child is then bounded
+    /// by the raw key, and the page by the key which bounded the child.
+    fn split_child(&mut self, slot_num: usize, raw_key: &[u8], page_num: PageNum) -> Result<()> {
+        let original_page_num = self.child(slot_num).to_le_bytes();
+        let original_raw_key = self.raw_key_of(slot_num);
+
+        self.slotted_page
+            .update_slot(slot_num, &[&original_raw_key, &page_num.to_le_bytes()])
+            .context(error::SlottedPageSnafu)?;
+        self.slotted_page
+            .insert(slot_num, &[raw_key, &original_page_num])
+            .context(error::SlottedPageSnafu)
+    }
+
     pub fn retrieve(&self, key: &K) -> Option<PageNum> {
+        let view = self.view();
         let slots = self.slotted_page.slots();
         Some(
-            match slots.binary_search_by_key(key, |slot| self.key(slot.range()).unwrap()) {
+            match slots.binary_search_by_key(key, |slot| view.key(slot.range()).unwrap()) {
                 Err(i) if i == self.slotted_page.slot_count() => {
                     let right_siblilng = self.header.right_sibling;
                     if right_siblilng == 0 {
@@ -185,33 +220,161 @@ where
                 }
                 Ok(i) | Err(i) => {
                     let slot = slots[i];
-                    self.get_page_num(slot.range())
+                    view.get_page_num(slot.range())
                 }
             },
         )
     }
 
+    /// Replaces the last key, which bounds the branch for its parent but is not searched
+    /// by. The old one is kept if the branch has no room for the new one.
     pub fn update_high_key(&mut self, high_key: &[u8]) {
-        let last_slot = self.slotted_page.slots().last().unwrap();
-        let range = last_slot.range();
+        let index = self.slotted_page.slot_count() - 1;
+        let _ = self.update_key(index, high_key);
+    }
 
-        let page_num = self
-            .slotted_page
-            .get_range(range.end - size_of::<PageNum>()..range.end)
-            .to_vec();
-        let data = [high_key, &page_num];
+    /// Replaces the key in the slot, which bounds the keys of the child.
+    pub fn update_key(&mut self, slot_num: usize, raw_key: &[u8]) -> Result<()> {
+        let page_num = self.child(slot_num).to_le_bytes();
 
-        let index = self.slotted_page.slot_count() - 1;
-        self.slotted_page.update_slot(index, &data).unwrap();
+        self.slotted_page
+            .update_slot(slot_num, &[raw_key, &page_num])
+            .context(error::SlottedPageSnafu)
     }
 
-    // // TODO: rebalance
-    // pub fn delete(&mut self, index: usize) -> Option<()> {
-    //     self.slotted_page.delete(index).ok()
-    // }
+    /// Removes the child in the slot once it has been merged with the child to its right,
+    /// the merged child is then bounded by the key of the right one.
+    pub fn remove_child(&mut self, slot_num: usize) -> Result<()> {
+        let page_num = self.child(slot_num).to_le_bytes();
+        let raw_key = self.raw_key_of(slot_num + 1);
+
+        self.slotted_page
+            .update_slot(slot_num + 1, &[&raw_key, &page_num])
+            .context(error::SlottedPageSnafu)?;
+        self.slotted_page
+            .delete(slot_num)
+            .context(error::SlottedPageSnafu)
+    }
+
+    /// Whether all the children of the right sibling fit in the branch, along with the key
+    /// bounding the branch in the parent.
+    pub(super) fn can_merge(&self, right: &Branch<C>, raw_high_key: &[u8]) -> bool {
+        right.slotted_page.used_space() + raw_high_key.len() + size_of::<PageNum>()
+            <= self.slotted_page.free_space()
+    }
+
+    /// Moves all the children of the right sibling into the branch, which takes the place
+    /// of the sibling on its level. The last child of the branch takes the key bounding
+    /// the branch in the parent, as its last key may be outdated.
+    pub fn merge(&mut self, right: &mut Branch<C>, raw_high_key: &[u8]) -> Result<()> {
+        self.update_key(self.children_count() - 1, raw_high_key)?;
+
+        let count = right.children_count();
+        self.slotted_page
+            .append_from(&mut right.slotted_page, count)
+            .context(error::SlottedPageSnafu)?;
+
+        self.header.right_sibling = right.header.right_sibling;
+
+        Ok(())
+    }
+
+    /// Moves children between the branch and its right sibling until both use about as
+    /// much space, returns the new high key of the branch. The last child of the branch
+    /// takes the key bounding the branch in the parent, as its last key may be outdated.
+    pub fn redistribute(&mut self, right: &mut Branch<C>, raw_high_key: &[u8]) -> Result<Vec<u8>> {
+        let last = self.children_count() - 1;
+
+        if self.slotted_page.used_space() > right.slotted_page.used_space() {
+            // the fuller branch may have no room for a longer key, the child moves over
+            let page_num = self.child(last).to_le_bytes();
+            self.slotted_page
+                .delete(last)
+                .context(error::SlottedPageSnafu)?;
+            right
+                .slotted_page
+                .insert(0, &[raw_high_key, &page_num])
+                .context(error::SlottedPageSnafu)?;
+        } else {
+            self.update_key(last, raw_high_key)?;
+        }
+
+        self.slotted_page
+            .balance(&mut right.slotted_page)
+            .context(error::SlottedPageSnafu)?;
+
+        Ok(self.raw_high_key())
+    }
+
+    pub fn is_right_most_slot(&self, slot_num: usize) -> bool {
+        slot_num == self.slotted_page.slot_count() - 1
+    }
+}
+
+/// A branch which is only read, as from a page locked in shared mode.
+pub struct BranchView<'a, 'b, C> {
+    slotted_page: SlottedPageView<'a>,
+
+    key_codec: &'b C,
+}
+
+impl<'a, 'b, C, K> BranchView<'a, 'b, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    pub fn new(bytes: &'a [u8], key_codec: &'b C) -> Self {
+        Self {
+            slotted_page: SlottedPageView::new(&bytes[size_of::<Header>()..]),
+            key_codec,
+        }
+    }
+
+    fn raw_key(&self, range: Range<usize>) -> Vec<u8> {
+        self.slotted_page
+            .get_range(range.start..range.end - size_of::<PageNum>())
+            .to_vec()
+    }
+
+    pub(super) fn raw_high_key(&self) -> Vec<u8> {
+        let slot = self.slotted_page.slots().last().unwrap();
+        self.raw_key(slot.range())
+    }
+
+    fn key(&self, range: Range<usize>) -> Result<K> {
+        let bytes = &self.slotted_page.get_range(range);
+        self.key_codec
+            .decode(bytes)
+            .map(|r| r.0)
+            .map_err(|e| error::Error::Decoding {
+                source: Box::new(e),
+            })
+    }
+
+    fn get_page_num(&self, range: Range<usize>) -> PageNum {
+        PageNum::from_le_bytes(
+            self.slotted_page
+                .get_range(range.end - size_of::<PageNum>()..range.end)
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// The key in the slot, as it is stored.
+    pub(super) fn raw_key_of(&self, slot_num: usize) -> Vec<u8> {
+        self.raw_key(self.slotted_page.slots()[slot_num].range())
+    }
+
+    pub(super) fn child(&self, slot_num: usize) -> PageNum {
+        self.get_page_num(self.slotted_page.slots()[slot_num].range())
+    }
+
+    pub(super) fn children_count(&self) -> usize {
+        self.slotted_page.slot_count()
+    }
 
     pub(super) fn search(&self, key: &K) -> (usize, PageNum) {
-        let slots = &self.slotted_page.slots();
+        let slots = self.slotted_page.slots();
 
         let index = match slots[..self.slotted_page.slot_count() - 1]
             .binary_search_by_key(key, |&slot| self.key(slot.range()).unwrap())
@@ -225,7 +388,11 @@ where
         (index, page_num)
     }
 
-    pub fn is_right_most_slot(&self, slot_num: usize) -> bool {
-        slot_num == self.slotted_page.slot_count() - 1
+    /// The children from the slot on, left to right.
+    pub(super) fn children_from(&self, slot_num: usize) -> Vec<PageNum> {
+        self.slotted_page.slots()[slot_num..]
+            .iter()
+            .map(|slot| self.get_page_num(slot.range()))
+            .collect()
     }
 }
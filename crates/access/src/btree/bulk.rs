@@ -0,0 +1,228 @@
+use {
+    super::{
+        branch::Branch, error, leaf::Leaf, meta::Meta, node::Node, BTree, Result, META_PAGE_NUM,
+    },
+    core::cmp::Ordering,
+    def::storage::{Decoder, Encoder},
+    snafu::ensure,
+    storage::PageNum,
+    wal::{PageOp, Xid},
+};
+
+/// How full in percent `bulk_load` packs the nodes, which leaves room for some inserts
+/// before the nodes split.
+pub const DEFAULT_FILL_FACTOR: u8 = 90;
+
+impl<C, K> BTree<'_, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    /// Builds the tree bottom-up from entries in ascending order of key: the leaves are
+    /// written one after another, then each level of branches over the level below, up
+    /// to the root. The nodes are filled to `fill_factor` percent of their space.
+    ///
+    /// The tree must be empty, fails with `DuplicateKey` or `UnsortedKeys` if the keys are
+    /// not strictly ascending.
+    pub fn bulk_load<I, V>(&mut self, xid: Xid, entries: I, fill_factor: u8) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        V: AsRef<[u8]>,
+    {
+        ensure!(
+            (1..=100).contains(&fill_factor),
+            error::InvalidFillFactorSnafu { fill_factor }
+        );
+        ensure!(self.root_page_num()? == 0, error::NotEmptySnafu);
+
+        let mut nodes = self.load_leaves(xid, entries, fill_factor)?;
+        let mut level = 1;
+        while nodes.len() > 1 {
+            nodes = self.load_branches(xid, nodes, fill_factor)?;
+            level += 1;
+        }
+
+        let Some(&(_, root)) = nodes.first() else {
+            return Ok(());
+        };
+
+        let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+        let mut meta_page = meta_page_ref.write();
+        let before = meta_page.to_vec();
+        let meta = Meta::from_bytes_mut(&mut meta_page);
+        meta.root = root;
+        meta.level = level;
+        self.log_page(xid, PageOp::MetaUpdate, &mut meta_page, &before)?;
+        meta_page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    /// Writes the entries into a list of leaves, returns the high key and the page of each.
+    fn load_leaves<I, V>(
+        &self,
+        xid: Xid,
+        entries: I,
+        fill_factor: u8,
+    ) -> Result<Vec<(Vec<u8>, PageNum)>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        V: AsRef<[u8]>,
+    {
+        let mut entries = entries.into_iter();
+        let mut last_key = None;
+        let mut leaves = Vec::new();
+        let mut is_last_underfull = false;
+
+        let mut entry = self.next_entry(&mut entries, &mut last_key)?;
+        let mut next_page_ref = match entry {
+            Some(_) => Some(self.free_list().allocate(xid)?),
+            None => None,
+        };
+        while let Some(page_ref) = next_page_ref.take() {
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+            let page_num = page.page_num();
+            let prev_page_num = leaves.last().map_or(0, |&(_, page_num)| page_num);
+
+            let mut leaf = Leaf::new(&mut page, &self.key_codec);
+            leaf.init(0, prev_page_num);
+
+            while let Some((raw_key, value)) = &entry
+                && (leaf.entries_count() == 0
+                    || leaf.fits_within(raw_key, value.as_ref(), fill_factor))
+            {
+                leaf.push(raw_key, value.as_ref())?;
+                entry = self.next_entry(&mut entries, &mut last_key)?;
+            }
+            is_last_underfull = leaf.is_underfull();
+
+            if entry.is_some() {
+                let next = self.free_list().allocate(xid)?;
+                leaf.set_next_page_num(next.page_num());
+                next_page_ref = Some(next);
+            }
+            leaves.push((leaf.raw_high_key(), page_num));
+
+            self.log_page(xid, PageOp::BulkLoad, &mut page, &before)?;
+            page_ref.set_dirty();
+        }
+
+        if is_last_underfull {
+            self.balance_last_node(xid, &mut leaves)?;
+        }
+
+        Ok(leaves)
+    }
+
+    /// The next entry with its key encoded, fails if the key is not greater than the last.
+    fn next_entry<V>(
+        &self,
+        entries: &mut impl Iterator<Item = (K, V)>,
+        last_key: &mut Option<K>,
+    ) -> Result<Option<(Vec<u8>, V)>> {
+        let Some((key, value)) = entries.next() else {
+            return Ok(None);
+        };
+
+        match last_key.as_ref().map(|last_key| last_key.cmp(&key)) {
+            Some(Ordering::Equal) => return Err(error::DuplicateKeySnafu.build()),
+            Some(Ordering::Greater) => return Err(error::UnsortedKeysSnafu.build()),
+            _ => {}
+        }
+
+        let raw_key = self
+            .key_codec
+            .encode(&key)
+            .map_err(|e| error::Error::Encoding {
+                source: Box::new(e),
+            })?;
+        *last_key = Some(key);
+
+        Ok(Some((raw_key, value)))
+    }
+
+    /// Writes a level of branches over the nodes, returns the high key and the page of
+    /// each branch.
+    fn load_branches(
+        &self,
+        xid: Xid,
+        children: Vec<(Vec<u8>, PageNum)>,
+        fill_factor: u8,
+    ) -> Result<Vec<(Vec<u8>, PageNum)>> {
+        let mut children = children.into_iter().peekable();
+        let mut branches = Vec::new();
+        let mut is_last_underfull = false;
+
+        let mut next_page_ref = Some(self.free_list().allocate(xid)?);
+        while let Some(page_ref) = next_page_ref.take() {
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+            let page_num = page.page_num();
+
+            let mut branch = Branch::new(&mut page, &self.key_codec);
+            branch.init_empty(0);
+
+            // two children at least, so that each level has fewer nodes than the one below
+            let mut raw_high_key = Vec::new();
+            while let Some((raw_key, _)) = children.peek()
+                && (branch.children_count() < 2 || branch.fits_within(raw_key, fill_factor))
+            {
+                let (raw_key, child) = children.next().unwrap();
+                branch.push(&raw_key, child)?;
+                raw_high_key = raw_key;
+            }
+            is_last_underfull = branch.is_underfull();
+
+            if children.peek().is_some() {
+                let next = self.free_list().allocate(xid)?;
+                branch.set_right_sibling(next.page_num());
+                next_page_ref = Some(next);
+            }
+            branches.push((raw_high_key, page_num));
+
+            self.log_page(xid, PageOp::BulkLoad, &mut page, &before)?;
+            page_ref.set_dirty();
+        }
+
+        if is_last_underfull {
+            self.balance_last_node(xid, &mut branches)?;
+        }
+
+        Ok(branches)
+    }
+
+    /// Moves entries or children into the last node of a level from the one before it, which
+    /// takes a new high key.
+    fn balance_last_node(&self, xid: Xid, nodes: &mut [(Vec<u8>, PageNum)]) -> Result<()> {
+        let [.., (raw_high_key, page_num), (_, last)] = nodes else {
+            return Ok(());
+        };
+
+        let page_ref = self.fetch_page(*page_num)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        let last_page_ref = self.fetch_page(*last)?;
+        let mut last_page = last_page_ref.write();
+        let last_before = last_page.to_vec();
+
+        *raw_high_key = match (
+            Node::new(&mut page, &self.key_codec)?,
+            Node::new(&mut last_page, &self.key_codec)?,
+        ) {
+            (Node::Leaf(mut node), Node::Leaf(mut last)) => node.redistribute(&mut last)?,
+            (Node::Branch(mut node), Node::Branch(mut last)) => {
+                node.redistribute(&mut last, raw_high_key)?
+            }
+            _ => return Err(error::InvalidTreeStructSnafu.build()),
+        };
+
+        self.log_page(xid, PageOp::BulkLoad, &mut page, &before)?;
+        page_ref.set_dirty();
+        self.log_page(xid, PageOp::BulkLoad, &mut last_page, &last_before)?;
+        last_page_ref.set_dirty();
+
+        Ok(())
+    }
+}
@@ -1,69 +1,523 @@
 use {
-    super::{node::Node, BTree},
+    super::{
+        error::{self, Result},
+        node::NodeView,
+        BTree,
+    },
     def::storage::{Decoder, Encoder},
-    snafu::prelude::*,
-    storage::PageNum,
+    snafu::OptionExt,
+    std::{
+        collections::VecDeque,
+        ops::{Bound, RangeBounds},
+    },
+    storage::{buffer::BufferRing, PageNum},
+    wal::Lsn,
 };
 
-#[derive(Debug, Snafu)]
-pub enum Error {}
+/// The leaves read ahead of the one a cursor is in.
+const READ_AHEAD_PAGES: usize = 8;
 
-type Result<T> = std::result::Result<T, Error>;
-
-pub struct Cursor<'a, 'b, C> {
-    btree: &'b BTree<'a, C>,
+/// Where an entry is, in a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Position {
     page_num: PageNum,
     slot_num: usize,
+    /// the LSN of the leaf when the position was found, as any change to the leaf is
+    /// logged and may have moved its entries
+    lsn: Lsn,
 }
 
-impl<'a, 'b, C, K> Cursor<'a, 'b, C>
+/// Iterates over the entries between two bounds, from either end. No leaf is latched
+/// between entries: an end whose leaf has changed meanwhile is found again from its
+/// bound, and the ends meet once an entry is past the bound of the other end.
+pub struct Cursor<'a, 'b, C, K> {
+    btree: &'b BTree<'a, C>,
+    /// the next entry from the front, `None` once the entries are exhausted
+    front: Option<Position>,
+    /// the next entry from the back, `None` once the entries are exhausted
+    back: Option<Position>,
+    /// the lower bound of the entries left, the key last taken from the front once any
+    front_bound: Bound<K>,
+    /// the upper bound of the entries left, the key last taken from the back once any
+    back_bound: Bound<K>,
+    /// the buffers the pages are read into, if the scan is not to use the whole pool
+    ring: Option<BufferRing>,
+    /// whether the cursor has just moved into the leaf
+    is_new_leaf: bool,
+    /// the leaves to the right of the current one, as far as its parent knows
+    next_leaves: VecDeque<PageNum>,
+}
+
+impl<'a, 'b, C, K> Cursor<'a, 'b, C, K>
 where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    pub(super) fn new(btree: &'b BTree<'a, C>, page_num: PageNum, slot_num: usize) -> Self {
+    pub(super) fn new(
+        btree: &'b BTree<'a, C>,
+        front: Option<Position>,
+        back: Option<Position>,
+        front_bound: Bound<K>,
+        back_bound: Bound<K>,
+    ) -> Self {
+        // one end alone is no range
+        let (front, back) = match (front, back) {
+            (Some(front), Some(back)) => (Some(front), Some(back)),
+            _ => (None, None),
+        };
+
         Self {
             btree,
-            page_num,
-            slot_num,
+            front,
+            back,
+            front_bound,
+            back_bound,
+            ring: None,
+            is_new_leaf: true,
+            next_leaves: VecDeque::new(),
+        }
+    }
+
+    /// Reads the pages through a ring of buffers, for scans over large parts of a table.
+    pub fn with_ring(mut self, ring: BufferRing) -> Self {
+        self.ring = Some(ring);
+        self
+    }
+
+    /// Takes the entry at one end, and moves that end by one entry. The cursor ends at
+    /// the first error.
+    fn take(&mut self, is_front: bool) -> Option<Result<(K, Vec<u8>)>> {
+        let position = if is_front { self.front? } else { self.back? };
+
+        let entry = self.take_at(position, is_front).transpose();
+        if !matches!(entry, Some(Ok(_))) {
+            (self.front, self.back) = (None, None);
+        }
+
+        entry
+    }
+
+    /// Takes the entry at the position, `None` if it is past the bound of the other end.
+    fn take_at(&mut self, mut position: Position, is_front: bool) -> Result<Option<(K, Vec<u8>)>> {
+        loop {
+            let page_ref = match self.ring.as_mut() {
+                Some(ring) => self.btree.fetch_page_with_ring(position.page_num, ring),
+                None => self.btree.fetch_page(position.page_num),
+            }?;
+            let page = page_ref.read();
+
+            // the leaf has changed since the position was found, which may have been
+            // split, merged, or freed
+            if page.lsn() != position.lsn {
+                drop(page);
+                drop(page_ref);
+
+                let found = match is_front {
+                    true => self.btree.first_in(self.front_bound.as_ref())?,
+                    false => self.btree.last_in(self.back_bound.as_ref())?,
+                };
+                match found {
+                    Some(found) => position = found,
+                    None => return Ok(None),
+                }
+                continue;
+            }
+
+            let NodeView::Leaf(leaf) = NodeView::new(&page, &self.btree.key_codec)? else {
+                return Err(error::InvalidTreeStructSnafu.build());
+            };
+            let ((key, value), bound_key) = leaf
+                .get_entry(position.slot_num)
+                .zip(leaf.get_key(position.slot_num))
+                .context(error::InvalidTreeStructSnafu)?;
+
+            let is_within = match is_front {
+                true => match &self.back_bound {
+                    Bound::Included(bound) => key <= *bound,
+                    Bound::Excluded(bound) => key < *bound,
+                    Bound::Unbounded => true,
+                },
+                false => match &self.front_bound {
+                    Bound::Included(bound) => key >= *bound,
+                    Bound::Excluded(bound) => key > *bound,
+                    Bound::Unbounded => true,
+                },
+            };
+            if !is_within {
+                return Ok(None);
+            }
+
+            let entries_count = leaf.entries_count();
+
+            // the leaves are looked up again to move on
+            drop(page);
+            drop(page_ref);
+
+            if is_front {
+                self.front_bound = Bound::Excluded(bound_key);
+                self.front = self.btree.step_forward(position, entries_count)?;
+            } else {
+                self.back_bound = Bound::Excluded(bound_key);
+                self.back = self.btree.step_backward(position)?;
+            }
+
+            return Ok(Some((key, value)));
         }
     }
 }
 
-impl<C, K> Iterator for Cursor<'_, '_, C>
+impl<C, K> Iterator for Cursor<'_, '_, C, K>
 where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    type Item = (K, Vec<u8>);
+    type Item = Result<(K, Vec<u8>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.page_num == 0 {
-            return None;
+        let page_num = self.front?.page_num;
+        let entry = self.take(true);
+
+        if self.is_new_leaf
+            && let Some(Ok((key, _))) = &entry
+        {
+            self.read_ahead(page_num, key);
+        }
+        self.is_new_leaf = self.front.map(|front| front.page_num) != Some(page_num);
+
+        entry
+    }
+}
+
+impl<C, K> DoubleEndedIterator for Cursor<'_, '_, C, K>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.take(false)
+    }
+}
+
+impl<C, K> Cursor<'_, '_, C, K>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    /// Starts reading the leaves following the one just moved into, which holds `key`.
+    /// The leaves are taken from the parent, which is looked up again once the cursor
+    /// has passed all of its children.
+    fn read_ahead(&mut self, page_num: PageNum, key: &K) {
+        if self.next_leaves.front() == Some(&page_num) {
+            self.next_leaves.pop_front();
+        } else {
+            self.next_leaves.clear();
         }
 
-        let mut page_ref = self.btree.fetch_page(self.page_num).unwrap();
+        if self.next_leaves.is_empty() {
+            // read ahead is best effort
+            self.next_leaves = self.btree.next_leaves(key).unwrap_or_default().into();
+        }
+
+        self.next_leaves
+            .iter()
+            .take(READ_AHEAD_PAGES)
+            .for_each(|&page_num| self.btree.prefetch_page(page_num));
+    }
+}
+
+impl<'a, C, K> BTree<'a, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    /// A cursor over the entries whose keys are in the range, which goes forward with
+    /// `next` and backward with `next_back`. Only the leaves in the range are read.
+    pub fn range<'b, R>(&'b self, range: R) -> Result<Cursor<'a, 'b, C, K>>
+    where
+        R: RangeBounds<K>,
+    {
+        let front = self.first_in(range.start_bound())?;
+        let back = self.last_in(range.end_bound())?;
+        let front_bound = self.owned_bound(range.start_bound())?;
+        let back_bound = self.owned_bound(range.end_bound())?;
+
+        Ok(Cursor::new(self, front, back, front_bound, back_bound))
+    }
 
-        let node = Node::new(
-            &mut page_ref,
-            self.btree.node_capacity,
-            &self.btree.key_codec,
-        )
-        .unwrap();
+    /// A cursor from the first entry whose key is not less than `key` to the end, along
+    /// with whether that entry has the key.
+    pub fn cursor<'b, 'c>(&'b self, key: &'c K) -> Result<Option<(Cursor<'a, 'b, C, K>, bool)>> {
+        let Some((position, is_matched)) = self.seek(key)? else {
+            return Err(error::InvalidTreeStructSnafu.build());
+        };
 
-        match node {
-            Node::Leaf(leaf) => {
-                let entry = leaf.get_entry(self.slot_num);
-                self.slot_num += 1;
+        let front = self.settle_forward(position)?;
+        let back = self.last_in(Bound::Unbounded)?;
+        let front_bound = self.owned_bound(Bound::Included(key))?;
 
-                if self.slot_num >= leaf.entries_count() {
-                    self.page_num = leaf.next_page_num();
-                    self.slot_num = 0;
+        Ok(Some((
+            Cursor::new(self, front, back, front_bound, Bound::Unbounded),
+            is_matched,
+        )))
+    }
+
+    /// The bound with a key of its own, which the cursor keeps.
+    fn owned_bound(&self, bound: Bound<&K>) -> Result<Bound<K>> {
+        let owned = |key| -> Result<K> {
+            let bytes = self
+                .key_codec
+                .encode(key)
+                .map_err(|e| error::Error::Encoding {
+                    source: Box::new(e),
+                })?;
+            let (key, _) = self
+                .key_codec
+                .decode(&bytes)
+                .map_err(|e| error::Error::Decoding {
+                    source: Box::new(e),
+                })?;
+            Ok(key)
+        };
+
+        Ok(match bound {
+            Bound::Included(key) => Bound::Included(owned(key)?),
+            Bound::Excluded(key) => Bound::Excluded(owned(key)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+
+    /// The position of the first entry whose key is not less than `key`, which may be
+    /// past the end of its leaf, along with whether that entry has the key.
+    fn seek(&self, key: &K) -> Result<Option<(Position, bool)>> {
+        let leaf = self.descend(
+            Some(key),
+            |branch| branch.search(key).0,
+            |leaf| leaf.search(key),
+        )?;
+
+        Ok(leaf.map(|(_, page_num, lsn, slot)| {
+            let (slot_num, is_matched) = match slot {
+                Ok(slot_num) => (slot_num, true),
+                Err(slot_num) => (slot_num, false),
+            };
+            let position = Position {
+                page_num,
+                slot_num,
+                lsn,
+            };
+
+            (position, is_matched)
+        }))
+    }
+
+    /// The first entry in the tree after the lower bound.
+    fn first_in(&self, bound: Bound<&K>) -> Result<Option<Position>> {
+        let position = match bound {
+            Bound::Unbounded => match self.edge_leaf(false)? {
+                Some((page_num, lsn)) => Position {
+                    page_num,
+                    slot_num: 0,
+                    lsn,
+                },
+                None => return Ok(None),
+            },
+            Bound::Included(key) | Bound::Excluded(key) => {
+                let Some((position, is_matched)) = self.seek(key)? else {
+                    return Ok(None);
+                };
+
+                let skips_key = is_matched && matches!(bound, Bound::Excluded(_));
+                Position {
+                    slot_num: position.slot_num + skips_key as usize,
+                    ..position
+                }
+            }
+        };
+
+        self.settle_forward(position)
+    }
+
+    /// The last entry in the tree before the upper bound.
+    fn last_in(&self, bound: Bound<&K>) -> Result<Option<Position>> {
+        let position = match bound {
+            Bound::Unbounded => match self.edge_leaf(true)? {
+                Some((page_num, _)) => {
+                    let (entries_count, _, _, lsn) = self.leaf_links(page_num)?;
+                    Position {
+                        page_num,
+                        slot_num: entries_count,
+                        lsn,
+                    }
                 }
+                None => return Ok(None),
+            },
+            Bound::Included(key) | Bound::Excluded(key) => {
+                let Some((position, is_matched)) = self.seek(key)? else {
+                    return Ok(None);
+                };
 
-                entry
+                // the slot is of the first key not less than the bound
+                let takes_key = is_matched && matches!(bound, Bound::Included(_));
+                Position {
+                    slot_num: position.slot_num + takes_key as usize,
+                    ..position
+                }
             }
-            Node::Branch(_) => unreachable!(),
+        };
+
+        // the slot is right after the entry
+        match position.slot_num {
+            0 => self.settle_backward(position),
+            slot_num => Ok(Some(Position {
+                slot_num: slot_num - 1,
+                ..position
+            })),
+        }
+    }
+
+    /// The leftmost or the rightmost leaf with its LSN, `None` if the tree is empty.
+    fn edge_leaf(&self, is_rightmost: bool) -> Result<Option<(PageNum, Lsn)>> {
+        let leaf = self.descend(
+            None,
+            |branch| match is_rightmost {
+                true => branch.children_count() - 1,
+                false => 0,
+            },
+            |leaf| leaf.next_page_num(),
+        )?;
+
+        let Some((_, mut page_num, mut lsn, mut next)) = leaf else {
+            return Ok(None);
+        };
+
+        // the rightmost leaf may have been split before its parent learns of it
+        while is_rightmost && next != 0 {
+            page_num = next;
+            (_, next, _, lsn) = self.leaf_links(page_num)?;
+        }
+
+        Ok(Some((page_num, lsn)))
+    }
+
+    /// The position of the entry after the one at `position`, in the leaf or the next.
+    fn step_forward(&self, position: Position, entries_count: usize) -> Result<Option<Position>> {
+        let position = Position {
+            slot_num: position.slot_num + 1,
+            ..position
+        };
+
+        match position.slot_num < entries_count {
+            true => Ok(Some(position)),
+            false => self.settle_forward(position),
+        }
+    }
+
+    /// The position of the entry before the one at `position`, in the leaf or the previous.
+    fn step_backward(&self, position: Position) -> Result<Option<Position>> {
+        match position.slot_num {
+            0 => self.settle_backward(position),
+            slot_num => Ok(Some(Position {
+                slot_num: slot_num - 1,
+                ..position
+            })),
+        }
+    }
+
+    /// Moves a position past the end of its leaf to the first entry of the leaves after.
+    /// A position in a leaf changed since it was found is returned as it is, to be found
+    /// again.
+    fn settle_forward(&self, mut position: Position) -> Result<Option<Position>> {
+        loop {
+            let page_ref = self.fetch_page(position.page_num)?;
+            let page = page_ref.read();
+            if page.lsn() != position.lsn {
+                return Ok(Some(position));
+            }
+
+            let NodeView::Leaf(leaf) = NodeView::new(&page, &self.key_codec)? else {
+                return Err(error::InvalidTreeStructSnafu.build());
+            };
+            if position.slot_num < leaf.entries_count() {
+                return Ok(Some(position));
+            }
+
+            let next = leaf.next_page_num();
+            if next == 0 {
+                return Ok(None);
+            }
+
+            // the next leaf is latched before this one is released, so no entry moves
+            // between the two meanwhile
+            let next_ref = self.fetch_page(next)?;
+            position = Position {
+                page_num: next,
+                slot_num: 0,
+                lsn: next_ref.read().lsn(),
+            };
+        }
+    }
+
+    /// Moves a position at the start of its leaf to the last entry of the leaves before.
+    /// A position in a leaf changed since it was found is returned as it is, to be found
+    /// again.
+    fn settle_backward(&self, mut position: Position) -> Result<Option<Position>> {
+        let (_, _, mut prev, lsn) = self.leaf_links(position.page_num)?;
+        if lsn != position.lsn {
+            return Ok(Some(position));
+        }
+
+        while prev != 0 {
+            // leaves are latched from left to right, as by the writers, so the one before
+            // is latched first, and checked to still lead to this one
+            let prev_ref = self.fetch_page(prev)?;
+            let prev_page = prev_ref.read();
+            let page_ref = self.fetch_page(position.page_num)?;
+            if page_ref.read().lsn() != position.lsn {
+                return Ok(Some(position));
+            }
+
+            let NodeView::Leaf(leaf) = NodeView::new(&prev_page, &self.key_codec)? else {
+                return Err(error::InvalidTreeStructSnafu.build());
+            };
+            // the leaf before has been split, into leaves still to its right
+            if leaf.next_page_num() != position.page_num {
+                prev = leaf.next_page_num();
+                continue;
+            }
+
+            let entries_count = leaf.entries_count();
+            if entries_count > 0 {
+                return Ok(Some(Position {
+                    page_num: prev,
+                    slot_num: entries_count - 1,
+                    lsn: prev_page.lsn(),
+                }));
+            }
+
+            position = Position {
+                page_num: prev,
+                slot_num: 0,
+                lsn: prev_page.lsn(),
+            };
+            prev = leaf.prev_page_num();
+        }
+
+        Ok(None)
+    }
+
+    /// The count of entries of the leaf, the leaves after and before it, and its LSN.
+    fn leaf_links(&self, page_num: PageNum) -> Result<(usize, PageNum, PageNum, Lsn)> {
+        let page_ref = self.fetch_page(page_num)?;
+        let page = page_ref.read();
+
+        match NodeView::new(&page, &self.key_codec)? {
+            NodeView::Leaf(leaf) => Ok((
+                leaf.entries_count(),
+                leaf.next_page_num(),
+                leaf.prev_page_num(),
+                page.lsn(),
+            )),
+            NodeView::Branch(_) => Err(error::InvalidTreeStructSnafu.build()),
         }
     }
 }
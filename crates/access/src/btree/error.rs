@@ -1,7 +1,7 @@
 use {
     crate::slotted_page,
     snafu::{prelude::*, Backtrace},
-    storage::buffer,
+    storage::{buffer, PageNum},
 };
 
 #[derive(Debug, Snafu)]
@@ -28,17 +28,50 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Keys are not in ascending order"))]
+    UnsortedKeys {
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Key `{}` not found", key))]
     KeyNotFound {
         backtrace: Backtrace,
         key: String,
     },
 
+    #[snafu(display(
+        "Invalid B-tree meta page with magic {:#x} and version {}",
+        magic,
+        version
+    ))]
+    InvalidMeta {
+        backtrace: Backtrace,
+        magic: u32,
+        version: u32,
+    },
+
+    #[snafu(display("Only an empty B-tree can be bulk loaded"))]
+    NotEmpty {
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Fill factor {} is not a percentage between 1 and 100", fill_factor))]
+    InvalidFillFactor {
+        backtrace: Backtrace,
+        fill_factor: u8,
+    },
+
     #[snafu(display("B-tree structure is invalid"))]
     InvalidTreeStruct {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Overflow pages of the value starting at page {} are broken", page_num))]
+    InvalidOverflow {
+        backtrace: Backtrace,
+        page_num: PageNum,
+    },
+
     Decoding {
         // #[snafu(backtrace)]
         source: Box<dyn std::error::Error>,
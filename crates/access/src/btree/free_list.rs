@@ -0,0 +1,126 @@
+use {
+    super::{error, meta::Meta, PageType, Result, META_PAGE_NUM},
+    bytemuck::from_bytes_mut,
+    core::mem::size_of,
+    snafu::ResultExt,
+    storage::{
+        buffer::{BufferManager, BufferRef, BufferWriteGuard, FileNode, PageTag},
+        PageNum,
+    },
+    wal::{PageOp, Xid},
+};
+
+/// The header of a deleted page, deleted pages are linked from `Meta::free_list`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct Header {
+    page_type: PageType,
+    /// the next deleted page, `0` if this is the last one
+    next_page_num: PageNum,
+}
+unsafe impl bytemuck::Zeroable for Header {}
+unsafe impl bytemuck::Pod for Header {}
+
+/// Allocates pages of a B-tree file, deleted pages are reused before the file grows.
+pub(super) struct FreeList<'a> {
+    manager: &'a BufferManager,
+    file_node: FileNode,
+}
+
+impl<'a> FreeList<'a> {
+    pub fn new(manager: &'a BufferManager, file_node: FileNode) -> Self {
+        Self { manager, file_node }
+    }
+
+    /// Takes the first page off the free list, or extends the file if the list is empty.
+    /// The page returned is all zeros, just like a new one.
+    pub fn allocate(&self, xid: Xid) -> Result<BufferRef<'a>> {
+        let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+        let mut meta_page = meta_page_ref.write();
+
+        let page_num = Meta::from_bytes(&meta_page).free_list;
+        if page_num == 0 {
+            return self
+                .manager
+                .new_page(&self.file_node)
+                .context(error::BufferSnafu);
+        }
+
+        let page_ref = self.fetch_page(page_num)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        if page[0] != PageType::Deleted as u8 {
+            return Err(error::InvalidPageTypeSnafu { page_type: page[0] }.build());
+        }
+        let next_page_num =
+            from_bytes_mut::<Header>(&mut page[..size_of::<Header>()]).next_page_num;
+
+        page.fill(0);
+        self.log_page(xid, PageOp::ReusePage, &page_ref, &mut page, &before)?;
+
+        let before = meta_page.to_vec();
+        Meta::from_bytes_mut(&mut meta_page).free_list = next_page_num;
+        self.log_page(
+            xid,
+            PageOp::MetaUpdate,
+            &meta_page_ref,
+            &mut meta_page,
+            &before,
+        )?;
+
+        drop(page);
+        Ok(page_ref)
+    }
+
+    /// Puts the page on the free list, it must be unreachable from the tree already.
+    pub fn free(&self, xid: Xid, page_num: PageNum) -> Result<()> {
+        let meta_page_ref = self.fetch_page(META_PAGE_NUM)?;
+        let mut meta_page = meta_page_ref.write();
+
+        let page_ref = self.fetch_page(page_num)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        page.fill(0);
+        let header = from_bytes_mut::<Header>(&mut page[..size_of::<Header>()]);
+        header.page_type = PageType::Deleted;
+        header.next_page_num = Meta::from_bytes(&meta_page).free_list;
+        self.log_page(xid, PageOp::FreePage, &page_ref, &mut page, &before)?;
+
+        let before = meta_page.to_vec();
+        Meta::from_bytes_mut(&mut meta_page).free_list = page_num;
+        self.log_page(
+            xid,
+            PageOp::MetaUpdate,
+            &meta_page_ref,
+            &mut meta_page,
+            &before,
+        )
+    }
+
+    fn log_page(
+        &self,
+        xid: Xid,
+        op: PageOp,
+        page_ref: &BufferRef,
+        page: &mut BufferWriteGuard,
+        before: &[u8],
+    ) -> Result<()> {
+        self.manager
+            .log_page(xid, op, page, before)
+            .context(error::BufferSnafu)?;
+        page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    fn fetch_page(&self, page_num: PageNum) -> Result<BufferRef<'a>> {
+        self.manager
+            .fetch_page(PageTag {
+                file_node: self.file_node,
+                page_num,
+            })
+            .context(error::BufferSnafu)
+    }
+}
@@ -1,14 +1,15 @@
 use {
-    super::{error, InsertEffect, PageType, Result},
-    crate::slotted_page::{Slot, SlottedPage},
-    bytemuck::from_bytes_mut,
+    super::{error, free_list::FreeList, InsertEffect, PageType, Result},
+    crate::slotted_page::{SlottedPage, SlottedPageView},
+    bytemuck::{from_bytes, from_bytes_mut},
     core::{mem::size_of, ops::Range},
     def::storage::{Decoder, Encoder},
     snafu::ResultExt,
     storage::{
-        buffer::{BufferManager, BufferRef, FileNode},
+        buffer::{BufferManager, BufferWriteGuard},
         PageNum,
     },
+    wal::{PageOp, Xid},
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -28,42 +29,31 @@ pub struct Leaf<'a, 'b, C> {
 
     key_codec: &'b C,
     page_num: PageNum,
-    capacity: usize,
 }
 
-impl<'a, 'b, C, K> Leaf<'a, 'b, C>
+/// A leaf which is only read, as from a page locked in shared mode.
+pub struct LeafView<'a, 'b, C> {
+    header: &'a Header,
+    slotted_page: SlottedPageView<'a>,
+
+    key_codec: &'b C,
+}
+
+impl<'a, 'b, C, K> LeafView<'a, 'b, C>
 where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    pub fn new(page_ref: &'a mut BufferRef, capacity: usize, key_codec: &'b C) -> Self {
-        let page_num = page_ref.page_num();
-
-        let bytes = page_ref.as_slice_mut();
-        let (header, bytes) = bytes.split_at_mut(size_of::<Header>());
+    pub fn new(bytes: &'a [u8], key_codec: &'b C) -> Self {
+        let (header, bytes) = bytes.split_at(size_of::<Header>());
 
         Self {
-            header: from_bytes_mut(header),
-            slotted_page: SlottedPage::new(bytes),
+            header: from_bytes(header),
+            slotted_page: SlottedPageView::new(bytes),
             key_codec,
-            page_num,
-            capacity,
         }
     }
 
-    pub fn init(&mut self, next_page_num: PageNum, prev_page_num: PageNum) {
-        self.header.page_type = PageType::Leaf;
-        self.header.next_page_num = next_page_num;
-        self.header.prev_page_num = prev_page_num;
-
-        self.slotted_page.init();
-    }
-
-    pub(super) fn capacity(page_size: usize, entry_size: usize) -> usize {
-        const RESERVED: usize = 64;
-        (page_size - size_of::<Header>() - RESERVED) / (size_of::<Slot>() + entry_size)
-    }
-
     fn raw_key(&self, range: Range<usize>) -> Vec<u8> {
         let bytes = self.slotted_page.get_range(range);
         let (_, len) = self.key_codec.decode(bytes).unwrap();
@@ -71,7 +61,7 @@ where
         bytes[..len].to_vec()
     }
 
-    fn raw_high_key(&self) -> Vec<u8> {
+    pub(super) fn raw_high_key(&self) -> Vec<u8> {
         let slot = self.slotted_page.slots().last().unwrap();
         self.raw_key(slot.range())
     }
@@ -93,7 +83,7 @@ where
         slots.binary_search_by_key(key, |slot| self.key(slot.range()).unwrap())
     }
 
-    pub(super) fn retrieve(&self, key: &K) -> Result<Option<&[u8]>> {
+    pub(super) fn retrieve(&self, key: &K) -> Result<Option<&'a [u8]>> {
         let slots = self.slotted_page.slots();
 
         match slots.binary_search_by_key(key, |slot| self.key(slot.range()).unwrap()) {
@@ -115,6 +105,34 @@ where
         self.header.next_page_num
     }
 
+    pub(super) fn prev_page_num(&self) -> PageNum {
+        self.header.prev_page_num
+    }
+
+    /// Whether `key` is beyond the entries of the leaf, with leaves on its right where it
+    /// may be.
+    pub(super) fn is_before(&self, key: &K) -> bool {
+        let last_key = self
+            .slotted_page
+            .slots()
+            .last()
+            .map(|slot| self.key(slot.range()));
+
+        self.header.next_page_num != 0
+            && match last_key {
+                Some(last_key) => last_key.is_ok_and(|last_key| *key > last_key),
+                None => true,
+            }
+    }
+
+    /// Whether the first entry of the leaf has a key not greater than `key`.
+    pub(super) fn starts_at_or_before(&self, key: &K) -> bool {
+        self.slotted_page.slots().first().is_some_and(|slot| {
+            self.key(slot.range())
+                .is_ok_and(|first_key| first_key <= *key)
+        })
+    }
+
     pub(super) fn get_entry(&self, index: usize) -> Option<(K, Vec<u8>)> {
         let slot = self.slotted_page.get_slot(index)?;
         let range = slot.range();
@@ -125,7 +143,12 @@ where
         Some((key, value))
     }
 
-    fn get_value(&self, range: Range<usize>) -> Result<&[u8]> {
+    pub(super) fn get_key(&self, index: usize) -> Option<K> {
+        let slot = self.slotted_page.get_slot(index)?;
+        self.key(slot.range()).ok()
+    }
+
+    fn get_value(&self, range: Range<usize>) -> Result<&'a [u8]> {
         let bytes = self.slotted_page.get_range(range);
         self.key_codec
             .decode(bytes)
@@ -134,53 +157,157 @@ where
                 source: Box::new(e),
             })
     }
+}
+
+impl<'a, 'b, C, K> Leaf<'a, 'b, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    pub fn new(page: &'a mut BufferWriteGuard, key_codec: &'b C) -> Self {
+        let page_num = page.page_num();
+
+        let (header, bytes) = page.split_at_mut(size_of::<Header>());
+
+        Self {
+            header: from_bytes_mut(header),
+            slotted_page: SlottedPage::new(bytes),
+            key_codec,
+            page_num,
+        }
+    }
+
+    pub fn init(&mut self, next_page_num: PageNum, prev_page_num: PageNum) {
+        self.header.page_type = PageType::Leaf;
+        self.header.next_page_num = next_page_num;
+        self.header.prev_page_num = prev_page_num;
+
+        self.slotted_page.init();
+    }
+
+    pub(super) fn view(&self) -> LeafView<'_, 'b, C> {
+        LeafView {
+            header: self.header,
+            slotted_page: self.slotted_page.view(),
+            key_codec: self.key_codec,
+        }
+    }
+
+    fn raw_key(&self, range: Range<usize>) -> Vec<u8> {
+        self.view().raw_key(range)
+    }
+
+    pub(super) fn raw_high_key(&self) -> Vec<u8> {
+        self.view().raw_high_key()
+    }
+
+    pub(super) fn search(&self, key: &K) -> std::result::Result<usize, usize> {
+        self.view().search(key)
+    }
+
+    pub(super) fn entries_count(&self) -> usize {
+        self.slotted_page.slot_count()
+    }
+
+    /// Whether the leaf uses so little of its space that it is to be rebalanced.
+    pub(super) fn is_underfull(&self) -> bool {
+        self.slotted_page.is_underfull()
+    }
+
+    /// Whether an entry of the raw key and the value fits in the leaf, with no more than
+    /// `percent` of its space used after.
+    pub(super) fn fits_within(&self, raw_key: &[u8], value: &[u8], percent: u8) -> bool {
+        self.slotted_page
+            .fits_within(raw_key.len() + value.len(), percent)
+    }
+
+    pub(super) fn next_page_num(&self) -> PageNum {
+        self.header.next_page_num
+    }
+
+    pub(super) fn set_prev_page_num(&mut self, page_num: PageNum) {
+        self.header.prev_page_num = page_num;
+    }
+
+    pub(super) fn set_next_page_num(&mut self, page_num: PageNum) {
+        self.header.next_page_num = page_num;
+    }
+
+    fn get_value(&self, range: Range<usize>) -> Result<&[u8]> {
+        self.view().get_value(range)
+    }
 
     pub fn insert(
         &mut self,
+        xid: Xid,
         key: &K,
         value: &[u8],
 
         manager: &BufferManager,
-        file_node: &FileNode,
+        free_list: &FreeList,
     ) -> Result<Option<InsertEffect>> {
-        let mut update_high_key = false;
-
         let index = match self.search(key) {
-            Err(i) if i == self.slotted_page.slot_count() => {
-                update_high_key = true;
-                i
-            }
             Err(i) => i,
-            // TODO: handle duplicate key
-            Ok(_) => return Ok(None),
+            Ok(_) => return Err(error::DuplicateKeySnafu.build()),
         };
 
         let key = self.key_codec.encode(key).unwrap();
-        self.slotted_page.insert(index, &[&key, value]).unwrap();
-
-        if self.slotted_page.slot_count() < self.capacity {
-            return Ok(if update_high_key {
-                Some(InsertEffect::UpdateHighKey(key))
-            } else {
-                None
-            });
+        self.insert_at(xid, index, &key, value, manager, free_list)
+    }
+
+    /// Inserts the entry into the slot, the leaf is split if the entry does not fit. The
+    /// leaf is left as it is if the page split into cannot be allocated.
+    fn insert_at(
+        &mut self,
+        xid: Xid,
+        index: usize,
+        key: &[u8],
+        value: &[u8],
+
+        manager: &BufferManager,
+        free_list: &FreeList,
+    ) -> Result<Option<InsertEffect>> {
+        let count = self.entries_count();
+
+        // a single entry is not split from another, the new one fits with it or in no leaf
+        if count < 2 || self.slotted_page.fits(key.len() + value.len()) {
+            self.slotted_page
+                .insert(index, &[key, value])
+                .context(error::SlottedPageSnafu)?;
+
+            return Ok((index == count).then_some(InsertEffect::UpdateHighKey(key.to_vec())));
         }
 
-        // TODO: rebalance
-        let mut splited_page_ref = manager.new_page(file_node).context(error::BufferSnafu)?;
-        let splited_page_num = splited_page_ref.page_num();
+        // the leaf is split by the space its entries take, then the entry is inserted into
+        // the half it falls in
+        let splited_page_ref = free_list.allocate(xid)?;
+        let mut splited_page = splited_page_ref.write();
+        let before = splited_page.to_vec();
+        let splited_page_num = splited_page.page_num();
 
-        let mut splited_leaf = Leaf::new(&mut splited_page_ref, self.capacity, self.key_codec);
-        splited_leaf.init(0, self.page_num);
+        let mut splited_leaf = Leaf::new(&mut splited_page, self.key_codec);
+        splited_leaf.init(self.header.next_page_num, self.page_num);
 
-        let slots_count = self.slotted_page.slot_count() / 2;
+        let slots_count = self.slotted_page.split_count();
         self.slotted_page
             .split_slots(slots_count, &mut splited_leaf.slotted_page);
 
         self.header.next_page_num = splited_page_num;
 
+        let left_count = self.entries_count();
+        match index < left_count {
+            true => self.slotted_page.insert(index, &[key, value]),
+            false => splited_leaf
+                .slotted_page
+                .insert(index - left_count, &[key, value]),
+        }
+        .context(error::SlottedPageSnafu)?;
+
         let raw_high_key = splited_leaf.raw_high_key();
 
+        manager
+            .log_page(xid, PageOp::LeafSplit, &mut splited_page, &before)
+            .context(error::BufferSnafu)?;
         splited_page_ref.set_dirty();
 
         Ok(Some(InsertEffect::Split {
@@ -190,20 +317,100 @@ where
         }))
     }
 
-    // pub fn delete(&mut self, key: &K, manager: &BufferManager) -> Result<usize> {
-    //     unimplemented!()
+    /// Appends the entry after the last one, the key must be greater than all in the leaf.
+    pub fn push(&mut self, raw_key: &[u8], value: &[u8]) -> Result<()> {
+        let index = self.slotted_page.slot_count();
+        self.slotted_page
+            .insert(index, &[raw_key, value])
+            .context(error::SlottedPageSnafu)
+    }
+
+    /// Whether the value fits in place of the one in the slot.
+    fn can_update(&self, slot_num: usize, value: &[u8]) -> bool {
+        let slot = self.slotted_page.slots()[slot_num];
+        let len = self.raw_key(slot.range()).len() + value.len();
+
+        // the data takes new space if it grows, before its old space is freed
+        len <= slot.len().max(self.slotted_page.free_space())
+    }
+
+    /// Replaces the value in the slot, returns the value replaced. The leaf is split if it
+    /// has no room for the value, the entry is then written again into the half it falls in.
+    pub fn replace(
+        &mut self,
+        xid: Xid,
+        slot_num: usize,
+        value: &[u8],
+
+        manager: &BufferManager,
+        free_list: &FreeList,
+    ) -> Result<(Vec<u8>, Option<InsertEffect>)> {
+        if self.can_update(slot_num, value) {
+            return Ok((self.update(slot_num, value)?, None));
+        }
+
+        let range = self.slotted_page.slots()[slot_num].range();
+        let old_value = self.get_value(range.clone())?.to_vec();
+        let raw_key = self.raw_key(range);
+
+        self.delete(slot_num)?;
+        match self.insert_at(xid, slot_num, &raw_key, value, manager, free_list) {
+            Ok(effect) => Ok((old_value, effect)),
+            Err(e) => {
+                // the old entry fits in the space it has just freed
+                self.slotted_page
+                    .insert(slot_num, &[&raw_key, &old_value])
+                    .context(error::SlottedPageSnafu)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Replaces the value in the slot, returns the value replaced.
+    pub fn update(&mut self, slot_num: usize, value: &[u8]) -> Result<Vec<u8>> {
+        let range = self.slotted_page.slots()[slot_num].range();
+        let old_value = self.get_value(range.clone())?.to_vec();
+        let raw_key = self.raw_key(range);
+
+        self.slotted_page
+            .update_slot(slot_num, &[&raw_key, value])
+            .context(error::SlottedPageSnafu)?;
+
+        Ok(old_value)
+    }
+
+    /// Removes the entry in the slot.
+    pub fn delete(&mut self, slot_num: usize) -> Result<()> {
+        self.slotted_page
+            .delete(slot_num)
+            .context(error::SlottedPageSnafu)
+    }
+
+    /// Whether all the entries of the right sibling fit in the leaf.
+    pub(super) fn can_merge(&self, right: &Leaf<C>) -> bool {
+        right.slotted_page.used_space() <= self.slotted_page.free_space()
+    }
+
+    /// Moves all the entries of the right sibling into the leaf, which takes the place of
+    /// the sibling in the list of leaves.
+    pub fn merge(&mut self, right: &mut Leaf<C>) -> Result<()> {
+        let count = right.entries_count();
+        self.slotted_page
+            .append_from(&mut right.slotted_page, count)
+            .context(error::SlottedPageSnafu)?;
 
-    //     // let index = match self.search_pos(key) {
-    //     //     Err(_) => return Ok(0),
-    //     //     Ok(i) => i,
-    //     // };
+        self.header.next_page_num = right.header.next_page_num;
 
-    //     // let update_high_key = index == self.slotted_page.slot_count();
+        Ok(())
+    }
 
-    //     // self.slotted_page
-    //     //     .delete(index)
-    //     //     .context(error::SlottedPageSnafu)?;
+    /// Moves entries between the leaf and its right sibling until both use about as much
+    /// space, returns the new high key of the leaf.
+    pub fn redistribute(&mut self, right: &mut Leaf<C>) -> Result<Vec<u8>> {
+        self.slotted_page
+            .balance(&mut right.slotted_page)
+            .context(error::SlottedPageSnafu)?;
 
-    //     // Ok(1)
-    // }
+        Ok(self.raw_high_key())
+    }
 }
@@ -6,6 +6,11 @@ use {
     storage::PageNum,
 };
 
+/// Identifies the meta page of a B-tree file.
+pub const MAGIC: u32 = 0x0DB0_B7EE;
+/// The version of the on-disk B-tree format, bumped whenever the format changes.
+pub const VERSION: u32 = 2;
+
 pub_fields_struct! {
     #[derive(Debug, Copy, Clone)]
     #[repr(C)]
@@ -17,6 +22,7 @@ pub_fields_struct! {
         magic: u32,
         version: u32,
         root: PageNum,
+        /// the first page of the free list, `0` if there is no deleted page
         free_list: PageNum,
     }
 }
@@ -35,6 +41,8 @@ impl Meta {
     pub fn init(&mut self) {
         self.level = 0;
         self.page_type = PageType::Meta;
+        self.magic = MAGIC;
+        self.version = VERSION;
     }
 }
 
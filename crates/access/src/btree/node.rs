@@ -1,12 +1,12 @@
 use {
     super::{
-        branch::Branch,
+        branch::{Branch, BranchView},
         error::{self, Result},
-        leaf::Leaf,
+        leaf::{Leaf, LeafView},
         PageType,
     },
     def::storage::{Decoder, Encoder},
-    storage::{buffer::BufferRef, PageNum},
+    storage::{buffer::BufferWriteGuard, PageNum},
 };
 
 pub(super) enum Node<'a, 'b, C> {
@@ -19,12 +19,30 @@ where
     C: Encoder<Item = K> + Decoder<Item = K>,
     K: Ord,
 {
-    pub(super) fn new(page_ref: &'a mut BufferRef, capacity: usize, codec: &'b C) -> Result<Self> {
-        Ok(match page_ref.as_slice()[0] {
-            ty if ty == PageType::Branch as u8 => {
-                Node::Branch(Branch::new(page_ref.as_slice_mut(), capacity, codec))
-            }
-            ty if ty == PageType::Leaf as u8 => Node::Leaf(Leaf::new(page_ref, capacity, codec)),
+    pub(super) fn new(page: &'a mut BufferWriteGuard, codec: &'b C) -> Result<Self> {
+        Ok(match page[0] {
+            ty if ty == PageType::Branch as u8 => Node::Branch(Branch::new(page, codec)),
+            ty if ty == PageType::Leaf as u8 => Node::Leaf(Leaf::new(page, codec)),
+            ty => return Err(error::InvalidPageTypeSnafu { page_type: ty }.build()),
+        })
+    }
+}
+
+/// A node which is only read, as from a page locked in shared mode.
+pub(super) enum NodeView<'a, 'b, C> {
+    Branch(BranchView<'a, 'b, C>),
+    Leaf(LeafView<'a, 'b, C>),
+}
+
+impl<'a, 'b, C, K> NodeView<'a, 'b, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    pub(super) fn new(page: &'a [u8], codec: &'b C) -> Result<Self> {
+        Ok(match page[0] {
+            ty if ty == PageType::Branch as u8 => NodeView::Branch(BranchView::new(page, codec)),
+            ty if ty == PageType::Leaf as u8 => NodeView::Leaf(LeafView::new(page, codec)),
             ty => return Err(error::InvalidPageTypeSnafu { page_type: ty }.build()),
         })
     }
@@ -0,0 +1,163 @@
+use {
+    super::{error, BTree, PageType, Result},
+    bytemuck::{from_bytes, from_bytes_mut},
+    common::pub_fields_struct,
+    core::mem::size_of,
+    def::storage::{Decoder, Encoder},
+    snafu::ensure,
+    storage::{PageNum, PAGE_HEADER_SIZE},
+    wal::{PageOp, Xid},
+};
+
+/// The header of an overflow page, which holds a part of a value too large for a leaf.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct Header {
+    page_type: PageType,
+    /// the bytes of the value in this page
+    len: u16,
+    /// the page holding the next part of the value, `0` if this is the last one
+    next_page_num: PageNum,
+}
+unsafe impl bytemuck::Zeroable for Header {}
+unsafe impl bytemuck::Pod for Header {}
+
+pub_fields_struct! {
+    /// Where a value kept out of its row is, the row holds this in place of the value.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    struct OverflowPointer {
+        /// the first page of the chain holding the value
+        page_num: PageNum,
+        len: u32,
+    }
+}
+
+impl OverflowPointer {
+    /// The bytes taken by a pointer in a row.
+    pub const SIZE: usize = size_of::<PageNum>() + size_of::<u32>();
+}
+
+impl<C, K> BTree<'_, C>
+where
+    C: Encoder<Item = K> + Decoder<Item = K>,
+    K: Ord,
+{
+    /// Writes the value into a chain of overflow pages in the file of the tree.
+    pub fn write_overflow(&self, xid: Xid, value: &[u8]) -> Result<OverflowPointer> {
+        let part_size = self.manager.page_size() - PAGE_HEADER_SIZE - size_of::<Header>();
+
+        // written from the last part, so that each page links to the one written before
+        let mut next_page_num = 0;
+        for part in value.chunks(part_size).rev() {
+            let page_ref = self.free_list().allocate(xid)?;
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+
+            let (header, data) = page.split_at_mut(size_of::<Header>());
+            *from_bytes_mut(header) = Header {
+                page_type: PageType::Overflow,
+                len: part.len() as u16,
+                next_page_num,
+            };
+            data[..part.len()].copy_from_slice(part);
+
+            self.log_page(xid, PageOp::OverflowWrite, &mut page, &before)?;
+            page_ref.set_dirty();
+
+            next_page_num = page.page_num();
+        }
+
+        Ok(OverflowPointer {
+            page_num: next_page_num,
+            len: value.len() as u32,
+        })
+    }
+
+    /// Reads a value written by `write_overflow`.
+    pub fn read_overflow(&self, pointer: OverflowPointer) -> Result<Vec<u8>> {
+        let OverflowPointer { mut page_num, len } = pointer;
+        let mut value = Vec::with_capacity(len as usize);
+
+        while value.len() < len as usize {
+            ensure!(
+                page_num != 0,
+                error::InvalidOverflowSnafu {
+                    page_num: pointer.page_num
+                }
+            );
+
+            let page_ref = self.fetch_page(page_num)?;
+            let page = page_ref.read();
+            ensure!(
+                page[0] == PageType::Overflow as u8,
+                error::InvalidPageTypeSnafu { page_type: page[0] }
+            );
+
+            let (header, data) = page.split_at(size_of::<Header>());
+            let header: &Header = from_bytes(header);
+            value.extend_from_slice(&data[..header.len as usize]);
+            page_num = header.next_page_num;
+        }
+
+        ensure!(
+            value.len() == len as usize,
+            error::InvalidOverflowSnafu {
+                page_num: pointer.page_num
+            }
+        );
+
+        Ok(value)
+    }
+
+    /// Puts the pages of a chain written by `write_overflow` on the free list, once the
+    /// value is no longer pointed to by any row.
+    pub fn free_overflow(&self, xid: Xid, pointer: OverflowPointer) -> Result<()> {
+        let OverflowPointer { mut page_num, len } = pointer;
+        let mut freed_len = 0;
+
+        while freed_len < len as usize {
+            ensure!(
+                page_num != 0,
+                error::InvalidOverflowSnafu {
+                    page_num: pointer.page_num
+                }
+            );
+
+            let next_page_num = {
+                let page_ref = self.fetch_page(page_num)?;
+                let page = page_ref.read();
+                ensure!(
+                    page[0] == PageType::Overflow as u8,
+                    error::InvalidPageTypeSnafu { page_type: page[0] }
+                );
+
+                let header: &Header = from_bytes(&page[..size_of::<Header>()]);
+                freed_len += header.len as usize;
+                header.next_page_num
+            };
+
+            self.free_list().free(xid, page_num)?;
+            page_num = next_page_num;
+        }
+
+        Ok(())
+    }
+
+    /// Frees the overflow pages the value points to, if the tree knows how values are
+    /// encoded.
+    pub(super) fn free_value_overflow(&self, xid: Xid, value: &[u8]) -> Result<()> {
+        let Some(values_codec) = &self.values_codec else {
+            return Ok(());
+        };
+
+        let pointers =
+            values_codec
+                .overflow_pointers(value)
+                .map_err(|e| error::Error::Decoding {
+                    source: Box::new(e),
+                })?;
+        pointers
+            .into_iter()
+            .try_for_each(|pointer| self.free_overflow(xid, pointer))
+    }
+}
@@ -1,76 +1,508 @@
 use {
-    super::*,
+    super::{free_list::FreeList, *},
     crate::codec::Codec,
-    def::{meta::Column, SqlType, Value},
+    def::{
+        meta::Column,
+        storage::{Decoder, Encoder},
+        SqlType, Value,
+    },
     rand::prelude::*,
-    storage::DEFAULT_PAGE_SIZE,
+    std::{
+        collections::BTreeSet,
+        fs,
+        ops::{Bound, RangeBounds},
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    },
+    storage::{control::ControlFile, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, MIN_PAGE_SIZE},
     tempfile::tempdir,
 };
 
 #[test]
 fn sequential_insertion() -> Result<()> {
-    let dir = tempdir().unwrap();
-
     let attr = Column::new(1, 1, "abc".to_string(), SqlType::TinyUint, 4, false);
     let codec = Codec::new(vec![attr]);
 
-    let manager = BufferManager::new(10, DEFAULT_PAGE_SIZE, dir.path().to_path_buf());
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
     let file_node = FileNode::new(1, 2, 3);
 
-    BTree::<Codec>::init(file_node, &manager)?;
-    let mut btree = BTree::new(codec, 1, file_node, &manager);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(codec, file_node, &manager);
 
     let range = 0..120;
 
     for i in range.clone() {
-        btree.insert(&vec![Value::TinyUint(i)], &[i * 2 + 5])?;
+        btree.insert(xid, &vec![Value::TinyUint(i)], &[i * 2 + 5])?;
     }
 
     for i in range {
         let (mut cursor, is_matched) = btree.cursor(&vec![Value::TinyUint(i)]).unwrap().unwrap();
 
-        let (_, value) = cursor.next().unwrap();
+        let (_, value) = cursor.next().unwrap()?;
 
         assert!(is_matched);
         assert_eq!(&[i * 2 + 5].as_ref(), &value);
     }
 
-    dir.close().unwrap();
-
     Ok(())
 }
 
 #[test]
 fn random_insertion() -> Result<()> {
-    let dir = tempdir().unwrap();
-
     let attr = Column::new(1, 1, "abc".to_string(), SqlType::TinyUint, 4, false);
     let codec = Codec::new(vec![attr]);
 
-    let manager = BufferManager::new(10, DEFAULT_PAGE_SIZE, dir.path().to_path_buf());
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
     let file_node = FileNode::new(1, 2, 3);
 
-    BTree::<Codec>::init(file_node, &manager)?;
-    let mut btree = BTree::new(codec, 1, file_node, &manager);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(codec, file_node, &manager);
 
     let mut rng = rand::thread_rng();
     let mut nums: Vec<u8> = (0..120).collect();
     nums.shuffle(&mut rng);
 
     for &i in nums.iter() {
-        btree.insert(&vec![Value::TinyUint(i)], &[i * 2 + 5])?;
+        btree.insert(xid, &vec![Value::TinyUint(i)], &[i * 2 + 5])?;
     }
 
     for &i in nums.iter() {
         let (mut cursor, is_matched) = btree.cursor(&vec![Value::TinyUint(i)]).unwrap().unwrap();
 
-        let (_, value) = cursor.next().unwrap();
+        let (_, value) = cursor.next().unwrap()?;
 
         assert!(is_matched);
         assert_eq!(&[i * 2 + 5].as_ref(), &value);
     }
 
-    dir.close().unwrap();
+    Ok(())
+}
+
+#[test]
+fn insert_update_upsert() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    let key = |k: u32| vec![Value::Uint(k)];
+
+    assert_eq!(btree.get(&key(1))?, None);
+    assert!(matches!(
+        btree.update(xid, &key(1), &[1]),
+        Err(error::Error::KeyNotFound { .. })
+    ));
+
+    for k in 0..500 {
+        btree.insert(xid, &key(k), &[k as u8])?;
+    }
+    assert!(matches!(
+        btree.insert(xid, &key(7), &[0]),
+        Err(error::Error::DuplicateKey { .. })
+    ));
+    assert_eq!(btree.get(&key(7))?, Some(vec![7]));
+
+    // values may grow and shrink in place
+    assert_eq!(btree.update(xid, &key(7), &[70; 100])?, [7]);
+    assert_eq!(btree.update(xid, &key(8), &[])?, [8]);
+    assert_eq!(btree.get(&key(7))?, Some(vec![70; 100]));
+    assert_eq!(btree.get(&key(8))?, Some(vec![]));
+
+    assert_eq!(btree.upsert(xid, &key(9), &[90])?, Some(vec![9]));
+    assert_eq!(btree.upsert(xid, &key(1000), &[100])?, None);
+    assert_eq!(btree.get(&key(9))?, Some(vec![90]));
+    assert_eq!(btree.get(&key(1000))?, Some(vec![100]));
+
+    let (cursor, _) = btree.cursor(&key(0))?.unwrap();
+    assert_eq!(cursor.count(), 501);
+
+    Ok(())
+}
+
+#[test]
+fn variable_length_keys() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Varchar, 200, false);
+    let manager = BufferManager::in_memory(16, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    let key = |k: u32| vec![Value::String(format!("{:05}", k))];
+    let leaves_count = |btree: &BTree<Codec>| -> Result<usize> {
+        let (mut stack, _) = btree.search(&key(0))?;
+        let mut page_num = stack.pop_back().unwrap().page_num;
+        let mut count = 0;
+        while page_num != 0 {
+            let page_ref = btree.fetch_page(page_num)?;
+            let mut page = page_ref.write();
+            page_num = match Node::new(&mut page, &btree.key_codec)? {
+                Node::Leaf(leaf) => leaf.next_page_num(),
+                Node::Branch(_) => unreachable!(),
+            };
+            count += 1;
+        }
+        Ok(count)
+    };
+
+    let mut rng = rand::thread_rng();
+    let mut keys: Vec<u32> = (0..2000).collect();
+    keys.shuffle(&mut rng);
+    for &k in &keys {
+        btree.insert(xid, &key(k), &[k as u8; 10])?;
+    }
+
+    // the short keys fill the leaves, as if the column was no wider than them
+    let count = leaves_count(&btree)?;
+    assert!(count < 2000 / 50);
+
+    // values growing out of their leaves split them
+    for &k in &keys {
+        btree.update(xid, &key(k), &[k as u8; 100])?;
+    }
+    assert!(leaves_count(&btree)? > count);
+
+    let scanned = btree.range(..)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(scanned.len(), 2000);
+    for (k, (scanned_key, value)) in (0..2000).zip(scanned) {
+        assert_eq!(scanned_key, key(k));
+        assert_eq!(value, [k as u8; 100]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn failed_update_keeps_value() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    // the meta page and the root leaf take the whole pool
+    let manager = BufferManager::in_memory(2, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    let key = |k: u32| vec![Value::Uint(k)];
+
+    for k in 0..20 {
+        btree.insert(xid, &key(k), &[k as u8; 150])?;
+    }
+
+    // the leaf has no room for the value, and no page to split into
+    assert!(btree.update(xid, &key(7), &[70; 1500]).is_err());
+    assert_eq!(btree.get(&key(7))?, Some(vec![7; 150]));
+    assert_eq!(btree.range(..)?.count(), 20);
+
+    manager.resize(8).unwrap();
+    assert_eq!(btree.update(xid, &key(7), &[70; 1500])?, [7; 150]);
+    assert_eq!(btree.get(&key(7))?, Some(vec![70; 1500]));
+
+    let scanned = btree.range(..)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(scanned.len(), 20);
+    for (k, (scanned_key, value)) in (0..20).zip(scanned) {
+        assert_eq!(scanned_key, key(k));
+        assert_eq!(value.len(), if k == 7 { 1500 } else { 150 });
+    }
+
+    Ok(())
+}
+
+#[test]
+fn deletion() -> Result<()> {
+    let attr = Column::new(1, 1, "abc".to_string(), SqlType::TinyUint, 4, false);
+    let codec = Codec::new(vec![attr]);
+
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(codec, file_node, &manager);
+
+    for i in 0..120 {
+        btree.insert(xid, &vec![Value::TinyUint(i)], &[i])?;
+    }
+
+    for i in (0..120).step_by(2) {
+        assert!(btree.delete(xid, &vec![Value::TinyUint(i)])?);
+    }
+    assert!(!btree.delete(xid, &vec![Value::TinyUint(0)])?);
+    assert!(!btree.delete(xid, &vec![Value::TinyUint(200)])?);
+
+    let (cursor, is_matched) = btree.cursor(&vec![Value::TinyUint(0)])?.unwrap();
+    assert!(!is_matched);
+    let values = cursor
+        .map(|entry| entry.map(|(_, value)| value[0]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(values, (1..120).step_by(2).collect::<Vec<_>>());
+
+    // deleted keys may be inserted again
+    btree.insert(xid, &vec![Value::TinyUint(10)], &[10])?;
+    let (mut cursor, is_matched) = btree.cursor(&vec![Value::TinyUint(10)])?.unwrap();
+    assert!(is_matched);
+    assert_eq!(cursor.next().unwrap()?.1, [10]);
+
+    Ok(())
+}
+
+#[test]
+fn deletion_merges_nodes() -> Result<()> {
+    let dir = tempdir().unwrap();
+    ControlFile::default().write(dir.path()).unwrap();
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Varchar, 128, false);
+    let manager = BufferManager::new(16, dir.path().to_path_buf()).unwrap();
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+    let file_size = || {
+        fs::metadata(dir.path().join(file_node.file_path()))
+            .unwrap()
+            .len()
+    };
+    let meta = |btree: &BTree<Codec>| -> Result<Meta> {
+        let meta_page_ref = btree.fetch_page(META_PAGE_NUM)?;
+        let meta_page = meta_page_ref.read();
+        Ok(*Meta::from_bytes(&meta_page))
+    };
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    // keys of various lengths, in the order of the numbers they start with
+    let key_of = |k: u32| {
+        vec![Value::String(format!(
+            "{:08}{}",
+            k,
+            "-".repeat(k as usize % 60)
+        ))]
+    };
+
+    let mut rng = rand::thread_rng();
+    let mut keys: Vec<u32> = (0..3000).collect();
+    keys.shuffle(&mut rng);
+
+    for &key in &keys {
+        btree.insert(xid, &key_of(key), &[key as u8; 200])?;
+    }
+    let (size, level) = (file_size(), meta(&btree)?.level);
+    assert!(level > 2);
+
+    let mut remaining = keys.clone();
+    remaining.sort();
+    for (i, &key) in keys.iter().enumerate() {
+        assert!(btree.delete(xid, &key_of(key))?);
+        remaining.remove(remaining.binary_search(&key).unwrap());
+
+        if i % 500 == 0 {
+            let scanned = btree
+                .range(..)?
+                .map(|entry| {
+                    let (key, value) = entry?;
+                    Ok(match &key[0] {
+                        Value::String(s) => {
+                            let k = s[..8].parse::<u32>().unwrap();
+                            assert_eq!(value, [k as u8; 200], "entry of key {} is broken", k);
+                            k
+                        }
+                        _ => unreachable!(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(scanned, remaining);
+            assert!(meta(&btree)?.level <= level);
+        }
+    }
+
+    // the root leaf is all that is left
+    let Meta {
+        level, free_list, ..
+    } = meta(&btree)?;
+    assert_eq!(level, 1);
+    assert_ne!(free_list, 0);
+    assert!(btree.range(..)?.next().is_none());
+
+    // and the pages of the other nodes are taken again
+    for &key in &keys {
+        btree.insert(xid, &key_of(key), &[key as u8; 200])?;
+    }
+    assert_eq!(file_size(), size);
+
+    Ok(())
+}
+
+#[test]
+fn range_cursors() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let manager = BufferManager::in_memory(16, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    let key = |k: u32| vec![Value::Uint(k)];
+    let keys_of = |cursor: Cursor<Codec, Vec<Value>>, reversed: bool| -> Vec<u32> {
+        let unwrap = |entry: Result<(Vec<Value>, Vec<u8>)>| match entry.unwrap().0[0] {
+            Value::Uint(k) => k,
+            _ => unreachable!(),
+        };
+        match reversed {
+            true => cursor.rev().map(unwrap).collect(),
+            false => cursor.map(unwrap).collect(),
+        }
+    };
+
+    // an empty tree has no entry in any range
+    assert_eq!(btree.range(..)?.count(), 0);
+
+    // even keys, with leaves split and merged
+    let mut rng = rand::thread_rng();
+    let mut keys: Vec<u32> = (0..2000).collect();
+    keys.shuffle(&mut rng);
+    for &k in &keys {
+        btree.insert(xid, &key(k), &[k as u8; 100])?;
+    }
+    for k in (1..2000).step_by(2) {
+        btree.delete(xid, &key(k))?;
+    }
+    let expected = (0..2000).step_by(2).collect::<BTreeSet<u32>>();
+
+    assert_eq!(
+        keys_of(btree.range(..)?, false),
+        Vec::from_iter(expected.clone())
+    );
+    assert_eq!(
+        keys_of(btree.range(..)?, true),
+        Vec::from_iter(expected.iter().rev().copied())
+    );
+
+    let bound = |kind: u8, k: u32| match kind {
+        0 => Bound::Included(k),
+        1 => Bound::Excluded(k),
+        _ => Bound::Unbounded,
+    };
+    for _ in 0..200 {
+        let (lower, upper) = (rng.gen_range(0..2100), rng.gen_range(0..2100));
+        let (lower, upper) = (
+            bound(rng.gen_range(0..3), lower),
+            bound(rng.gen_range(0..3), upper),
+        );
+        let range = (lower.map(key), upper.map(key));
+        let in_range = expected
+            .iter()
+            .copied()
+            .filter(|k| (lower, upper).contains(k))
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys_of(btree.range(range.clone())?, false), in_range);
+        assert_eq!(
+            keys_of(btree.range(range)?, true),
+            Vec::from_iter(in_range.iter().rev().copied())
+        );
+    }
+
+    // both ends meet in the middle
+    let mut cursor = btree.range(key(100)..=key(198))?;
+    let mut taken = vec![];
+    while let Some(front) = cursor.next() {
+        taken.push(front?.0[0].clone());
+        if let Some(back) = cursor.next_back() {
+            taken.push(back?.0[0].clone());
+        }
+    }
+    assert_eq!(taken.len(), 50);
+    assert_eq!(taken[..2], [Value::Uint(100), Value::Uint(198)]);
+
+    Ok(())
+}
+
+#[test]
+fn bulk_loading() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let manager = BufferManager::in_memory(16, &ControlFile::default());
+    let xid = manager.wal().begin().unwrap();
+    let key = |k: u32| vec![Value::Uint(k)];
+    let keys_of = |btree: &BTree<Codec>, reversed: bool| -> Result<Vec<u32>> {
+        let unwrap = |(key, value): (Vec<Value>, Vec<u8>)| match key[0] {
+            Value::Uint(k) if value == [k as u8; 100] => k,
+            _ => panic!("entry of key {:?} is broken", key),
+        };
+        let cursor = btree.range(..)?;
+        match reversed {
+            true => cursor.rev().map(|entry| entry.map(unwrap)).collect(),
+            false => cursor.map(|entry| entry.map(unwrap)).collect(),
+        }
+    };
+    let new_btree = |file_node: FileNode| -> Result<BTree<Codec>> {
+        BTree::<Codec>::init(xid, file_node, &manager)?;
+        Ok(BTree::new(
+            Codec::new(vec![column.clone()]),
+            file_node,
+            &manager,
+        ))
+    };
+
+    // even keys, loaded at several fill factors
+    let mut expected = (0..4000).step_by(2).collect::<BTreeSet<u32>>();
+    let entries = || (0..4000).step_by(2).map(|k| (key(k), [k as u8; 100]));
+    for (i, fill_factor) in [100, DEFAULT_FILL_FACTOR, 30].into_iter().enumerate() {
+        let mut btree = new_btree(FileNode::new(1, 2, 10 + i as u32))?;
+        btree.bulk_load(xid, entries(), fill_factor)?;
+
+        assert!(btree.root_page_num()? != 0);
+        assert_eq!(keys_of(&btree, false)?, Vec::from_iter(expected.clone()));
+        assert_eq!(
+            keys_of(&btree, true)?,
+            Vec::from_iter(expected.iter().rev().copied())
+        );
+        assert_eq!(btree.get(&key(1000))?, Some(vec![232; 100]));
+    }
+
+    // the loaded tree takes inserts and deletes as any other
+    let mut btree = new_btree(FileNode::new(1, 2, 3))?;
+    btree.bulk_load(xid, entries(), DEFAULT_FILL_FACTOR)?;
+    for k in (1..4000).step_by(2) {
+        btree.insert(xid, &key(k), &[k as u8; 100])?;
+        expected.insert(k);
+    }
+    assert_eq!(keys_of(&btree, false)?, Vec::from_iter(expected.clone()));
+    for k in (0..4000).step_by(3) {
+        assert!(btree.delete(xid, &key(k))?);
+        expected.remove(&k);
+    }
+    assert_eq!(keys_of(&btree, false)?, Vec::from_iter(expected.clone()));
+    assert!(matches!(
+        btree.bulk_load(xid, entries(), DEFAULT_FILL_FACTOR),
+        Err(error::Error::NotEmpty { .. })
+    ));
+
+    // the keys must be strictly ascending
+    let mut btree = new_btree(FileNode::new(1, 2, 4))?;
+    let unsorted = [5, 6, 7, 1].map(|k| (key(k), [k as u8; 100]));
+    assert!(matches!(
+        btree.bulk_load(xid, unsorted, DEFAULT_FILL_FACTOR),
+        Err(error::Error::UnsortedKeys { .. })
+    ));
+    let mut btree = new_btree(FileNode::new(1, 2, 5))?;
+    let duplicate = [5, 6, 6].map(|k| (key(k), [k as u8; 100]));
+    assert!(matches!(
+        btree.bulk_load(xid, duplicate, DEFAULT_FILL_FACTOR),
+        Err(error::Error::DuplicateKey { .. })
+    ));
+    assert!(matches!(
+        btree.bulk_load(xid, entries(), 0),
+        Err(error::Error::InvalidFillFactor { fill_factor: 0, .. })
+    ));
+
+    // nothing to load leaves the tree empty
+    let mut btree = new_btree(FileNode::new(1, 2, 6))?;
+    btree.bulk_load(xid, std::iter::empty::<(Vec<Value>, Vec<u8>)>(), 100)?;
+    assert_eq!(btree.root_page_num()?, 0);
+    assert_eq!(btree.range(..)?.count(), 0);
 
     Ok(())
 }
@@ -78,31 +510,34 @@ fn random_insertion() -> Result<()> {
 #[test]
 fn flush() -> Result<()> {
     let dir = tempdir().unwrap();
+    ControlFile::default().write(dir.path()).unwrap();
 
     let attr = Column::new(1, 1, "abc".to_string(), SqlType::TinyUint, 4, false);
     let key_codec = Codec::new(vec![attr]);
 
-    let manager = BufferManager::new(10, DEFAULT_PAGE_SIZE, dir.path().to_path_buf());
+    let manager = BufferManager::new(10, dir.path().to_path_buf()).unwrap();
     let file_node = FileNode::new(1, 2, 3);
 
-    BTree::<Codec>::init(file_node, &manager)?;
-    let mut btree = BTree::new(key_codec.clone(), 1, file_node, &manager);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(key_codec.clone(), file_node, &manager);
 
     let range = 0..120;
 
     for i in range.clone() {
-        btree.insert(&vec![Value::TinyUint(i)], &[i * 2 + 5])?;
+        btree.insert(xid, &vec![Value::TinyUint(i)], &[i * 2 + 5])?;
     }
 
     manager.flush_pages().unwrap();
 
-    let manager = BufferManager::new(10, DEFAULT_PAGE_SIZE, dir.path().to_path_buf());
-    let btree2 = BTree::new(key_codec, 1, file_node, &manager);
+    let manager = BufferManager::new(10, dir.path().to_path_buf()).unwrap();
+    let btree2 = BTree::new(key_codec, file_node, &manager);
 
     for i in range {
         let (mut cursor, is_matched) = btree2.cursor(&vec![Value::TinyUint(i)]).unwrap().unwrap();
 
-        let (_, value) = cursor.next().unwrap();
+        let (_, value) = cursor.next().unwrap()?;
 
         assert!(is_matched);
         assert_eq!(&[i * 2 + 5].as_ref(), &value);
@@ -112,3 +547,426 @@ fn flush() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn small_buffer_pool() -> Result<()> {
+    let attr = Column::new(1, 1, "abc".to_string(), SqlType::Uint, 4, false);
+    let key_codec = Codec::new(vec![attr]);
+
+    // the tree grows far beyond the pool, so pages are evicted and read back all the time
+    let manager = BufferManager::in_memory(8, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(key_codec, file_node, &manager);
+
+    let range = 0..5000u32;
+
+    for i in range.clone() {
+        btree.insert(xid, &vec![Value::Uint(i)], &(i * 3).to_le_bytes())?;
+    }
+
+    let (cursor, _) = btree.cursor(&vec![Value::Uint(0)])?.unwrap();
+    let entries = cursor.collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(entries.len(), range.len());
+    for (i, (key, value)) in range.zip(entries) {
+        assert_eq!(key, vec![Value::Uint(i)]);
+        assert_eq!(value, (i * 3).to_le_bytes());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn page_sizes() -> Result<()> {
+    let attr = Column::new(1, 1, "abc".to_string(), SqlType::Uint, 4, false);
+    let key_codec = Codec::new(vec![attr]);
+
+    let mut last_page_count = usize::MAX;
+    for page_size in [MIN_PAGE_SIZE, 1 << 13, 1 << 14, MAX_PAGE_SIZE] {
+        let dir = tempdir().unwrap();
+        ControlFile {
+            checksums: false,
+            page_size,
+        }
+        .write(dir.path())
+        .unwrap();
+
+        let manager = BufferManager::new(8, dir.path().to_path_buf()).unwrap();
+        let file_node = FileNode::new(1, 2, 3);
+
+        let xid = manager.wal().begin().unwrap();
+
+        BTree::<Codec>::init(xid, file_node, &manager)?;
+        let mut btree = BTree::new(key_codec.clone(), file_node, &manager);
+
+        let range = 0..10000u32;
+
+        for i in range.clone() {
+            btree.insert(xid, &vec![Value::Uint(i)], &i.to_le_bytes())?;
+        }
+
+        let (cursor, _) = btree.cursor(&vec![Value::Uint(0)])?.unwrap();
+        let entries = cursor.collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(entries.len(), range.len());
+        for (i, (key, value)) in range.zip(entries) {
+            assert_eq!(key, vec![Value::Uint(i)]);
+            assert_eq!(value, i.to_le_bytes());
+        }
+
+        // bigger pages hold more entries
+        let page_count = fs::metadata(dir.path().join(file_node.file_path()))
+            .unwrap()
+            .len() as usize
+            / page_size;
+        assert!(page_count < last_page_count);
+        last_page_count = page_count;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn check_meta_page() -> Result<()> {
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+
+    let xid = manager.wal().begin().unwrap();
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    BTree::<Codec>::check(file_node, &manager)?;
+
+    let other_file_node = FileNode::new(1, 2, 4);
+    manager.new_page(&other_file_node).unwrap();
+    assert!(matches!(
+        BTree::<Codec>::check(other_file_node, &manager),
+        Err(error::Error::InvalidMeta { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn free_pages_are_reused() -> Result<()> {
+    let dir = tempdir().unwrap();
+    ControlFile::default().write(dir.path()).unwrap();
+    let file_node = FileNode::new(1, 2, 3);
+    let page_count = || {
+        fs::metadata(dir.path().join(file_node.file_path()))
+            .unwrap()
+            .len() as usize
+            / DEFAULT_PAGE_SIZE
+    };
+
+    let freed = {
+        let manager = BufferManager::new(10, dir.path().to_path_buf()).unwrap();
+        let xid = manager.wal().begin().unwrap();
+        BTree::<Codec>::init(xid, file_node, &manager)?;
+
+        let free_list = FreeList::new(&manager, file_node);
+        let pages = (0..3)
+            .map(|_| Ok(free_list.allocate(xid)?.page_num()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(page_count(), 4);
+
+        for &page_num in &pages[..2] {
+            free_list.free(xid, page_num)?;
+        }
+
+        // the last freed page is reused first
+        let page = free_list.allocate(xid)?;
+        assert_eq!(page.page_num(), pages[1]);
+        assert!(page.read().iter().all(|&b| b == 0));
+        assert_eq!(page_count(), 4);
+
+        manager.wal().commit(xid).unwrap();
+        manager.flush_pages().unwrap();
+
+        pages[0]
+    };
+
+    // the free list survives a restart
+    let manager = BufferManager::new(10, dir.path().to_path_buf()).unwrap();
+    let xid = manager.wal().begin().unwrap();
+    let free_list = FreeList::new(&manager, file_node);
+
+    assert_eq!(free_list.allocate(xid)?.page_num(), freed);
+    assert_eq!(free_list.allocate(xid)?.page_num(), 4);
+    assert_eq!(page_count(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn scans_read_ahead() -> Result<()> {
+    // long keys, so that the leaves are under several parents
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Char, 100, false);
+    let manager = BufferManager::in_memory(8, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![column]), file_node, &manager);
+    let key_of = |key: u32| vec![Value::String(format!("{:0100}", key))];
+
+    let keys = 0..1000;
+    for key in keys.clone() {
+        btree.insert(xid, &key_of(key), &[key as u8; 100])?;
+    }
+
+    // the leaves after the first one, as linked to each other
+    let (mut stack, _) = btree.search(&key_of(0))?;
+    let mut page_num = stack.pop_back().unwrap().page_num;
+    let mut leaves = vec![];
+    loop {
+        let page_ref = btree.fetch_page(page_num)?;
+        let mut page = page_ref.write();
+        page_num = match Node::new(&mut page, &btree.key_codec)? {
+            Node::Leaf(leaf) => leaf.next_page_num(),
+            Node::Branch(_) => unreachable!(),
+        };
+
+        if page_num == 0 {
+            break;
+        }
+        leaves.push(page_num);
+    }
+
+    // the siblings under the parent of the first leaf, and no further
+    let next_leaves = btree.next_leaves(&key_of(0))?;
+    assert!(!next_leaves.is_empty() && next_leaves.len() < leaves.len());
+    assert_eq!(next_leaves, leaves[..next_leaves.len()]);
+
+    // a scan through a pool smaller than the tree, reading ahead
+    let (cursor, _) = btree.cursor(&key_of(0))?.unwrap();
+    let scanned = cursor
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(scanned, keys.map(key_of).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[test]
+fn overflow_values() -> Result<()> {
+    let key_column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let value_column = Column::new(1, 2, "b".to_string(), SqlType::Varchar, 20000, false);
+    let values_codec = Codec::new(vec![value_column]).with_overflow();
+
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![key_column]), file_node, &manager);
+
+    // documents spanning several pages, next to short values kept in the rows
+    let value = |key: u32| {
+        let len = if key % 2 == 0 {
+            5000 + key as usize * 100
+        } else {
+            10
+        };
+        vec![Value::String(
+            char::from(b'a' + key as u8).to_string().repeat(len),
+        )]
+    };
+
+    let keys = 0..20;
+    for key in keys.clone() {
+        let bytes = values_codec
+            .encode_with(&value(key), |value| btree.write_overflow(xid, value))
+            .unwrap();
+        assert!(bytes.len() <= values_codec.max_size());
+
+        btree.insert(xid, &vec![Value::Uint(key)], &bytes)?;
+    }
+
+    let (cursor, _) = btree.cursor(&vec![Value::Uint(0)])?.unwrap();
+    for (key, entry) in keys.zip(cursor) {
+        let (_, bytes) = entry?;
+
+        // without the overflow pages, only the short values can be decoded
+        assert_eq!(values_codec.decode(&bytes).is_ok(), key % 2 == 1);
+
+        let (values, _) = values_codec
+            .decode_with(&bytes, |pointer| btree.read_overflow(pointer))
+            .unwrap();
+        assert_eq!(values, value(key));
+    }
+
+    // a pointer to a leaf
+    assert!(matches!(
+        btree.read_overflow(OverflowPointer {
+            page_num: btree.root_page_num()?,
+            len: 10,
+        }),
+        Err(error::Error::InvalidPageType { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn free_overflow_pages() -> Result<()> {
+    let key_column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let value_column = Column::new(1, 2, "b".to_string(), SqlType::Varchar, 20000, false);
+    let values_codec = Codec::new(vec![value_column]).with_overflow();
+
+    let manager = BufferManager::in_memory(10, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(Codec::new(vec![key_column]), file_node, &manager)
+        .with_overflow(values_codec.clone());
+    let free_list = FreeList::new(&manager, file_node);
+
+    let key = |key: u32| vec![Value::Uint(key)];
+    let large = values_codec
+        .encode_with(&[Value::String("a".repeat(15000))], |value| {
+            btree.write_overflow(xid, value)
+        })
+        .unwrap();
+    let small = values_codec
+        .encode(&vec![Value::String("b".to_string())])
+        .unwrap();
+
+    // the pages taken off the free list before the file grows again
+    let reused_count = || -> Result<usize> {
+        let end = manager.new_page(&file_node).unwrap().page_num();
+        let mut count = 0;
+        while free_list.allocate(xid)?.page_num() < end {
+            count += 1;
+        }
+        Ok(count)
+    };
+    let chain_len = {
+        let start = manager.new_page(&file_node).unwrap().page_num();
+        let bytes = values_codec
+            .encode_with(&[Value::String("a".repeat(15000))], |value| {
+                btree.write_overflow(xid, value)
+            })
+            .unwrap();
+        let end = manager.new_page(&file_node).unwrap().page_num();
+        btree.insert(xid, &key(0), &bytes)?;
+        end - start - 1
+    };
+    assert!(chain_len > 1);
+    assert_eq!(reused_count()?, 0);
+
+    // a large value replaced by a small one
+    btree.update(xid, &key(0), &small)?;
+    assert_eq!(reused_count()?, chain_len as usize);
+
+    // a small value replaced by a large one frees nothing
+    btree.insert(xid, &key(1), &small)?;
+    btree.update(xid, &key(1), &large)?;
+    assert_eq!(reused_count()?, 0);
+
+    // a deleted row with a large value
+    assert!(btree.delete(xid, &key(1))?);
+    assert_eq!(reused_count()?, chain_len as usize);
+
+    // the values left in the tree
+    assert_eq!(btree.get(&key(0))?, Some(small));
+    assert_eq!(btree.get(&key(1))?, None);
+
+    Ok(())
+}
+
+#[test]
+fn reads_during_splits() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let codec = Codec::new(vec![column]);
+
+    let manager = BufferManager::in_memory(500, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+    let key = |k: u32| vec![Value::Uint(k)];
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(codec.clone(), file_node, &manager);
+    for k in (0..4000).step_by(2) {
+        btree.insert(xid, &key(k), &[1; 100])?;
+    }
+
+    // the keys between those inserted split every leaf, while the others are read
+    let is_done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut btree = BTree::new(codec.clone(), file_node, &manager);
+            for k in (1..4000).step_by(2) {
+                btree.insert(xid, &key(k), &[2; 100]).unwrap();
+            }
+            is_done.store(true, Ordering::SeqCst);
+        });
+
+        while !is_done.load(Ordering::SeqCst) {
+            for k in (0..4000).step_by(2) {
+                assert_eq!(btree.get(&key(k))?, Some(vec![1; 100]), "key {}", k);
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn scans_during_splits() -> Result<()> {
+    let column = Column::new(1, 1, "a".to_string(), SqlType::Uint, 4, false);
+    let codec = Codec::new(vec![column]);
+
+    let manager = BufferManager::in_memory(500, &ControlFile::default());
+    let file_node = FileNode::new(1, 2, 3);
+    let xid = manager.wal().begin().unwrap();
+    let key = |k: u32| vec![Value::Uint(k)];
+    let uint = |key: &[Value]| match key[0] {
+        Value::Uint(k) => k,
+        _ => unreachable!(),
+    };
+
+    BTree::<Codec>::init(xid, file_node, &manager)?;
+    let mut btree = BTree::new(codec.clone(), file_node, &manager);
+    for k in (0..4000).step_by(2) {
+        btree.insert(xid, &key(k), &[1; 100])?;
+    }
+
+    // the leaves are split, then merged, under the cursors, which must neither skip nor
+    // repeat a key
+    let is_done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut btree = BTree::new(codec.clone(), file_node, &manager);
+            for k in (1..4000).step_by(2) {
+                btree.insert(xid, &key(k), &[2; 100]).unwrap();
+            }
+            for k in (1..4000).step_by(2) {
+                btree.delete(xid, &key(k)).unwrap();
+            }
+            is_done.store(true, Ordering::SeqCst);
+        });
+
+        while !is_done.load(Ordering::SeqCst) {
+            let forward = btree
+                .range(..)?
+                .map(|entry| entry.map(|(key, _)| uint(&key)))
+                .collect::<Result<Vec<_>>>()?;
+            assert!(forward.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(forward.iter().filter(|&&k| k % 2 == 0).count(), 2000);
+
+            let backward = btree
+                .range(..)?
+                .rev()
+                .map(|entry| entry.map(|(key, _)| uint(&key)))
+                .collect::<Result<Vec<_>>>()?;
+            assert!(backward.windows(2).all(|pair| pair[0] > pair[1]));
+            assert_eq!(backward.iter().filter(|&&k| k % 2 == 0).count(), 2000);
+        }
+
+        Ok(())
+    })
+}
@@ -1,4 +1,5 @@
 use {
+    crate::btree::OverflowPointer,
     byteorder::{ReadBytesExt, WriteBytesExt, LE},
     common::dsa::bitmap::{Bitmap, BitmapMut},
     def::{
@@ -33,6 +34,19 @@ pub enum Error {
         source: FromUtf8Error,
     },
 
+    Overflow {
+        source: Box<dyn std::error::Error>,
+    },
+
+    #[snafu(display(
+        "the value of column `{}` is in overflow pages, which are not at hand",
+        column
+    ))]
+    OverflowUnavailable {
+        backtrace: Backtrace,
+        column: String,
+    },
+
     #[snafu(display("internal error"))]
     Internal {
         backtrace: Backtrace,
@@ -41,12 +55,24 @@ pub enum Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Values of variable-length columns longer than this are kept in overflow pages, by
+/// codecs made `with_overflow`.
+pub const OVERFLOW_THRESHOLD: usize = 512;
+
+/// Set in the length of a value kept in overflow pages, the row holds a pointer instead.
+const OVERFLOWED: u16 = 0x8000;
+
+type WriteOverflow<'a> = &'a mut dyn FnMut(&[u8]) -> Result<OverflowPointer>;
+type ReadOverflow<'a> = &'a mut dyn FnMut(OverflowPointer) -> Result<Vec<u8>>;
+
 #[derive(Clone)]
 pub struct Codec {
     columns: Vec<Column>,
     var_lens_byte_count: usize,
     bitmap_byte_count: usize,
     data_region_start: usize,
+    /// whether large values are kept out of the row
+    overflow: bool,
 }
 
 impl Codec {
@@ -59,20 +85,103 @@ impl Codec {
             var_lens_byte_count,
             bitmap_byte_count,
             data_region_start,
+            overflow: false,
         }
     }
-}
 
-impl Encoder for Codec {
-    type Item = Vec<Value>;
-    type Error = Error;
+    /// Keeps values longer than `OVERFLOW_THRESHOLD` in overflow pages, so that large
+    /// columns take little room in the rows.
+    pub fn with_overflow(mut self) -> Self {
+        self.overflow = true;
+        self
+    }
 
-    fn encode(&self, values: &Vec<Value>) -> Result<Vec<u8>> {
+    /// Encodes the values, handing those to be kept out of the row to `write_overflow`.
+    pub fn encode_with<F, E>(&self, values: &[Value], mut write_overflow: F) -> Result<Vec<u8>>
+    where
+        F: FnMut(&[u8]) -> std::result::Result<OverflowPointer, E>,
+        E: std::error::Error + 'static,
+    {
+        self.encode_row(
+            values,
+            Some(&mut |value| {
+                write_overflow(value).map_err(|e| Error::Overflow {
+                    source: Box::new(e),
+                })
+            }),
+        )
+    }
+
+    /// Decodes the values, reading those kept out of the row through `read_overflow`.
+    /// The overflow pages are only read here, a cursor passing the row doesn't touch them.
+    pub fn decode_with<F, E>(&self, src: &[u8], mut read_overflow: F) -> Result<(Vec<Value>, usize)>
+    where
+        F: FnMut(OverflowPointer) -> std::result::Result<Vec<u8>, E>,
+        E: std::error::Error + 'static,
+    {
+        self.decode_row(
+            src,
+            Some(&mut |pointer| {
+                read_overflow(pointer).map_err(|e| Error::Overflow {
+                    source: Box::new(e),
+                })
+            }),
+        )
+    }
+
+    /// The pointers the row holds in place of values kept in overflow pages, found without
+    /// reading the pages.
+    pub fn overflow_pointers(&self, src: &[u8]) -> Result<Vec<OverflowPointer>> {
+        let mut pointers = vec![];
+        self.decode_row(
+            src,
+            Some(&mut |pointer| {
+                pointers.push(pointer);
+                Ok(vec![])
+            }),
+        )?;
+
+        Ok(pointers)
+    }
+
+    fn is_overflowed(&self, column: &Column, value: &Value) -> bool {
+        self.overflow
+            && column.type_id.is_variable_length()
+            && value.byte_count() > OVERFLOW_THRESHOLD
+    }
+
+    fn encode_row(
+        &self,
+        values: &[Value],
+        mut write_overflow: Option<WriteOverflow>,
+    ) -> Result<Vec<u8>> {
         if values.len() != self.columns.len() {
             return Err(ValuesCountSnafu.build());
         }
 
-        let data_byte_count = values.iter().map(|v| v.byte_count()).sum::<usize>();
+        let pointers = values
+            .iter()
+            .zip(self.columns.iter())
+            .map(|(v, col)| match (v, &mut write_overflow) {
+                (Value::String(s), Some(write)) if self.is_overflowed(col, v) => {
+                    write(s.as_bytes()).map(Some)
+                }
+                _ if self.is_overflowed(col, v) => OverflowUnavailableSnafu {
+                    column: col.name.clone(),
+                }
+                .fail(),
+                _ => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let data_byte_count = values
+            .iter()
+            .zip(pointers.iter())
+            .map(|(v, pointer)| match pointer {
+                Some(_) => OverflowPointer::SIZE,
+                None => v.byte_count(),
+            })
+            .sum::<usize>();
         let mut bytes =
             vec![0u8; self.var_lens_byte_count + self.bitmap_byte_count + data_byte_count];
 
@@ -83,33 +192,38 @@ impl Encoder for Codec {
         let mut bitmap = BitmapMut::new(bitmap);
         let mut data_writer = Cursor::new(data_region);
 
-        for (i, (v, col)) in values.iter().zip(self.columns.iter()).enumerate() {
+        for (i, ((v, col), pointer)) in values
+            .iter()
+            .zip(self.columns.iter())
+            .zip(pointers)
+            .enumerate()
+        {
             if col.type_id.is_variable_length() {
-                var_lens_writer
-                    .write_u16::<LE>(v.byte_count() as u16)
-                    .context(IoSnafu)?;
+                let len = match pointer {
+                    Some(_) => OVERFLOWED | OverflowPointer::SIZE as u16,
+                    None => v.byte_count() as u16,
+                };
+                var_lens_writer.write_u16::<LE>(len).context(IoSnafu)?;
             }
 
             if col.is_nullable && matches!(v, Value::Null) {
                 bitmap.set_unchecked(i);
             }
 
-            data_writer.write_value(v)?;
+            match pointer {
+                Some(pointer) => data_writer.write_pointer(&pointer)?,
+                None => data_writer.write_value(v)?,
+            }
         }
 
         Ok(bytes)
     }
 
-    fn max_size(&self) -> usize {
-        self.columns.iter().map(|col| col.type_len).sum::<u16>() as usize + self.data_region_start
-    }
-}
-
-impl Decoder for Codec {
-    type Item = Vec<Value>;
-    type Error = Error;
-
-    fn decode(&self, src: &[u8]) -> Result<(Vec<Value>, usize)> {
+    fn decode_row(
+        &self,
+        src: &[u8],
+        mut read_overflow: Option<ReadOverflow>,
+    ) -> Result<(Vec<Value>, usize)> {
         let mut var_lens = Cursor::new(&src[..self.var_lens_byte_count]);
         let null_bitmap = Bitmap::new(&src[self.var_lens_byte_count..self.data_region_start]);
         let mut reader = Cursor::new(&src[self.data_region_start..]);
@@ -122,18 +236,61 @@ impl Decoder for Codec {
                     return Ok(Value::Null);
                 }
 
-                if col.type_id.is_variable_length() {
-                    let len = var_lens.read_u16::<LE>().context(IoSnafu)? as usize;
-                    reader.read_string(len)
-                } else {
-                    reader.read_fixed_size_value(&col.type_id, col.type_len)
+                if !col.type_id.is_variable_length() {
+                    return reader.read_fixed_size_value(&col.type_id, col.type_len);
                 }
+
+                // the row holds a pointer in place of a value kept in overflow pages
+                let len = var_lens.read_u16::<LE>().context(IoSnafu)?;
+                if !self.overflow || len & OVERFLOWED == 0 {
+                    return reader.read_string(len as usize);
+                }
+
+                let pointer = reader.read_pointer()?;
+                let read = read_overflow.as_mut().context(OverflowUnavailableSnafu {
+                    column: col.name.clone(),
+                })?;
+
+                Ok(Value::String(
+                    String::from_utf8(read(pointer)?).context(Utf8EncodingSnafu)?,
+                ))
             })
             .collect::<Result<_>>()
             .map(|values| (values, self.data_region_start + reader.position() as usize))
     }
 }
 
+impl Encoder for Codec {
+    type Item = Vec<Value>;
+    type Error = Error;
+
+    fn encode(&self, values: &Vec<Value>) -> Result<Vec<u8>> {
+        self.encode_row(values, None)
+    }
+
+    fn max_size(&self) -> usize {
+        self.columns
+            .iter()
+            .map(
+                |col| match col.type_id.is_variable_length() && self.overflow {
+                    true => (col.type_len as usize).min(OVERFLOW_THRESHOLD),
+                    false => col.type_len as usize,
+                },
+            )
+            .sum::<usize>()
+            + self.data_region_start
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Vec<Value>;
+    type Error = Error;
+
+    fn decode(&self, src: &[u8]) -> Result<(Vec<Value>, usize)> {
+        self.decode_row(src, None)
+    }
+}
+
 fn bytes_repr_info(columns: &[Column]) -> (usize, usize) {
     let mut contain_nullable = false;
 
@@ -193,6 +350,13 @@ trait ReadValue: io::Read {
             String::from_utf8(buf).context(Utf8EncodingSnafu)?,
         ))
     }
+
+    fn read_pointer(&mut self) -> Result<OverflowPointer> {
+        Ok(OverflowPointer {
+            page_num: self.read_u32::<LE>().context(IoSnafu)?,
+            len: self.read_u32::<LE>().context(IoSnafu)?,
+        })
+    }
 }
 
 impl<T> ReadValue for Cursor<T> where T: AsRef<[u8]> {}
@@ -223,6 +387,11 @@ trait WriteValue: io::Write {
         }
         .context(IoSnafu)
     }
+
+    fn write_pointer(&mut self, pointer: &OverflowPointer) -> Result<()> {
+        self.write_u32::<LE>(pointer.page_num).context(IoSnafu)?;
+        self.write_u32::<LE>(pointer.len).context(IoSnafu)
+    }
 }
 
 impl WriteValue for Cursor<&mut [u8]> {}
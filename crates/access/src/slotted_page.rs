@@ -51,12 +51,12 @@ impl Slot {
 
     fn update_offset(&mut self, offset: u16) {
         self.0 &= 0xC0_00_7F_FF;
-        self.0 |= ((offset as u32) << 15) | 0xC0_00_7F_FF;
+        self.0 |= (offset as u32) << 15;
     }
 
     fn set_state(&mut self, state: SlotState) {
         self.0 &= 0x3F_FF_FF_FF;
-        self.0 |= ((state as u32) << 30) | 0x3F_FF_FF_FF;
+        self.0 |= (state as u32) << 30;
     }
 }
 
@@ -85,6 +85,46 @@ pub struct SlottedPage<'a> {
     body: &'a mut [u8],
 }
 
+/// A slotted page which is only read, as from a page locked in shared mode.
+#[derive(Debug, Clone, Copy)]
+pub struct SlottedPageView<'a> {
+    header: &'a Header,
+    body: &'a [u8],
+}
+
+impl<'a> SlottedPageView<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let (header, body) = bytes.split_at(size_of::<Header>());
+        Self {
+            header: from_bytes(header),
+            body,
+        }
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.header.slot_count as usize
+    }
+
+    pub fn slots(&self) -> &'a [Slot] {
+        cast_slice(&self.body[..self.slot_count() * size_of::<Slot>()])
+    }
+
+    pub fn get_slot(&self, index: usize) -> Option<&'a Slot> {
+        if index >= self.slot_count() {
+            return None;
+        }
+
+        const SLOT_SIZE: usize = size_of::<Slot>();
+        Some(from_bytes(
+            &self.body[index * SLOT_SIZE..(index + 1) * SLOT_SIZE],
+        ))
+    }
+
+    pub fn get_range(&self, range: Range<usize>) -> &'a [u8] {
+        &self.body[range]
+    }
+}
+
 impl<'a> SlottedPage<'a> {
     pub fn new(bytes: &'a mut [u8]) -> Self {
         let (header, body) = bytes.split_at_mut(size_of::<Header>());
@@ -94,40 +134,66 @@ impl<'a> SlottedPage<'a> {
         }
     }
 
+    pub fn view(&self) -> SlottedPageView<'_> {
+        SlottedPageView {
+            header: self.header,
+            body: self.body,
+        }
+    }
+
     pub fn init(&mut self) {
+        // offsets and lengths in slots are of 15 bits
+        debug_assert!(self.body.len() <= storage::MAX_PAGE_SIZE);
+
         *self.header = Header {
             slot_count: 0,
             total_free_space: self.body.len() as u16,
             fragment_list: 0,
-            free_area_end: self.body.len() as u16 - 1,
+            free_area_end: self.body.len() as u16,
         };
     }
 
     pub fn slot_count(&self) -> usize {
-        self.header.slot_count as usize
+        self.view().slot_count()
     }
 
     fn total_free_space(&self) -> u16 {
         self.header.total_free_space
     }
 
-    fn slots_size(&self) -> usize {
-        self.slot_count() * size_of::<Slot>()
+    /// The bytes free in the page, along with those left between the data by updates and
+    /// deletes.
+    pub fn free_space(&self) -> usize {
+        self.total_free_space() as usize
     }
 
-    pub fn slots(&self) -> &[Slot] {
-        cast_slice(&self.body[..self.slots_size()])
+    /// The bytes taken by the slots and their data.
+    pub fn used_space(&self) -> usize {
+        self.body.len() - self.free_space()
     }
 
-    pub fn get_slot(&self, index: usize) -> Option<&Slot> {
-        if index > self.slot_count() {
-            return None;
-        }
+    /// Whether data of the length fits in a new slot.
+    pub fn fits(&self, len: usize) -> bool {
+        size_of::<Slot>() + len <= self.free_space()
+    }
 
-        const SLOT_SIZE: usize = size_of::<Slot>();
-        Some(from_bytes(
-            &self.body[index * SLOT_SIZE..(index + 1) * SLOT_SIZE],
-        ))
+    /// Whether data of the length fits in a new slot, with no more than `percent` of the
+    /// page used after.
+    pub fn fits_within(&self, len: usize, percent: u8) -> bool {
+        self.used_space() + size_of::<Slot>() + len <= self.body.len() * percent as usize / 100
+    }
+
+    /// Whether no more than a quarter of the page is used.
+    pub fn is_underfull(&self) -> bool {
+        self.used_space() <= self.body.len() / 4
+    }
+
+    fn slots_size(&self) -> usize {
+        self.slot_count() * size_of::<Slot>()
+    }
+
+    pub fn slots(&self) -> &[Slot] {
+        self.view().slots()
     }
 
     fn slots_mut(&mut self) -> &mut [Slot] {
@@ -135,10 +201,6 @@ impl<'a> SlottedPage<'a> {
         cast_slice_mut(&mut self.body[..slots_size])
     }
 
-    pub fn get_range(&self, range: Range<usize>) -> &[u8] {
-        &self.body[range]
-    }
-
     fn insert_slot(&mut self, index: usize, slot: Slot) {
         let slot_count = self.slot_count();
         let slots_mut = cast_slice_mut(&mut self.body[..(slot_count + 1) * size_of::<Slot>()]);
@@ -156,8 +218,11 @@ impl<'a> SlottedPage<'a> {
         }
 
         if self.slots_size() as u16 + space_cost > self.header.free_area_end {
-            // TODO: find available fragment or defragment
-            return Err(Error::SpaceNotEnough);
+            self.defragment();
+            ensure!(
+                self.slots_size() as u16 + space_cost <= self.header.free_area_end,
+                SpaceNotEnoughSnafu
+            );
         }
 
         let offset = self.header.free_area_end - len;
@@ -191,8 +256,11 @@ impl<'a> SlottedPage<'a> {
             }
 
             if self.slots_size() as u16 + len > self.header.free_area_end {
-                // TODO: find available fragment or defragment
-                return Err(Error::SpaceNotEnough);
+                self.defragment();
+                ensure!(
+                    self.slots_size() as u16 + len <= self.header.free_area_end,
+                    SpaceNotEnoughSnafu
+                );
             }
 
             self.header.total_free_space -= len - origin_len;
@@ -200,8 +268,7 @@ impl<'a> SlottedPage<'a> {
             self.header.free_area_end
         };
 
-        *self.slots_mut().get_mut(index).unwrap() =
-            Slot::new(offset, len, SlotState::Normal);
+        *self.slots_mut().get_mut(index).unwrap() = Slot::new(offset, len, SlotState::Normal);
 
         data.iter().fold(offset as usize, |start, d| {
             let next = start + d.len();
@@ -251,6 +318,94 @@ impl<'a> SlottedPage<'a> {
         Ok(())
     }
 
+    /// Moves the first `count` slots of `other` to the end of this page.
+    pub fn append_from(&mut self, other: &mut SlottedPage, count: usize) -> Result<()> {
+        for _ in 0..count {
+            let index = self.slot_count();
+            self.insert(index, &[other.get(0)?])?;
+            other.delete(0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the last `count` slots of this page to the front of `other`.
+    pub fn prepend_to(&mut self, other: &mut SlottedPage, count: usize) -> Result<()> {
+        for _ in 0..count {
+            let index = self.slot_count() - 1;
+            other.insert(0, &[self.get(index)?])?;
+            self.delete(index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the last slots of this page to the front of `other`, or the first slots of
+    /// `other` to the end of this page, for as long as that brings the space used by both
+    /// closer. Each page keeps one slot at least.
+    pub fn balance(&mut self, other: &mut SlottedPage) -> Result<()> {
+        loop {
+            let (used, other_used) = (self.used_space(), other.used_space());
+
+            if used > other_used && self.slot_count() > 1 {
+                let slot = self.slots()[self.slot_count() - 1];
+                if size_of::<Slot>() + slot.len() >= used - other_used {
+                    return Ok(());
+                }
+                self.prepend_to(other, 1)?;
+            } else if other_used > used && other.slot_count() > 1 {
+                let slot = other.slots()[0];
+                if size_of::<Slot>() + slot.len() >= other_used - used {
+                    return Ok(());
+                }
+                self.append_from(other, 1)?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The count of the last slots holding about half of the space used, which are moved
+    /// to another page to split this one. Both pages keep one slot at least.
+    pub fn split_count(&self) -> usize {
+        debug_assert!(self.slot_count() > 1);
+
+        let half = self.used_space() / 2;
+        let mut moved = 0;
+        let count = self
+            .slots()
+            .iter()
+            .rev()
+            .take_while(|slot| {
+                moved += size_of::<Slot>() + slot.len();
+                moved <= half
+            })
+            .count();
+
+        count.clamp(1, self.slot_count() - 1)
+    }
+
+    /// Packs the data of the slots together at the end of the page, so that the space
+    /// left between them by updates and splits can be taken again.
+    fn defragment(&mut self) {
+        let data = self
+            .slots()
+            .iter()
+            .map(|slot| self.body[slot.range()].to_vec())
+            .collect::<Vec<_>>();
+
+        let mut offset = self.body.len();
+        for (index, data) in data.iter().enumerate() {
+            offset -= data.len();
+            self.body[offset..offset + data.len()].copy_from_slice(data);
+            self.slots_mut()[index].update_offset(offset as u16);
+        }
+
+        let data_size = data.iter().map(|d| d.len()).sum::<usize>();
+        self.header.free_area_end = offset as u16;
+        self.header.total_free_space = (self.body.len() - self.slots_size() - data_size) as u16;
+    }
+
     pub fn split_slots<'b>(&mut self, count: usize, other: &mut SlottedPage<'b>) {
         let mut space_free = 0;
 
@@ -305,4 +460,84 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn space_is_defragmented() -> Result<()> {
+        let mut bytes = vec![0; 50];
+
+        let mut page = SlottedPage::new(bytes.as_mut_slice());
+        page.init();
+
+        page.insert(0, &[&[1; 10]])?;
+        page.insert(1, &[&[2; 10]])?;
+
+        // the data grows out of its place, which is left between the others
+        page.update_slot(0, &[&[3; 12]])?;
+        page.insert(2, &[&[4; 6]])?;
+        assert!(matches!(
+            page.insert(3, &[&[5; 1]]),
+            Err(Error::SpaceNotEnough)
+        ));
+
+        assert_eq!(page.get(0)?, [3; 12]);
+        assert_eq!(page.get(1)?, [2; 10]);
+        assert_eq!(page.get(2)?, [4; 6]);
+
+        let mut other_bytes = vec![0; 50];
+        let mut other = SlottedPage::new(other_bytes.as_mut_slice());
+        other.init();
+
+        page.prepend_to(&mut other, 2)?;
+        assert_eq!((page.slot_count(), other.slot_count()), (1, 2));
+        assert_eq!(other.get(0)?, [2; 10]);
+
+        page.append_from(&mut other, 1)?;
+        assert_eq!((page.slot_count(), other.slot_count()), (2, 1));
+        assert_eq!(page.get(1)?, [2; 10]);
+        assert_eq!(other.get(0)?, [4; 6]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_and_balance_by_space() -> Result<()> {
+        let mut bytes = vec![0; 200];
+        let mut page = SlottedPage::new(bytes.as_mut_slice());
+        page.init();
+
+        // a few large slots after many small ones
+        for i in 0..10 {
+            page.insert(i, &[&[i as u8; 2]])?;
+        }
+        page.insert(10, &[&[10; 40]])?;
+        page.insert(11, &[&[11; 40]])?;
+        assert!(!page.fits(page.free_space()));
+        assert!(page.fits(page.free_space() - size_of::<Slot>()));
+
+        // half of the space is in the two large slots
+        assert_eq!(page.split_count(), 1);
+
+        let mut other_bytes = vec![0; 200];
+        let mut other = SlottedPage::new(other_bytes.as_mut_slice());
+        other.init();
+
+        // the large slots move over, until a move would leave the pages further apart
+        page.balance(&mut other)?;
+        assert_eq!((page.slot_count(), other.slot_count()), (10, 2));
+        assert_eq!((page.used_space(), other.used_space()), (60, 88));
+        assert_eq!(other.get(0)?, [10; 40]);
+
+        page.balance(&mut other)?;
+        assert_eq!((page.slot_count(), other.slot_count()), (10, 2));
+
+        // a single slot is kept
+        other.delete(0)?;
+        let mut empty_bytes = vec![0; 200];
+        let mut empty = SlottedPage::new(empty_bytes.as_mut_slice());
+        empty.init();
+        other.balance(&mut empty)?;
+        assert_eq!((other.slot_count(), empty.slot_count()), (1, 0));
+
+        Ok(())
+    }
 }
@@ -7,6 +7,10 @@ pub use crate::{common::*, stmt::*};
 
 #[derive(Debug, PartialEq)]
 pub enum Statement {
+    AlterSystem {
+        name: Identifier,
+        value: expr::Expression,
+    },
     CreateDatabase {
         if_not_exists: bool,
         name: Identifier,
@@ -19,12 +23,19 @@ pub enum Statement {
     },
     CreateTable(CreateTableStmt),
     CreateTableAs(CreateTableAsStmt),
+    CreateTablespace {
+        name: Identifier,
+        location: String,
+    },
     DropDatabase {
         name: Identifier,
     },
     DropTable {
         name: Identifier,
     },
+    DropTablespace {
+        name: Identifier,
+    },
     Insert(InsertStmt),
     Select(Query),
 }
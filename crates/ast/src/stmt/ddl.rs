@@ -41,6 +41,7 @@ pub_fields_struct! {
         if_not_exists: bool,
         name: Identifier,
         table_schema: TableSchema,
+        tablespace: Option<Identifier>,
     }
 
     // /// `RedefinedColumn` is used to represent columns defined in `CreateTableAs` statement.
@@ -58,6 +59,7 @@ pub_fields_struct! {
         name: Identifier,
         columns: Option<Vec<Column>>,
         constraints: Vec<Spanned<TableConstraint>>,
+        tablespace: Option<Identifier>,
         query: Query,
     }
 }
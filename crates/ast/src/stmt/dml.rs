@@ -47,6 +47,13 @@ pub_fields_struct! {
         targets: Vec<TargetElem>,
         from: Vec<TableReference>,
         cond: Option<Expression>,
+        order_by: Vec<OrderByItem>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct OrderByItem {
+        expr: Expression,
+        desc: bool,
     }
 
     #[derive(Debug, PartialEq)]
@@ -62,6 +62,7 @@ macro_rules! keyword {
 }
 
 keyword! {
+    ALTER,
     AND,
     AS,
     ASC,
@@ -96,6 +97,7 @@ keyword! {
     LEFT,
     LIKE,
     LIMIT,
+    LOCATION,
     NOT,
     NUMERIC,
     NULL,
@@ -107,8 +109,11 @@ keyword! {
     RIGHT,
     ROLLBACK,
     SELECT,
+    SET,
     SMALLINT,
+    SYSTEM,
     TABLE,
+    TABLESPACE,
     TEMP,
     TEMPORARY,
     TRUE,
@@ -2,9 +2,9 @@ use {
     access::{BTree, Codec},
     core::{default::Default, ops::Bound::Excluded},
     def::{
-        meta::{self, MetaTable, MetaTableId},
-        storage::{Decoder, Encoder},
-        DatabaseId, SchemaId, TableId, Value,
+        meta::{self, MetaTable, MetaTableId, SystemTable},
+        storage::Decoder,
+        DatabaseId, SchemaId, TableId, TableSpaceId, Value,
     },
     snafu::prelude::*,
     std::{
@@ -16,6 +16,12 @@ use {
 
 #[derive(Debug, Snafu)]
 pub enum Error {
+    #[snafu(display("error scanning metadata"))]
+    Access {
+        #[snafu(backtrace)]
+        source: access::btree::error::Error,
+    },
+
     #[snafu(display("error reading metadata"))]
     MetaData {
         #[snafu(backtrace)]
@@ -30,6 +36,7 @@ pub struct Binder {
     next_table_id: AtomicU32,
     tables: BTreeMap<(SchemaId, String), meta::Table>,
     columns: BTreeMap<(TableId, String), meta::Column>,
+    tablespaces: BTreeMap<String, meta::Tablespace>,
 }
 
 impl Binder {
@@ -39,6 +46,7 @@ impl Binder {
             next_table_id: Default::default(),
             tables: Default::default(),
             columns: Default::default(),
+            tablespaces: Default::default(),
         };
 
         binder.build_index(manager)?;
@@ -50,6 +58,14 @@ impl Binder {
         self.tables.get(&(schema_id, table)).map(|tbl| tbl.id)
     }
 
+    /// Looks up a table of the public schema, and then the system tables.
+    pub fn resolve_table(&self, table: String) -> Option<TableId> {
+        let system_table = SystemTable::from_name(&table).map(|table| table.id());
+
+        self.get_table_id(meta::SCHEMA_ID_PUBLIC, table)
+            .or(system_table)
+    }
+
     pub fn get_columns(&self, table_id: TableId) -> Vec<meta::Column> {
         self.columns
             .range((
@@ -65,6 +81,31 @@ impl Binder {
         self.columns.get(&(table_id, name)).cloned()
     }
 
+    /// The tablespace the files of the table are in.
+    pub fn get_space_id(&self, table_id: TableId) -> TableSpaceId {
+        self.tables
+            .values()
+            .find(|table| table.id == table_id)
+            .map_or(meta::TABLESPACE_ID_DEFAULT, |table| table.space_id)
+    }
+
+    pub fn get_tablespace(&self, name: &str) -> Option<&meta::Tablespace> {
+        self.tablespaces.get(name)
+    }
+
+    /// Whether any table has its files in the tablespace.
+    pub fn is_tablespace_used(&self, space_id: TableSpaceId) -> bool {
+        self.tables.values().any(|table| table.space_id == space_id)
+    }
+
+    pub fn get_next_tablespace_id(&self) -> TableSpaceId {
+        self.tablespaces
+            .values()
+            .map(|tablespace| tablespace.id + 1)
+            .max()
+            .unwrap_or_default()
+    }
+
     pub fn get_next_table_id(&self) -> TableId {
         self.next_table_id.fetch_add(1, Ordering::SeqCst)
     }
@@ -82,11 +123,20 @@ impl Binder {
             self.tables.insert((tbl.schema_id, tbl.name.clone()), tbl);
         });
 
-        let columns = self.load_columns(manager)?;
-        columns.into_iter().for_each(|col| {
-            self.columns.insert((col.table_id, col.name.clone()), col);
+        let tablespaces = self.load_tablespaces(manager)?;
+        tablespaces.into_iter().for_each(|tablespace| {
+            self.tablespaces
+                .insert(tablespace.name.clone(), tablespace);
         });
 
+        let columns = self.load_columns(manager)?;
+        columns
+            .into_iter()
+            .chain(SystemTable::ALL.iter().flat_map(SystemTable::columns))
+            .for_each(|col| {
+                self.columns.insert((col.table_id, col.name.clone()), col);
+            });
+
         Ok(())
     }
 
@@ -105,7 +155,7 @@ impl Binder {
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        let btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        let btree = BTree::new(key_codec, file_node, manager);
 
         let key = vec![Value::Uint(TableId::MIN)];
         let (cursor, _) = btree.cursor(&key).unwrap().unwrap();
@@ -113,14 +163,43 @@ impl Binder {
         cursor
             .into_iter()
             .map(|entry| {
-                let (value, _) = values_codec.decode(&entry.1).unwrap();
-                let values = [entry.0, value].concat();
+                let (key, value) = entry.context(AccessSnafu)?;
+                let (value, _) = values_codec.decode(&value).unwrap();
+                let values = [key, value].concat();
 
                 meta::Table::try_from(values).context(MetaDataSnafu)
             })
             .collect()
     }
 
+    fn load_tablespaces(&self, manager: &BufferManager) -> Result<Vec<meta::Tablespace>> {
+        let file_node = FileNode::global_meta(meta::Tablespace::TABLE_ID);
+
+        let (key_codec, values_codec) = {
+            let mut columns = meta::Tablespace::columns();
+            let v_columns = columns.split_off(1);
+            let k_columns = columns;
+
+            (Codec::new(k_columns), Codec::new(v_columns))
+        };
+
+        let btree = BTree::new(key_codec, file_node, manager);
+
+        let key = vec![Value::Uint(TableSpaceId::MIN)];
+        let (cursor, _) = btree.cursor(&key).unwrap().unwrap();
+
+        cursor
+            .into_iter()
+            .map(|entry| {
+                let (key, value) = entry.context(AccessSnafu)?;
+                let (value, _) = values_codec.decode(&value).unwrap();
+                let values = [key, value].concat();
+
+                meta::Tablespace::try_from(values).context(MetaDataSnafu)
+            })
+            .collect()
+    }
+
     fn load_columns(&self, manager: &BufferManager) -> Result<Vec<meta::Column>> {
         let file_node = FileNode::new(
             meta::TABLESPACE_ID_DEFAULT,
@@ -137,7 +216,7 @@ impl Binder {
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        let btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        let btree = BTree::new(key_codec, file_node, manager);
 
         let key = vec![Value::Uint(TableId::MIN), Value::SmallInt(i16::MIN)];
         let (cursor, _) = btree.cursor(&key).unwrap().unwrap();
@@ -145,8 +224,9 @@ impl Binder {
         cursor
             .into_iter()
             .map(|entry| {
-                let (value, _) = values_codec.decode(&entry.1).unwrap();
-                let values = [entry.0, value].concat();
+                let (key, value) = entry.context(AccessSnafu)?;
+                let (value, _) = values_codec.decode(&value).unwrap();
+                let values = [key, value].concat();
 
                 meta::Column::try_from(values).context(MetaDataSnafu)
             })
@@ -158,6 +238,15 @@ impl Binder {
             .insert((table.schema_id, table.name.clone()), table);
     }
 
+    pub fn update_tablespace(&mut self, tablespace: meta::Tablespace) {
+        self.tablespaces
+            .insert(tablespace.name.clone(), tablespace);
+    }
+
+    pub fn remove_tablespace(&mut self, name: &str) {
+        self.tablespaces.remove(name);
+    }
+
     pub fn update_columns(&mut self, columns: Vec<meta::Column>) {
         columns.into_iter().for_each(|col| {
             self.columns.insert((col.table_id, col.name.clone()), col);
@@ -5,7 +5,10 @@ pub use {expr::*, stmt::*};
 
 #[derive(Debug, PartialEq)]
 pub enum Statement {
+    AlterSystem(SystemSetting),
     CreateTable(CreateTableStmt),
+    CreateTablespace(CreateTablespaceStmt),
+    DropTablespace(DropTablespaceStmt),
     Insert(InsertStmt),
     Select(Query),
 }
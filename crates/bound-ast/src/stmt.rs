@@ -2,5 +2,6 @@ mod common;
 mod ddl;
 mod insert;
 mod select;
+mod system;
 
-pub use {self::common::*, ddl::*, insert::*, select::*};
+pub use {self::common::*, ddl::*, insert::*, select::*, system::*};
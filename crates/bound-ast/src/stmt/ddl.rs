@@ -1,7 +1,7 @@
 use {
     crate::ColumnNum,
     common::pub_fields_struct,
-    def::{DataType, SchemaId},
+    def::{DataType, SchemaId, TableSpaceId},
 };
 
 pub_fields_struct! {
@@ -22,5 +22,18 @@ pub_fields_struct! {
         columns: Vec<Column>,
         primary_key: Option<Vec<ColumnNum>>,
         unique_constraints: Vec<Vec<ColumnNum>>,
+        space_id: TableSpaceId,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct CreateTablespaceStmt {
+        name: String,
+        location: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct DropTablespaceStmt {
+        name: String,
+        space_id: TableSpaceId,
     }
 }
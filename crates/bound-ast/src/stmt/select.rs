@@ -1,4 +1,8 @@
-use {crate::ColumnNum, common::pub_fields_struct, def::TableId};
+use {
+    crate::ColumnNum,
+    common::pub_fields_struct,
+    def::{TableId, Value},
+};
 
 pub_fields_struct! {
     #[derive(Debug, PartialEq, Clone, Copy)]
@@ -7,9 +11,49 @@ pub_fields_struct! {
         column: ColumnNum,
     }
 
+    /// A comparison of a column with a value, the rows of which are kept.
+    #[derive(Debug, PartialEq, Clone)]
+    struct Predicate {
+        target: QueryTarget,
+        op: ComparisonOp,
+        value: Value,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct OrderBy {
+        target: QueryTarget,
+        desc: bool,
+    }
+
     #[derive(Debug, PartialEq)]
     struct Query {
         targets: Vec<QueryTarget>,
         tables: Vec<TableId>,
+        /// the conditions all of which the rows meet
+        predicates: Vec<Predicate>,
+        order_by: Vec<OrderBy>,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ComparisonOp {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl ComparisonOp {
+    /// The operator with its operands swapped, as in `1 < a` for `a > 1`.
+    pub fn flip(self) -> Self {
+        match self {
+            Self::LessThan => Self::GreaterThan,
+            Self::LessThanOrEqual => Self::GreaterThanOrEqual,
+            Self::GreaterThan => Self::LessThan,
+            Self::GreaterThanOrEqual => Self::LessThanOrEqual,
+            op => op,
+        }
     }
 }
@@ -0,0 +1,6 @@
+/// A setting changed by `ALTER SYSTEM`, with its new value.
+#[derive(Debug, PartialEq)]
+pub enum SystemSetting {
+    /// the number of buffers in the buffer pool
+    BufferPoolSize(usize),
+}
@@ -0,0 +1,108 @@
+use std::{
+    array,
+    ops::Index,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+const SEGMENT_COUNT: usize = usize::BITS as usize;
+
+/// A vector which only grows, and never moves its elements, so that they may be borrowed
+/// while it grows. Segment `k` holds the `2^k` elements from index `2^k - 1`.
+pub struct SegmentedVec<T> {
+    segments: [OnceLock<Box<[T]>>; SEGMENT_COUNT],
+    len: AtomicUsize,
+    grow_lock: Mutex<()>,
+}
+
+impl<T> SegmentedVec<T> {
+    pub fn new() -> Self {
+        Self {
+            segments: array::from_fn(|_| OnceLock::new()),
+            len: AtomicUsize::new(0),
+            grow_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the segment of the element, and its index in the segment.
+    fn locate(index: usize) -> (usize, usize) {
+        let segment = (index + 1).ilog2() as usize;
+        (segment, index + 1 - (1 << segment))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let (segment, offset) = Self::locate(index);
+        self.segments[segment].get().map(|segment| &segment[offset])
+    }
+
+    /// Grows to at least `len` elements, the new elements are made by `f` from their
+    /// indexes. Whole segments are added, so more elements than asked for may be made.
+    pub fn grow(&self, len: usize, mut f: impl FnMut(usize) -> T) {
+        let _grow = self.grow_lock.lock().unwrap();
+
+        while self.len() < len {
+            let start = self.len();
+            let (segment, _) = Self::locate(start);
+
+            let elements = (start..start + (1 << segment)).map(&mut f).collect();
+            if self.segments[segment].set(elements).is_err() {
+                unreachable!("segments are only added under the lock");
+            }
+
+            self.len.store(start + (1 << segment), Ordering::Release);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).map(|index| &self[index])
+    }
+}
+
+impl<T> Default for SegmentedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<usize> for SegmentedVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_stay_in_place() {
+        let vec = SegmentedVec::new();
+        assert!(vec.is_empty());
+
+        vec.grow(2, |i| i * 10);
+        assert_eq!(vec.len(), 3);
+        let first = &vec[0];
+
+        vec.grow(100, |i| i * 10);
+        assert_eq!(vec.len(), 127);
+        assert!(std::ptr::eq(first, &vec[0]));
+        assert!(vec.iter().enumerate().all(|(i, &n)| n == i * 10));
+        assert_eq!(vec.get(127), None);
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    fs::{self, File},
+    io::Result,
+    path::Path,
+};
+
+/// When writes are forced to stable storage, a weaker policy trades durability for speed.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SyncPolicy {
+    /// every write of a page is synced at once
+    Always,
+    /// the log is synced at commit, and the data files at checkpoints
+    #[default]
+    Commit,
+    /// nothing is synced, a crash of the machine may lose committed changes
+    Never,
+}
+
+impl SyncPolicy {
+    pub fn syncs_every_write(self) -> bool {
+        self == Self::Always
+    }
+
+    pub fn syncs(self) -> bool {
+        self != Self::Never
+    }
+}
+
+/// Makes the entries of the directory durable, a file created or renamed in it may be
+/// lost in a crash until its directory is synced.
+pub fn sync_dir(path: &Path) -> Result<()> {
+    File::open(path)?.sync_all()
+}
+
+/// Creates the directory and its missing parents, syncing the parent of every directory
+/// created.
+pub fn create_dirs_synced(path: &Path) -> Result<()> {
+    let missing = path
+        .ancestors()
+        .take_while(|dir| !dir.exists())
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(path)?;
+
+    for dir in missing.iter().rev() {
+        match dir.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => sync_dir(Path::new("."))?,
+            Some(parent) => sync_dir(parent)?,
+            None => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,2 +1,3 @@
 pub mod dsa;
+pub mod durability;
 pub mod macros;
@@ -2,6 +2,7 @@ mod column;
 mod database;
 pub mod error;
 mod schema;
+mod system;
 mod table;
 mod tablespace;
 
@@ -12,7 +13,7 @@ use {
     },
     snafu::prelude::*,
 };
-pub use {column::*, database::*, schema::*, table::*, tablespace::*};
+pub use {column::*, database::*, schema::*, system::*, table::*, tablespace::*};
 
 #[repr(u32)]
 pub enum MetaTableId {
@@ -22,6 +23,10 @@ pub enum MetaTableId {
     Table,
     Column,
 
+    // system tables, which are not stored
+    Buffers = 100,
+    BufferUsage,
+
     Reserved = 1000,
 }
 
@@ -81,6 +86,7 @@ macro_rules! meta_table_struct {
                         id: MetaTableId::$name as TableId,
                         name: stringify!($name).to_string().to_lowercase(),
                         schema_id: SCHEMA_ID_META,
+                        space_id: TABLESPACE_ID_DEFAULT,
                     }
                 }
 
@@ -112,7 +118,7 @@ macro_rules! meta_table_struct {
                 type Error = Error;
 
                 fn try_from(values: Vec<Value>) -> Result<Self> {
-                    let values: [Value; ${count(prop)}] = values
+                    let values: [Value; ${count($prop)}] = values
                         .try_into()
                         .map_err(|_| error::InternalSnafu.build())?;
 
@@ -160,11 +166,13 @@ meta_table_struct! {
         id: (TableId, DataType::Uint),
         name: (String, DataType::Varchar(50)),
         schema_id: (SchemaId, DataType::Uint),
+        space_id: (TableSpaceId, DataType::Uint),
     }
 
     #[derive(Debug, Clone, PartialEq)]
     struct Tablespace {
         id: (TableSpaceId, DataType::Uint),
         name: (String, DataType::Varchar(50)),
+        location: (String, DataType::Varchar(255)),
     }
 }
@@ -0,0 +1,92 @@
+use {
+    super::{Column, MetaTableId, Table, SCHEMA_ID_META, TABLESPACE_ID_DEFAULT},
+    crate::{DataType, TableId},
+};
+
+/// Tables which are not stored, their rows are made up from the state of the server
+/// every time they are read.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SystemTable {
+    /// a row for every frame of the buffer pool, with the page it holds
+    Buffers,
+    /// the counters of the buffer pool, a row for every file it has held pages of
+    BufferUsage,
+}
+
+impl SystemTable {
+    pub const ALL: [Self; 2] = [Self::Buffers, Self::BufferUsage];
+
+    pub fn id(&self) -> TableId {
+        match self {
+            Self::Buffers => MetaTableId::Buffers as TableId,
+            Self::BufferUsage => MetaTableId::BufferUsage as TableId,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Buffers => "db0_buffers",
+            Self::BufferUsage => "db0_buffer_usage",
+        }
+    }
+
+    pub fn from_id(id: TableId) -> Option<Self> {
+        Self::ALL.into_iter().find(|table| table.id() == id)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|table| table.name() == name)
+    }
+
+    pub fn table(&self) -> Table {
+        Table {
+            id: self.id(),
+            name: self.name().to_string(),
+            schema_id: SCHEMA_ID_META,
+            space_id: TABLESPACE_ID_DEFAULT,
+        }
+    }
+
+    /// The name, type and nullability of every column.
+    fn column_defs(&self) -> &'static [(&'static str, DataType, bool)] {
+        match self {
+            // the page of a free frame is null
+            Self::Buffers => &[
+                ("buffer_id", DataType::Uint, false),
+                ("space_id", DataType::Uint, true),
+                ("database_id", DataType::Uint, true),
+                ("table_id", DataType::Uint, true),
+                ("page_num", DataType::Uint, true),
+                ("is_dirty", DataType::Boolean, false),
+                ("pin_count", DataType::Uint, false),
+            ],
+            Self::BufferUsage => &[
+                ("space_id", DataType::Uint, false),
+                ("database_id", DataType::Uint, false),
+                ("table_id", DataType::Uint, false),
+                ("hits", DataType::BigUint, false),
+                ("misses", DataType::BigUint, false),
+                ("evictions", DataType::BigUint, false),
+                ("writes", DataType::BigUint, false),
+            ],
+        }
+    }
+
+    pub fn columns(&self) -> Vec<Column> {
+        self.column_defs()
+            .iter()
+            .enumerate()
+            .map(|(i, (name, data_type, is_nullable))| {
+                let (type_id, type_len) = data_type.value_repr();
+                Column::new(
+                    self.id(),
+                    i as i16 + 1,
+                    name.to_string(),
+                    type_id,
+                    type_len,
+                    *is_nullable,
+                )
+            })
+            .collect()
+    }
+}
@@ -3,6 +3,9 @@ use crate::TableSpaceId;
 pub const TABLESPACE_ID_DEFAULT: TableSpaceId = 0;
 pub const TABLESPACE_ID_GLOBAL: TableSpaceId = 1;
 
+pub const TABLESPACE_NAME_DEFAULT: &str = "db0_default";
+pub const TABLESPACE_NAME_GLOBAL: &str = "db0_global";
+
 // meta_table_struct! {
 //     struct Tablespace {
 //         id: (TableSpaceId, DataType::Uint),
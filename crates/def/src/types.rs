@@ -36,7 +36,7 @@ macro_rules! define_types {
             type Error = Error;
 
             fn try_into(self) -> Result<SqlType> {
-                if self > 0 && self <= ${count(var)} {
+                if self > 0 && self <= ${count($var)} {
                     Ok(unsafe { transmute::<u16, SqlType>(self) })
                 } else {
                     Err(Error::InvalidType { ty: self })
@@ -3,19 +3,44 @@ mod stmt;
 use {
     binder::Binder,
     bound_ast::Statement,
-    def::{DatabaseId, Value},
+    def::{DatabaseId, TableId, Value},
     snafu::prelude::*,
     std::sync::{Arc, RwLock},
-    storage::buffer::BufferManager,
+    storage::buffer::{BufferManager, FileNode},
+    wal::Xid,
 };
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    CreateTable { source: stmt::CreateTableError },
+    AlterSystem {
+        source: stmt::AlterSystemError,
+    },
 
-    Insert { source: stmt::InsertError },
+    CreateTable {
+        source: stmt::CreateTableError,
+    },
 
-    Query { source: stmt::QueryError },
+    Insert {
+        source: stmt::InsertError,
+    },
+
+    Query {
+        source: stmt::QueryError,
+    },
+
+    Tablespace {
+        source: stmt::TablespaceError,
+    },
+
+    Wal {
+        source: wal::Error,
+    },
+
+    #[snafu(display("{}, and its changes could not be undone: {}", source, undo))]
+    Unfinished {
+        source: Box<Error>,
+        undo: storage::buffer::Error,
+    },
 
     Unspported,
 }
@@ -36,11 +61,59 @@ impl Executor {
         manager: &BufferManager,
     ) -> Result<Vec<Vec<Value>>, Error> {
         match stmt {
-            Statement::CreateTable(stmt) => {
-                self.create_table(stmt, manager).context(CreateTableSnafu)
-            }
-            Statement::Insert(stmt) => self.insert(stmt, manager).context(InsertSnafu),
+            Statement::AlterSystem(setting) => self
+                .alter_system(setting, manager)
+                .context(AlterSystemSnafu),
+            // the files of tablespaces and the binder are changed around the statement
+            Statement::CreateTable(stmt) => self.create_table(stmt, manager),
+            Statement::CreateTablespace(stmt) => self.create_tablespace(stmt, manager),
+            Statement::DropTablespace(stmt) => self.drop_tablespace(stmt, manager),
+            Statement::Insert(stmt) => self.in_statement(manager, |xid| {
+                self.insert(stmt, xid, manager).context(InsertSnafu)
+            }),
             Statement::Select(stmt) => self.select(stmt, manager).context(QuerySnafu),
         }
     }
+
+    /// Runs a statement which modifies data, its changes are durable once this returns.
+    /// The changes of a statement which fails are undone before the next one starts.
+    fn in_statement<T>(
+        &self,
+        manager: &BufferManager,
+        f: impl FnOnce(Xid) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let _statement = manager.lock_statement();
+        self.in_locked_statement(manager, f)
+    }
+
+    /// Runs a statement as `in_statement` does, for callers which have taken the lock of
+    /// statements already, as it is taken before the binder.
+    fn in_locked_statement<T>(
+        &self,
+        manager: &BufferManager,
+        f: impl FnOnce(Xid) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let wal = manager.wal();
+
+        let xid = wal.begin().context(WalSnafu)?;
+        match f(xid) {
+            Ok(result) => {
+                wal.commit(xid).context(WalSnafu)?;
+                Ok(result)
+            }
+            Err(e) => match manager.abort(xid) {
+                Ok(()) => Err(e),
+                Err(undo) => Err(Error::Unfinished {
+                    source: Box::new(e),
+                    undo,
+                }),
+            },
+        }
+    }
+
+    /// The file of the table, in the tablespace it has been created in.
+    fn file_node(&self, table: TableId) -> FileNode {
+        let space_id = self.binder.read().unwrap().get_space_id(table);
+        FileNode::new(space_id, self.database, table)
+    }
 }
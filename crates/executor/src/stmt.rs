@@ -1,8 +1,11 @@
+mod alter_system;
 mod create_table;
 mod insert;
 mod query;
+mod tablespace;
 
 pub(super) use {
-    create_table::Error as CreateTableError, insert::Error as InsertError,
-    query::Error as QueryError,
+    alter_system::Error as AlterSystemError, create_table::Error as CreateTableError,
+    insert::Error as InsertError, query::Error as QueryError,
+    tablespace::Error as TablespaceError,
 };
@@ -0,0 +1,32 @@
+use {
+    crate::Executor,
+    bound_ast::SystemSetting,
+    def::Value,
+    snafu::{prelude::*, ResultExt},
+    storage::buffer::BufferManager,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    Storage {
+        #[snafu(backtrace)]
+        source: storage::buffer::Error,
+    },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl Executor {
+    /// Applies the setting to the running server, it doesn't outlive a restart.
+    pub(crate) fn alter_system(
+        &self,
+        setting: SystemSetting,
+        manager: &BufferManager,
+    ) -> Result<Vec<Vec<Value>>> {
+        match setting {
+            SystemSetting::BufferPoolSize(size) => manager.resize(size).context(StorageSnafu)?,
+        }
+
+        Ok(vec![])
+    }
+}
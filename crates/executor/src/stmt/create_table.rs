@@ -1,6 +1,7 @@
 use {
     crate::Executor,
     access::{btree::BTree, Codec},
+    binder::Binder,
     bound_ast::{Column, CreateTableStmt},
     def::{
         meta::{self, MetaTable},
@@ -9,10 +10,16 @@ use {
     },
     snafu::prelude::*,
     storage::buffer::{BufferManager, FileNode},
+    wal::Xid,
 };
 
 #[derive(Debug, Snafu)]
 pub enum Error {
+    Encoding {
+        #[snafu(backtrace)]
+        source: access::codec::Error,
+    },
+
     Access {
         #[snafu(backtrace)]
         source: access::btree::error::Error,
@@ -25,11 +32,36 @@ pub enum Error {
 type Result<T> = std::result::Result<T, Error>;
 
 impl Executor {
+    /// Creates a table, the binder only learns of it once the statement has committed.
     pub(crate) fn create_table(
         &self,
         stmt: CreateTableStmt,
         manager: &BufferManager,
-    ) -> Result<Vec<Vec<Value>>> {
+    ) -> std::result::Result<Vec<Vec<Value>>, crate::Error> {
+        let _statement = manager.lock_statement();
+        // held until the statement ends, so that no other table takes the name
+        let mut binder = self.binder.write().unwrap();
+
+        let (table, columns) = self.in_locked_statement(manager, |xid| {
+            self.insert_table(&binder, stmt, xid, manager)
+                .context(crate::CreateTableSnafu)
+        })?;
+
+        binder.update_table(table);
+        binder.update_columns(columns);
+
+        Ok(vec![vec![Value::Uint(1)]])
+    }
+
+    /// Records the table and its columns in the catalog and creates its file, returns the
+    /// records made.
+    fn insert_table(
+        &self,
+        binder: &Binder,
+        stmt: CreateTableStmt,
+        xid: Xid,
+        manager: &BufferManager,
+    ) -> Result<(meta::Table, Vec<meta::Column>)> {
         let CreateTableStmt {
             if_not_exists: _,
             schema,
@@ -37,54 +69,42 @@ impl Executor {
             columns,
             primary_key: _,
             unique_constraints: _,
+            space_id,
         } = stmt;
 
         // check if table with the same name exists in meta table `table`
-        if self.table_exists(schema, &name) {
+        if table_exists(binder, schema, &name) {
             return Err(TableExistsSnafu { name }.build());
         };
 
         // create a new record in `table` table
-        let table_id = {
-            let binder = self.binder.read().unwrap();
-            binder.get_next_table_id()
-        };
+        let table_id = binder.get_next_table_id();
         let table = meta::Table {
             id: table_id,
             name,
             schema_id: schema,
+            space_id,
         };
-        self.create_table_record(table.clone(), manager)?;
+        self.create_table_record(table.clone(), xid, manager)?;
 
         // create new records in `column` table
         let columns = transform_columns(columns, table_id);
-        self.create_column_records(columns.clone(), manager)?;
-
-        {
-            let mut binder = self.binder.try_write().unwrap();
-            binder.update_table(table);
-            binder.update_columns(columns);
-        }
+        self.create_column_records(columns.clone(), xid, manager)?;
 
         // create table file
-        // TODO: determine table space by schema and database
-        let space_id = meta::TABLESPACE_ID_DEFAULT;
         let file_node = FileNode::new(space_id, self.database, table_id);
-        BTree::<Codec>::init(file_node, manager).context(AccessSnafu)?;
-
-        Ok(vec![vec![Value::Uint(1)]])
-    }
+        BTree::<Codec>::init(xid, file_node, manager).context(AccessSnafu)?;
 
-    fn table_exists(&self, schema: SchemaId, table: &str) -> bool {
-        self.binder
-            .read()
-            .unwrap()
-            .get_table_id(schema, table.to_string())
-            .is_some()
+        Ok((table, columns))
     }
 
     // TODO: just a reference of `table` should be enough
-    fn create_table_record(&self, table: meta::Table, manager: &BufferManager) -> Result<()> {
+    fn create_table_record(
+        &self,
+        table: meta::Table,
+        xid: Xid,
+        manager: &BufferManager,
+    ) -> Result<()> {
         let file_node = FileNode::new(
             meta::TABLESPACE_ID_DEFAULT,
             self.database,
@@ -99,19 +119,20 @@ impl Executor {
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        let mut btree = BTree::new(key_codec, file_node, manager);
 
         let mut kv: Vec<Value> = table.into();
         let values = kv.split_off(1);
         let key = kv;
 
-        let values = values_codec.encode(&values).unwrap();
-        btree.insert(&key, &values).context(AccessSnafu)
+        let values = values_codec.encode(&values).context(EncodingSnafu)?;
+        btree.insert(xid, &key, &values).context(AccessSnafu)
     }
 
     fn create_column_records(
         &self,
         columns: Vec<meta::Column>,
+        xid: Xid,
         manager: &BufferManager,
     ) -> Result<()> {
         let file_node = FileNode::new(
@@ -128,21 +149,23 @@ impl Executor {
             (Codec::new(k_columns), Codec::new(v_columns))
         };
 
-        let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
+        let mut btree = BTree::new(key_codec, file_node, manager);
 
-        columns.into_iter().for_each(|column| {
+        columns.into_iter().try_for_each(|column| {
             let mut kv: Vec<Value> = column.into();
             let values = kv.split_off(2);
             let key = kv;
 
-            let values = values_codec.encode(&values).unwrap();
-            btree.insert(&key, &values).unwrap();
-        });
-
-        Ok(())
+            let values = values_codec.encode(&values).context(EncodingSnafu)?;
+            btree.insert(xid, &key, &values).context(AccessSnafu)
+        })
     }
 }
 
+fn table_exists(binder: &Binder, schema: SchemaId, table: &str) -> bool {
+    binder.get_table_id(schema, table.to_string()).is_some()
+}
+
 fn transform_columns(columns: Vec<Column>, table_id: TableId) -> Vec<meta::Column> {
     columns
         .into_iter()
@@ -172,7 +195,7 @@ fn transform_columns(columns: Vec<Column>, table_id: TableId) -> Vec<meta::Colum
 
 //         let temp_dir = tempdir().unwrap();
 //         let path = temp_dir.path();
-//         let mut manager = BufferManager::new(100, DEFAULT_PAGE_SIZE, path.to_path_buf());
+//         let mut manager = BufferManager::new(100, path.to_path_buf());
 
 //         let columns = vec![Column {
 //             name: "abc".to_string(),
@@ -2,13 +2,11 @@ use {
     crate::Executor,
     access::{btree::BTree, Codec},
     bound_ast::InsertStmt,
-    def::{
-        meta::{self},
-        storage::Encoder,
-        Value,
-    },
+    def::Value,
     snafu::{prelude::*, ResultExt},
-    storage::buffer::{BufferManager, FileNode},
+    std::collections::BTreeSet,
+    storage::buffer::BufferManager,
+    wal::Xid,
 };
 
 #[derive(Debug, Snafu)]
@@ -22,6 +20,9 @@ pub enum Error {
         #[snafu(backtrace)]
         source: access::btree::error::Error,
     },
+
+    #[snafu(display("duplicate key {:?} violates the primary key", key))]
+    DuplicateKey { key: Vec<Value> },
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -30,6 +31,7 @@ impl Executor {
     pub(crate) fn insert(
         &self,
         stmt: InsertStmt,
+        xid: Xid,
         manager: &BufferManager,
     ) -> Result<Vec<Vec<Value>>> {
         let InsertStmt {
@@ -38,7 +40,7 @@ impl Executor {
             source,
         } = stmt;
 
-        let file_node = FileNode::new(meta::TABLESPACE_ID_DEFAULT, self.database, table);
+        let file_node = self.file_node(table);
 
         let columns_count;
         let (key_codec, values_codec) = {
@@ -50,29 +52,42 @@ impl Executor {
             let v_columns = columns.split_off(1);
             let k_columns = columns;
 
-            (Codec::new(k_columns), Codec::new(v_columns))
+            (Codec::new(k_columns), Codec::new(v_columns).with_overflow())
         };
 
-        let mut btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
-
-        let mut new_rows_count = 0;
+        let mut btree = BTree::new(key_codec, file_node, manager);
 
         let mut values = vec![Value::Null; columns_count];
-        for row in source {
-            row.into_iter().zip(targets.iter()).for_each(|(v, &i)| {
-                values[i as usize - 1] = v;
-            });
+        let rows = source
+            .into_iter()
+            .map(|row| {
+                row.into_iter().zip(targets.iter()).for_each(|(v, &i)| {
+                    values[i as usize - 1] = v;
+                });
 
-            // TODO: no need to clone `values`, should update the `Encoder` trait
-            let mut tmp_values = values.clone();
-            // FIXME: remove hard code
-            let values = tmp_values.split_off(1);
-            let key = tmp_values;
+                // TODO: no need to clone `values`, should update the `Encoder` trait
+                let mut key = values.clone();
+                // FIXME: remove hard code
+                let values = key.split_off(1);
+                (key, values)
+            })
+            .collect::<Vec<_>>();
 
-            let values = values_codec.encode(&values).context(EncodingSnafu)?;
+        // a key taken fails the statement before any row is written
+        let mut keys = BTreeSet::new();
+        for (key, _) in &rows {
+            if !keys.insert(key) || btree.get(key).context(AccessSnafu)?.is_some() {
+                return Err(DuplicateKeySnafu { key: key.clone() }.build());
+            }
+        }
+
+        let mut new_rows_count = 0;
+        for (key, values) in rows {
+            let values = values_codec
+                .encode_with(&values, |value| btree.write_overflow(xid, value))
+                .context(EncodingSnafu)?;
 
-            // TODO: should distinguish whether this is an insert or an update
-            btree.insert(&key, &values).context(AccessSnafu)?;
+            btree.insert(xid, &key, &values).context(AccessSnafu)?;
             new_rows_count += 1;
         }
 
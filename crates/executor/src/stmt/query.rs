@@ -1,15 +1,11 @@
 use {
     crate::Executor,
     access::{btree::BTree, Codec},
-    bound_ast::Query,
-    def::{
-        meta,
-        storage::{Decoder, Encoder},
-        Value,
-    },
+    bound_ast::{ColumnNum, ComparisonOp, OrderBy, Predicate, Query},
+    def::{meta::SystemTable, TableId, Value},
     snafu::{prelude::*, ResultExt},
-    std::collections::HashMap,
-    storage::buffer::{BufferManager, FileNode},
+    std::{cmp::Ordering, collections::HashMap, ops::Bound},
+    storage::buffer::{BufferManager, BufferRing},
 };
 
 #[derive(Debug, Snafu)]
@@ -18,48 +14,52 @@ pub enum Error {
         #[snafu(backtrace)]
         source: access::btree::error::Error,
     },
+
+    Decoding {
+        #[snafu(backtrace)]
+        source: access::codec::Error,
+    },
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// The buffers a scan of a table reads its pages into, so that it doesn't push the
+/// catalog and the pages of other tables out of the pool.
+const SCAN_RING_SIZE: usize = 16;
+
 impl Executor {
     // leave alone planning for now
     pub(crate) fn select(&self, stmt: Query, manager: &BufferManager) -> Result<Vec<Vec<Value>>> {
-        let Query { targets, tables } = stmt;
+        let Query {
+            targets,
+            tables,
+            predicates,
+            order_by,
+        } = stmt;
 
         let row_values = tables
             .iter()
             .map(|&table| {
-                let file_node = FileNode::new(meta::TABLESPACE_ID_DEFAULT, self.database, table);
-
-                let (key_codec, values_codec) = {
-                    let binder = self.binder.read().unwrap();
-                    // TODO: there should be some information about primary keys in metadata
-                    let mut columns = binder.get_columns(table);
-                    let v_columns = columns.split_off(1);
-                    let k_columns = columns;
+                let predicates = predicates
+                    .iter()
+                    .filter(|p| p.target.table == table)
+                    .collect::<Vec<_>>();
+                let order_by = order_by
+                    .iter()
+                    .filter(|o| o.target.table == table)
+                    .collect::<Vec<_>>();
 
-                    (Codec::new(k_columns), Codec::new(v_columns))
+                let (mut rows, is_ordered) = match SystemTable::from_id(table) {
+                    Some(system_table) => (system_table_rows(system_table, manager), false),
+                    None => self.scan(table, &predicates, &order_by, manager)?,
                 };
 
-                let btree = BTree::new(key_codec, values_codec.max_size(), file_node, manager);
-
-                // FIXME: generate search key by key columns
-                let (cursor, _) = btree
-                    .cursor(&vec![Value::Null])
-                    .context(AccessSnafu)?
-                    .unwrap();
-
-                Ok((
-                    table,
-                    cursor
-                        .into_iter()
-                        .map(|entry| {
-                            let (value, _) = values_codec.decode(&entry.1).unwrap();
-                            [entry.0, value].concat()
-                        })
-                        .collect::<Vec<_>>(),
-                ))
+                rows.retain(|row| predicates.iter().all(|p| satisfies(row, p)));
+                if !is_ordered {
+                    rows.sort_by(|a, b| compare_rows(a, b, &order_by));
+                }
+
+                Ok((table, rows))
             })
             .collect::<Result<HashMap<_, _>>>()?;
 
@@ -83,4 +83,192 @@ impl Executor {
 
         Ok(values)
     }
+
+    /// Reads the rows of the table, only over the keys the predicates on the key column
+    /// leave. The rows come in the order of the key, backward if they are ordered by the
+    /// key descending, along with whether that is the order asked for.
+    fn scan(
+        &self,
+        table: TableId,
+        predicates: &[&Predicate],
+        order_by: &[&OrderBy],
+        manager: &BufferManager,
+    ) -> Result<(Vec<Vec<Value>>, bool)> {
+        let file_node = self.file_node(table);
+
+        let (key_column, key_codec, values_codec) = {
+            let binder = self.binder.read().unwrap();
+            // TODO: there should be some information about primary keys in metadata
+            let mut columns = binder.get_columns(table);
+            let v_columns = columns.split_off(1);
+            let k_columns = columns;
+
+            (
+                k_columns[0].num,
+                Codec::new(k_columns),
+                Codec::new(v_columns).with_overflow(),
+            )
+        };
+
+        let btree = BTree::new(key_codec, file_node, manager);
+
+        let range = key_range(predicates.iter().filter(|p| p.target.column == key_column));
+        let cursor = btree
+            .range(range)
+            .context(AccessSnafu)?
+            .with_ring(BufferRing::new(SCAN_RING_SIZE));
+
+        let (is_ordered, is_backward) = match order_by {
+            [] => (true, false),
+            [OrderBy { target, desc }] if target.column == key_column => (true, *desc),
+            _ => (false, false),
+        };
+        let entries = match is_backward {
+            true => cursor.rev().collect::<std::result::Result<Vec<_>, _>>(),
+            false => cursor.collect(),
+        }
+        .context(AccessSnafu)?;
+
+        let rows = entries
+            .into_iter()
+            .map(|(key, values)| {
+                let (values, _) = values_codec
+                    .decode_with(&values, |pointer| btree.read_overflow(pointer))
+                    .context(DecodingSnafu)?;
+                Ok([key, values].concat())
+            })
+            .collect::<Result<_>>()?;
+
+        Ok((rows, is_ordered))
+    }
+}
+
+/// The bounds of the keys meeting all the predicates on the key column.
+fn key_range<'a>(
+    predicates: impl Iterator<Item = &'a &'a Predicate>,
+) -> (Bound<Vec<Value>>, Bound<Vec<Value>>) {
+    let (mut lower, mut upper) = (Bound::Unbounded, Bound::Unbounded);
+
+    for Predicate { op, value, .. } in predicates {
+        let key = vec![value.clone()];
+        match op {
+            ComparisonOp::Equal => {
+                lower = narrower(lower, Bound::Included(key.clone()), Ordering::Greater);
+                upper = narrower(upper, Bound::Included(key), Ordering::Less);
+            }
+            ComparisonOp::GreaterThan => {
+                lower = narrower(lower, Bound::Excluded(key), Ordering::Greater)
+            }
+            ComparisonOp::GreaterThanOrEqual => {
+                lower = narrower(lower, Bound::Included(key), Ordering::Greater)
+            }
+            ComparisonOp::LessThan => upper = narrower(upper, Bound::Excluded(key), Ordering::Less),
+            ComparisonOp::LessThanOrEqual => {
+                upper = narrower(upper, Bound::Included(key), Ordering::Less)
+            }
+            ComparisonOp::NotEqual => {}
+        }
+    }
+
+    (lower, upper)
+}
+
+/// The bound leaving fewer keys, the one whose key compares to the other's as `inward`
+/// does, or the exclusive one of two bounds on the same key.
+fn narrower(a: Bound<Vec<Value>>, b: Bound<Vec<Value>>, inward: Ordering) -> Bound<Vec<Value>> {
+    let key = |bound: &Bound<Vec<Value>>| match bound {
+        Bound::Included(key) | Bound::Excluded(key) => Some(key.clone()),
+        Bound::Unbounded => None,
+    };
+
+    match (key(&a), key(&b)) {
+        (None, _) => b,
+        (_, None) => a,
+        (Some(ka), Some(kb)) => match ka.cmp(&kb) {
+            Ordering::Equal if matches!(b, Bound::Excluded(_)) => b,
+            Ordering::Equal => a,
+            ordering if ordering == inward => a,
+            _ => b,
+        },
+    }
+}
+
+/// Whether the value of the row in the column of the predicate compares to the value of
+/// the predicate as it requires. A null value meets no predicate.
+fn satisfies(row: &[Value], predicate: &Predicate) -> bool {
+    let value = &row[column_index(predicate.target.column)];
+    if *value == Value::Null || predicate.value == Value::Null {
+        return false;
+    }
+
+    let ordering = value.cmp(&predicate.value);
+    match predicate.op {
+        ComparisonOp::Equal => ordering.is_eq(),
+        ComparisonOp::NotEqual => ordering.is_ne(),
+        ComparisonOp::LessThan => ordering.is_lt(),
+        ComparisonOp::LessThanOrEqual => ordering.is_le(),
+        ComparisonOp::GreaterThan => ordering.is_gt(),
+        ComparisonOp::GreaterThanOrEqual => ordering.is_ge(),
+    }
+}
+
+fn compare_rows(a: &[Value], b: &[Value], order_by: &[&OrderBy]) -> Ordering {
+    order_by
+        .iter()
+        .map(|o| {
+            let i = column_index(o.target.column);
+            match o.desc {
+                true => b[i].cmp(&a[i]),
+                false => a[i].cmp(&b[i]),
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn column_index(column: ColumnNum) -> usize {
+    column as usize - 1
+}
+
+/// Makes up the rows of a system table from the state of the buffer pool.
+fn system_table_rows(table: SystemTable, manager: &BufferManager) -> Vec<Vec<Value>> {
+    match table {
+        SystemTable::Buffers => manager
+            .frames()
+            .into_iter()
+            .map(|frame| {
+                let page = match frame.page_tag {
+                    Some(tag) => [
+                        Value::Uint(tag.file_node.space_id),
+                        Value::Uint(tag.file_node.database_id),
+                        Value::Uint(tag.file_node.table_id),
+                        Value::Uint(tag.page_num),
+                    ],
+                    None => [Value::Null, Value::Null, Value::Null, Value::Null],
+                };
+
+                [
+                    vec![Value::Uint(frame.buffer_id as u32)],
+                    page.to_vec(),
+                    vec![Value::Boolean(frame.is_dirty), Value::Uint(frame.pin_count)],
+                ]
+                .concat()
+            })
+            .collect(),
+        SystemTable::BufferUsage => manager
+            .file_stats()
+            .into_iter()
+            .map(|(file_node, stats)| {
+                vec![
+                    Value::Uint(file_node.space_id),
+                    Value::Uint(file_node.database_id),
+                    Value::Uint(file_node.table_id),
+                    Value::BigUint(stats.hits),
+                    Value::BigUint(stats.misses),
+                    Value::BigUint(stats.evictions),
+                    Value::BigUint(stats.writes),
+                ]
+            })
+            .collect(),
+    }
 }
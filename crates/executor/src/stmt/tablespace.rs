@@ -0,0 +1,167 @@
+use {
+    crate::Executor,
+    access::{btree::BTree, Codec},
+    binder::Binder,
+    bound_ast::{CreateTablespaceStmt, DropTablespaceStmt},
+    def::{
+        meta::{self, MetaTable},
+        storage::Encoder,
+        TableSpaceId, Value,
+    },
+    snafu::prelude::*,
+    std::path::Path,
+    storage::buffer::{BufferManager, FileNode},
+    wal::Xid,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    Access {
+        #[snafu(backtrace)]
+        source: access::btree::error::Error,
+    },
+
+    Storage {
+        #[snafu(backtrace)]
+        source: storage::buffer::Error,
+    },
+
+    #[snafu(display("tablespace '{}' already exists", name))]
+    TablespaceExists { name: String },
+
+    #[snafu(display("tablespace '{}' is not empty", name))]
+    TablespaceInUse { name: String },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl Executor {
+    /// Creates a tablespace, whose files are kept at its location rather than in the
+    /// data directory.
+    pub(crate) fn create_tablespace(
+        &self,
+        stmt: CreateTablespaceStmt,
+        manager: &BufferManager,
+    ) -> std::result::Result<Vec<Vec<Value>>, crate::Error> {
+        let _statement = manager.lock_statement();
+        // held until the statement ends, so that no other tablespace takes the id
+        let mut binder = self.binder.write().unwrap();
+
+        // the location is linked first, the record is left out if that fails
+        let tablespace = link_tablespace(&binder, stmt, manager).context(crate::TablespaceSnafu)?;
+
+        let result = self.in_locked_statement(manager, |xid| {
+            insert_tablespace(&tablespace, xid, manager).context(crate::TablespaceSnafu)
+        });
+        if let Err(e) = result {
+            // a link left by a crash is replaced by the next tablespace with the id
+            let _ = manager.drop_tablespace(tablespace.id);
+            return Err(e);
+        }
+
+        binder.update_tablespace(tablespace);
+
+        Ok(vec![vec![Value::Uint(1)]])
+    }
+
+    /// Drops a tablespace which no table is in.
+    pub(crate) fn drop_tablespace(
+        &self,
+        stmt: DropTablespaceStmt,
+        manager: &BufferManager,
+    ) -> std::result::Result<Vec<Vec<Value>>, crate::Error> {
+        let DropTablespaceStmt { name, space_id } = stmt;
+
+        let _statement = manager.lock_statement();
+        // held until the statement ends, so that no table is created in the tablespace
+        let mut binder = self.binder.write().unwrap();
+
+        self.in_locked_statement(manager, |xid| {
+            delete_tablespace(&binder, &name, space_id, xid, manager)
+                .context(crate::TablespaceSnafu)
+        })?;
+
+        binder.remove_tablespace(&name);
+        drop(binder);
+
+        // the link is only removed once the record is gone for good, one left by a crash
+        // is replaced by the next tablespace with the id
+        manager
+            .drop_tablespace(space_id)
+            .context(StorageSnafu)
+            .context(crate::TablespaceSnafu)?;
+
+        Ok(vec![vec![Value::Uint(1)]])
+    }
+}
+
+/// Links the location of a new tablespace from the data directory.
+fn link_tablespace(
+    binder: &Binder,
+    stmt: CreateTablespaceStmt,
+    manager: &BufferManager,
+) -> Result<meta::Tablespace> {
+    let CreateTablespaceStmt { name, location } = stmt;
+
+    if binder.get_tablespace(&name).is_some() {
+        return Err(TablespaceExistsSnafu { name }.build());
+    }
+    let space_id = binder.get_next_tablespace_id();
+
+    manager
+        .create_tablespace(space_id, Path::new(&location))
+        .context(StorageSnafu)?;
+
+    Ok(meta::Tablespace::new(space_id, name, location))
+}
+
+/// Deletes the record of a tablespace from the catalog, if no table is in it.
+fn delete_tablespace(
+    binder: &Binder,
+    name: &str,
+    space_id: TableSpaceId,
+    xid: Xid,
+    manager: &BufferManager,
+) -> Result<()> {
+    if binder.is_tablespace_used(space_id) {
+        return Err(TablespaceInUseSnafu { name }.build());
+    }
+
+    let (mut btree, _) = tablespace_tree(manager);
+    btree
+        .delete(xid, &vec![Value::Uint(space_id)])
+        .context(AccessSnafu)?;
+
+    Ok(())
+}
+
+/// Inserts the record of the tablespace into the catalog.
+fn insert_tablespace(
+    tablespace: &meta::Tablespace,
+    xid: Xid,
+    manager: &BufferManager,
+) -> Result<()> {
+    let mut kv: Vec<Value> = tablespace.clone().into();
+    let values = kv.split_off(1);
+    let key = kv;
+
+    let (mut btree, values_codec) = tablespace_tree(manager);
+    let values = values_codec.encode(&values).unwrap();
+    btree.insert(xid, &key, &values).context(AccessSnafu)?;
+
+    Ok(())
+}
+
+/// The catalog table of tablespaces, which is shared by all databases.
+fn tablespace_tree(manager: &BufferManager) -> (BTree<'_, Codec>, Codec) {
+    let file_node = FileNode::global_meta(meta::Tablespace::TABLE_ID);
+
+    let mut columns = meta::Tablespace::columns();
+    let v_columns = columns.split_off(1);
+    let k_columns = columns;
+    let (key_codec, values_codec) = (Codec::new(k_columns), Codec::new(v_columns));
+
+    let btree = BTree::new(key_codec, file_node, manager);
+
+    (btree, values_codec)
+}
@@ -4,6 +4,7 @@ mod error;
 mod expr;
 mod insert;
 mod query;
+mod system;
 
 pub use self::error::{Error, Result};
 use {
@@ -42,6 +43,7 @@ impl<'a> Iterator for Parser<'a> {
         self.skip_semicolons();
 
         Some(match self.tokens.next()? {
+            Ok(Spanned(Token::Keyword(Keyword::ALTER), _)) => self.parse_alter(),
             Ok(Spanned(Token::Keyword(Keyword::CREATE), _)) => self.parse_create(),
             Ok(Spanned(Token::Keyword(Keyword::DROP), _)) => self.parse_drop(),
             Ok(Spanned(Token::Keyword(Keyword::INSERT), _)) => self.parse_insert(),
@@ -83,6 +85,10 @@ mod tests {
 
             CREATE INDEX hi on abc (a, b);
             CREATE unique INDEX hello on abc (a);
+
+            CREATE TABLESPACE fast LOCATION '/mnt/ssd';
+            CREATE TABLE hot (a int) TABLESPACE fast;
+            DROP TABLESPACE fast;
         ";
 
         let expected_output = vec![
@@ -116,6 +122,7 @@ mod tests {
                         110..=124,
                     )],
                 },
+                tablespace: None,
             }),
             Statement::DropDatabase {
                 name: identifier_from_str("abc"),
@@ -135,6 +142,26 @@ mod tests {
                 table: identifier_from_str("abc"),
                 columns: vec![identifier_from_str("a")],
             },
+            Statement::CreateTablespace {
+                name: identifier_from_str("fast"),
+                location: "/mnt/ssd".to_string(),
+            },
+            Statement::CreateTable(CreateTableStmt {
+                if_not_exists: false,
+                name: identifier_from_str("hot"),
+                table_schema: TableSchema {
+                    columns: vec![Column {
+                        name: identifier_from_str("a"),
+                        data_type: DataType::Int,
+                        constraints: vec![],
+                    }],
+                    constraints: vec![],
+                },
+                tablespace: Some(identifier_from_str("fast")),
+            }),
+            Statement::DropTablespace {
+                name: identifier_from_str("fast"),
+            },
         ];
 
         let output = Parser::parse(sql).unwrap();
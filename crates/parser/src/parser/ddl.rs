@@ -19,6 +19,7 @@ impl<'a> Parser<'a> {
         match_token!(self.tokens.next(), {
             Spanned(Token::Keyword(Keyword::DATABASE), _) => self.parse_create_database(),
             Spanned(Token::Keyword(Keyword::TABLE), _) => self.parse_create_table(),
+            Spanned(Token::Keyword(Keyword::TABLESPACE), _) => self.parse_create_tablespace(),
             Spanned(Token::Keyword(Keyword::INDEX), _) => self.parse_create_index(false),
             Spanned(Token::Keyword(Keyword::UNIQUE), _) => {
                 self.must_match(Token::Keyword(Keyword::INDEX))?;
@@ -33,6 +34,8 @@ impl<'a> Parser<'a> {
                 Ok(Statement::DropDatabase { name: self.parse_identifier()? }),
             Spanned(Token::Keyword(Keyword::TABLE), _) =>
                 Ok(Statement::DropTable { name: self.parse_identifier()? }),
+            Spanned(Token::Keyword(Keyword::TABLESPACE), _) =>
+                Ok(Statement::DropTablespace { name: self.parse_identifier()? }),
         })
     }
 
@@ -59,6 +62,11 @@ impl<'a> Parser<'a> {
             _ => (vec![], vec![]),
         };
 
+        let tablespace = match self.try_match(Token::Keyword(Keyword::TABLESPACE)) {
+            Some(_) => Some(self.parse_identifier()?),
+            None => None,
+        };
+
         if self.try_match(Token::Keyword(Keyword::AS)).is_some() {
             let query = self.parse_select()?;
 
@@ -73,6 +81,7 @@ impl<'a> Parser<'a> {
                 name,
                 columns,
                 constraints,
+                tablespace,
                 query,
             }))
         } else {
@@ -85,10 +94,22 @@ impl<'a> Parser<'a> {
                 if_not_exists,
                 name,
                 table_schema,
+                tablespace,
             }))
         }
     }
 
+    /// Parses `CREATE TABLESPACE name LOCATION 'directory'`.
+    fn parse_create_tablespace(&mut self) -> Result<Statement> {
+        let name = self.parse_identifier()?;
+
+        self.must_match(Token::Keyword(Keyword::LOCATION))?;
+        let Spanned(_, span) = self.must_match(Token::String)?;
+        let location = self.string_from_span(span);
+
+        Ok(Statement::CreateTablespace { name, location })
+    }
+
     fn parse_table_schema(&mut self) -> Result<(Vec<Column>, Vec<Spanned<TableConstraint>>)> {
         self.must_match(Token::LeftParen)?;
 
@@ -107,6 +107,7 @@ mod tests {
                         joins: vec![],
                     }],
                     cond: None,
+                    order_by: vec![],
                 })),
             }),
         ];
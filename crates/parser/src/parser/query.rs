@@ -7,7 +7,7 @@ use {
     ast::{
         expr::*,
         token::{Keyword, Token},
-        JoinItem, Query, Spanned, TableFactor, TableReference, TargetElem,
+        JoinItem, OrderByItem, Query, Spanned, TableFactor, TableReference, TargetElem,
     },
     def::JoinType,
 };
@@ -18,12 +18,14 @@ impl<'a> Parser<'a> {
         let targets = self.parse_select_targets()?;
         let from = self.parse_from_clause()?;
         let cond = self.parse_where_clause()?;
+        let order_by = self.parse_order_by_clause()?;
 
         Ok(Query {
             distinct,
             targets,
             from,
             cond,
+            order_by,
         })
     }
 
@@ -133,6 +135,28 @@ impl<'a> Parser<'a> {
             None => None,
         })
     }
+
+    fn parse_order_by_clause(&mut self) -> Result<Vec<OrderByItem>> {
+        if self.try_match(Token::Keyword(Keyword::ORDER)).is_none() {
+            return Ok(Vec::new());
+        };
+        self.must_match(Token::Keyword(Keyword::BY))?;
+
+        self.parse_comma_separated(Self::parse_order_by_item)
+    }
+
+    fn parse_order_by_item(&mut self) -> Result<OrderByItem> {
+        let expr = self.parse_expr()?;
+        let desc = match self.try_match(Token::Keyword(Keyword::DESC)) {
+            Some(_) => true,
+            None => {
+                self.try_match(Token::Keyword(Keyword::ASC));
+                false
+            }
+        };
+
+        Ok(OrderByItem { expr, desc })
+    }
 }
 
 #[cfg(test)]
@@ -193,10 +217,58 @@ mod tests {
                 })),
                 Box::new(Expression::Literal(Literal::Int(0))),
             ))),
+            order_by: vec![],
         })];
 
         let output = Parser::parse(input).unwrap();
 
         assert_eq!(output, expected_output);
     }
+
+    #[test]
+    fn order_by() {
+        let input = "SELECT a FROM abc ORDER BY a DESC, b, c ASC;";
+        let column = |name| {
+            Expression::Column(ColumnRef {
+                name: identifier_from_str(name),
+                table: None,
+            })
+        };
+
+        let output = Parser::parse(input).unwrap();
+
+        let Statement::Select(Query { order_by, .. }) = &output[0] else {
+            panic!("not a query");
+        };
+        assert_eq!(
+            order_by,
+            &vec![
+                OrderByItem {
+                    expr: column("a"),
+                    desc: true,
+                },
+                OrderByItem {
+                    expr: column("b"),
+                    desc: false,
+                },
+                OrderByItem {
+                    expr: column("c"),
+                    desc: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn order_by_after_where() {
+        let output = Parser::parse("SELECT a FROM abc WHERE a > 1 ORDER BY a;").unwrap();
+
+        let Statement::Select(Query { cond, order_by, .. }) = &output[0] else {
+            panic!("not a query");
+        };
+        assert!(cond.is_some());
+        assert_eq!(order_by.len(), 1);
+
+        assert!(Parser::parse("SELECT a FROM abc ORDER a;").is_err());
+    }
 }
@@ -0,0 +1,41 @@
+use {
+    super::{error::Result, Parser},
+    ast::{
+        token::{Keyword, Token},
+        Statement,
+    },
+};
+
+impl<'a> Parser<'a> {
+    /// Parses `ALTER SYSTEM SET name = value`, which changes a setting of the running
+    /// server.
+    pub(super) fn parse_alter(&mut self) -> Result<Statement> {
+        self.must_match(Token::Keyword(Keyword::SYSTEM))?;
+        self.must_match(Token::Keyword(Keyword::SET))?;
+
+        let name = self.parse_identifier()?;
+        self.must_match(Token::Equal)?;
+        let value = self.parse_expr()?;
+
+        Ok(Statement::AlterSystem { name, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ast::{expr::Literal, identifier_from_str},
+    };
+
+    #[test]
+    fn parse_alter_system() {
+        let sql = "ALTER SYSTEM SET buffer_pool_size = 256";
+        let expected_output = Statement::AlterSystem {
+            name: identifier_from_str("buffer_pool_size"),
+            value: Literal::Int(256).into(),
+        };
+
+        assert_eq!(Parser::parse(sql).unwrap(), vec![expected_output]);
+    }
+}
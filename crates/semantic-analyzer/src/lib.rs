@@ -9,12 +9,16 @@ use {
 
 #[derive(Debug, Snafu)]
 pub enum Error {
+    AlterSystem { source: stmt::AlterSystemError },
+
     CreateTable { source: stmt::CreateTableError },
 
     Insert { source: stmt::InsertError },
 
     Select { source: stmt::SelectError },
 
+    Tablespace { source: stmt::TablespaceError },
+
     Unspported,
 }
 
@@ -29,9 +33,18 @@ impl Analyzer {
 
     pub fn analyze(&self, stmt: ast::Statement) -> Result<Statement, Error> {
         Ok(match stmt {
+            ast::Statement::AlterSystem { name, value } => self
+                .analyze_alter_system(name, value)
+                .context(AlterSystemSnafu)?,
             ast::Statement::CreateTable(stmt) => {
                 self.analyze_create_table(stmt).context(CreateTableSnafu)?
             }
+            ast::Statement::CreateTablespace { name, location } => self
+                .analyze_create_tablespace(name, location)
+                .context(TablespaceSnafu)?,
+            ast::Statement::DropTablespace { name } => self
+                .analyze_drop_tablespace(name)
+                .context(TablespaceSnafu)?,
             ast::Statement::Insert(stmt) => self.analyze_insert(stmt).context(InsertSnafu)?,
             ast::Statement::Select(stmt) => self.analyze_select(stmt).context(SelectSnafu)?,
             _ => return Err(UnspportedSnafu.build()),
@@ -1,8 +1,11 @@
+mod alter_system;
 mod create_table;
 mod insert;
 mod select;
+mod tablespace;
 
 pub(super) use {
-    create_table::Error as CreateTableError, insert::Error as InsertError,
-    select::Error as SelectError,
+    alter_system::Error as AlterSystemError, create_table::Error as CreateTableError,
+    insert::Error as InsertError, select::Error as SelectError,
+    tablespace::Error as TablespaceError,
 };
@@ -0,0 +1,40 @@
+use {
+    crate::Analyzer,
+    ast::{
+        expr::{Expression, Literal},
+        Identifier, Spanned,
+    },
+    bound_ast::{Statement, SystemSetting},
+    snafu::prelude::*,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(r#"unrecognized configuration parameter "{}""#, name))]
+    UnknownSetting { name: Spanned<String> },
+
+    #[snafu(display(r#"invalid value for parameter "{}""#, name))]
+    InvalidValue { name: Spanned<String> },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl Analyzer {
+    pub(crate) fn analyze_alter_system(
+        &self,
+        name: Identifier,
+        value: Expression,
+    ) -> Result<Statement> {
+        let setting = match name.0.to_ascii_lowercase().as_str() {
+            "buffer_pool_size" => match value {
+                Expression::Literal(Literal::Int(size)) if size > 0 => {
+                    SystemSetting::BufferPoolSize(size as usize)
+                }
+                _ => return InvalidValueSnafu { name }.fail(),
+            },
+            _ => return UnknownSettingSnafu { name }.fail(),
+        };
+
+        Ok(Statement::AlterSystem(setting))
+    }
+}
@@ -24,6 +24,15 @@ pub enum Error {
         span: Span,
         name: String,
     },
+
+    Tablespace {
+        source: super::TablespaceError,
+    },
+
+    #[snafu(display("only catalog tables can be placed in tablespace {}", name))]
+    GlobalTablespace {
+        name: Identifier,
+    },
 }
 
 impl Analyzer {
@@ -35,8 +44,23 @@ impl Analyzer {
             if_not_exists,
             name,
             table_schema,
+            tablespace,
         } = stmt;
 
+        let space_id = match tablespace {
+            Some(tablespace) => {
+                let space_id = self
+                    .resolve_tablespace(&tablespace)
+                    .context(TablespaceSnafu)?;
+                ensure!(
+                    space_id != meta::TABLESPACE_ID_GLOBAL,
+                    GlobalTablespaceSnafu { name: tablespace }
+                );
+                space_id
+            }
+            None => meta::TABLESPACE_ID_DEFAULT,
+        };
+
         let mut column_nums = HashMap::new();
 
         let mut primary_key = None;
@@ -115,6 +139,7 @@ impl Analyzer {
             columns,
             primary_key,
             unique_constraints,
+            space_id,
         }))
     }
 }
@@ -170,7 +170,7 @@ impl Analyzer {
     }
 }
 
-fn cast_value(literal: Literal, target: &meta::Column) -> Result<Value> {
+pub(super) fn cast_value(literal: Literal, target: &meta::Column) -> Result<Value> {
     let sql_type = target.type_id.clone();
 
     Ok(match (literal, &sql_type) {
@@ -1,9 +1,13 @@
 use {
+    super::insert::{self, cast_value},
     crate::Analyzer,
-    ast::{expr::Expression, ColumnRef, Spanned, TableFactor, TargetElem},
-    bound_ast::{Query, QueryTarget, Statement},
+    ast::{
+        expr::{Expression, Operation},
+        ColumnRef, OrderByItem, Spanned, TableFactor, TargetElem,
+    },
+    bound_ast::{ComparisonOp, OrderBy, Predicate, Query, QueryTarget, Statement},
     core::cmp::Ordering,
-    def::{meta, TableId},
+    def::TableId,
     snafu::prelude::*,
     std::collections::HashMap,
 };
@@ -30,6 +34,11 @@ pub enum Error {
         column_ref: Spanned<String>,
     },
 
+    #[snafu(display("{}", source))]
+    InvalidValue {
+        source: insert::Error,
+    },
+
     Unsupported,
 }
 
@@ -41,7 +50,8 @@ impl Analyzer {
             distinct: _,
             targets,
             from,
-            cond: _,
+            cond,
+            order_by,
         } = query;
 
         let mut tables = HashMap::new();
@@ -56,7 +66,7 @@ impl Analyzer {
                 .binder
                 .read()
                 .unwrap()
-                .get_table_id(meta::SCHEMA_ID_PUBLIC, table.0.clone())
+                .resolve_table(table.0.clone())
                 .ok_or(
                     TableNotExistsSnafu {
                         name: table.clone(),
@@ -83,9 +93,84 @@ impl Analyzer {
             })
             .collect::<Result<Vec<_>>>()?;
 
+        let mut predicates = vec![];
+        if let Some(cond) = cond {
+            self.bind_predicates(cond, &tables, &mut predicates)?;
+        }
+
+        let order_by = order_by
+            .into_iter()
+            .map(|OrderByItem { expr, desc }| match expr {
+                Expression::Column(column) => Ok(OrderBy {
+                    target: self.bind_column_ref(column, &tables)?,
+                    desc,
+                }),
+                _ => Err(UnsupportedSnafu.build()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         let tables = tables.into_values().collect();
 
-        Ok(Statement::Select(Query { targets, tables }))
+        Ok(Statement::Select(Query {
+            targets,
+            tables,
+            predicates,
+            order_by,
+        }))
+    }
+
+    /// Binds a condition made of comparisons between columns and literals joined by
+    /// `AND`, one predicate for each comparison.
+    fn bind_predicates(
+        &self,
+        cond: Expression,
+        tables: &HashMap<String, TableId>,
+        predicates: &mut Vec<Predicate>,
+    ) -> Result<()> {
+        let (op, lhs, rhs) = match cond {
+            Expression::Operation(Operation::And(lhs, rhs)) => {
+                self.bind_predicates(*lhs, tables, predicates)?;
+                return self.bind_predicates(*rhs, tables, predicates);
+            }
+            Expression::Operation(operation) => match operation {
+                Operation::Equal(lhs, rhs) => (ComparisonOp::Equal, lhs, rhs),
+                Operation::NotEqual(lhs, rhs) => (ComparisonOp::NotEqual, lhs, rhs),
+                Operation::LessThan(lhs, rhs) => (ComparisonOp::LessThan, lhs, rhs),
+                Operation::LessThanOrEqual(lhs, rhs) => (ComparisonOp::LessThanOrEqual, lhs, rhs),
+                Operation::GreaterThan(lhs, rhs) => (ComparisonOp::GreaterThan, lhs, rhs),
+                Operation::GreaterThanOrEqual(lhs, rhs) => {
+                    (ComparisonOp::GreaterThanOrEqual, lhs, rhs)
+                }
+                _ => return Err(UnsupportedSnafu.build()),
+            },
+            _ => return Err(UnsupportedSnafu.build()),
+        };
+
+        let (column, literal, op) = match (*lhs, *rhs) {
+            (Expression::Column(column), Expression::Literal(literal)) => (column, literal, op),
+            (Expression::Literal(literal), Expression::Column(column)) => {
+                (column, literal, op.flip())
+            }
+            _ => return Err(UnsupportedSnafu.build()),
+        };
+
+        let target = self.bind_column_ref(column, tables)?;
+        let column = self
+            .binder
+            .read()
+            .unwrap()
+            .get_columns(target.table)
+            .into_iter()
+            .find(|column| column.num == target.column)
+            .context(UnsupportedSnafu)?;
+
+        predicates.push(Predicate {
+            target,
+            op,
+            value: cast_value(literal, &column).context(InvalidValueSnafu)?,
+        });
+
+        Ok(())
     }
 
     fn bind_column_ref(
@@ -0,0 +1,62 @@
+use {
+    crate::Analyzer,
+    ast::Identifier,
+    bound_ast::{CreateTablespaceStmt, DropTablespaceStmt, Statement},
+    def::meta,
+    snafu::prelude::*,
+    std::path::Path,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("tablespace location '{}' must be an absolute path", path))]
+    RelativeLocation { path: String },
+
+    #[snafu(display("tablespace {} does not exist", name))]
+    UndefinedTablespace { name: Identifier },
+
+    #[snafu(display("tablespace {} is built in and cannot be dropped", name))]
+    BuiltinTablespace { name: Identifier },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl Analyzer {
+    pub(crate) fn analyze_create_tablespace(
+        &self,
+        name: Identifier,
+        location: String,
+    ) -> Result<Statement> {
+        ensure!(
+            Path::new(&location).is_absolute(),
+            RelativeLocationSnafu { path: location }
+        );
+
+        Ok(Statement::CreateTablespace(CreateTablespaceStmt {
+            name: name.to_string(),
+            location,
+        }))
+    }
+
+    pub(crate) fn analyze_drop_tablespace(&self, name: Identifier) -> Result<Statement> {
+        let space_id = self.resolve_tablespace(&name)?;
+        ensure!(
+            ![meta::TABLESPACE_ID_DEFAULT, meta::TABLESPACE_ID_GLOBAL].contains(&space_id),
+            BuiltinTablespaceSnafu { name }
+        );
+
+        Ok(Statement::DropTablespace(DropTablespaceStmt {
+            name: name.to_string(),
+            space_id,
+        }))
+    }
+
+    pub(crate) fn resolve_tablespace(&self, name: &Identifier) -> Result<def::TableSpaceId> {
+        self.binder
+            .read()
+            .unwrap()
+            .get_tablespace(&name.0)
+            .map(|tablespace| tablespace.id)
+            .context(UndefinedTablespaceSnafu { name: name.clone() })
+    }
+}
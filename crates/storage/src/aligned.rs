@@ -0,0 +1,71 @@
+use {
+    crate::IO_ALIGNMENT,
+    std::{
+        alloc::{self, Layout},
+        ops::{Deref, DerefMut},
+        ptr::NonNull,
+        slice,
+    },
+};
+
+/// A zeroed buffer on the heap which starts at a multiple of `IO_ALIGNMENT`, as direct
+/// I/O requires.
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+// SAFETY: the buffer owns its memory, as a `Box<[u8]>` does
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    pub fn new(len: usize) -> Self {
+        let layout = Layout::from_size_align(len.max(1), IO_ALIGNMENT).unwrap();
+        // SAFETY: the size of the layout is not zero
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+
+        Self { ptr, len, layout }
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut buffer = Self::new(bytes.len());
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+
+    /// The start of the buffer, which may be written through while the buffer is shared
+    /// as long as no slice of it is borrowed.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub fn is_aligned(bytes: &[u8]) -> bool {
+        bytes.as_ptr().align_offset(IO_ALIGNMENT) == 0
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the buffer holds `len` initialized bytes
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the buffer holds `len` initialized bytes, and is borrowed exclusively
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: the memory was allocated with the same layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
@@ -1,11 +1,25 @@
 mod error;
 mod manager;
+mod read_ahead;
 mod replacer;
+mod ring;
+mod stats;
+mod tag_table;
+mod writer;
 
-pub(self) use self::replacer::Replacer;
 pub use self::{
     error::{Error, Result},
-    manager::{BufferManager, BufferRef},
+    manager::{BufferManager, BufferReadGuard, BufferRef, BufferWriteGuard, WAL_DIR},
+    replacer::ReplacementPolicy,
+    ring::BufferRing,
+    stats::{BufferStats, FrameInfo},
+    writer::{BackgroundWriter, BackgroundWriterConfig, Checkpointer, CheckpointerConfig},
+};
+pub(self) use self::{
+    read_ahead::ReadAhead,
+    replacer::Replacer,
+    stats::{Event, Stats},
+    tag_table::TagTable,
 };
 use {
     crate::PageNum,
@@ -15,8 +29,12 @@ use {
         DatabaseId, TableId, TableSpaceId,
     },
     std::path::PathBuf,
+    wal::PageId,
 };
 
+/// The directory of the links to the tablespaces created by the user.
+pub const TABLESPACE_DIR: &str = "tablespace";
+
 pub_fields_struct! {
     #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
     struct FileNode {
@@ -53,10 +71,35 @@ impl FileNode {
         match self.space_id {
             TABLESPACE_ID_GLOBAL => PathBuf::from("global"),
             TABLESPACE_ID_DEFAULT => PathBuf::from(format!("base/{}", self.table_id)),
-            _ => PathBuf::from(format!("tablespace/{}", self.space_id)),
+            space_id => Self::tablespace_dir(space_id).join(self.database_id.to_string()),
         }
         .join(self.table_id.to_string())
     }
+
+    /// The directory of a tablespace created by the user, which links to its location.
+    pub fn tablespace_dir(space_id: TableSpaceId) -> PathBuf {
+        PathBuf::from(TABLESPACE_DIR).join(space_id.to_string())
+    }
+}
+
+impl From<&PageTag> for PageId {
+    fn from(tag: &PageTag) -> Self {
+        Self {
+            space_id: tag.file_node.space_id,
+            database_id: tag.file_node.database_id,
+            table_id: tag.file_node.table_id,
+            page_num: tag.page_num,
+        }
+    }
+}
+
+impl From<&PageId> for PageTag {
+    fn from(page: &PageId) -> Self {
+        Self {
+            file_node: FileNode::new(page.space_id, page.database_id, page.table_id),
+            page_num: page.page_num,
+        }
+    }
 }
 
 pub(crate) type BufferId = usize;
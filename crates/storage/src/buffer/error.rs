@@ -1,5 +1,6 @@
 use {
     super::PageTag,
+    crate::{control, temp},
     snafu::prelude::*,
     std::{backtrace::Backtrace, io},
 };
@@ -12,6 +13,27 @@ pub enum Error {
         source: io::Error,
     },
 
+    Wal {
+        #[snafu(backtrace)]
+        source: wal::Error,
+    },
+
+    Control {
+        #[snafu(backtrace)]
+        source: control::Error,
+    },
+
+    Temp {
+        #[snafu(backtrace)]
+        source: temp::Error,
+    },
+
+    #[snafu(display("checksum mismatch in page {:?}", page_tag))]
+    ChecksumMismatch {
+        backtrace: Backtrace,
+        page_tag: PageTag,
+    },
+
     #[snafu(display("page is not in buffer"))]
     PageNotInBuffer {
         backtrace: Backtrace,
@@ -20,6 +42,15 @@ pub enum Error {
 
     #[snafu(display("buffer pool has no more buffer to offer"))]
     NoMoreBuffer { backtrace: Option<Backtrace> },
+
+    #[snafu(display("buffer pool can't have {} buffers", capacity))]
+    InvalidCapacity {
+        backtrace: Backtrace,
+        capacity: usize,
+    },
+
+    #[snafu(display("{} buffers to remove from the pool are still in use", count))]
+    BuffersInUse { backtrace: Backtrace, count: usize },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,323 +1,1502 @@
+mod recovery;
+
 use {
-    super::{error, FileNode, PageTag, Replacer, Result},
-    crate::{manager::StorageManager, PageNum, DEFAULT_PAGE_SIZE},
-    core::ptr::NonNull,
-    snafu::ResultExt,
+    super::{
+        error, BufferRing, BufferStats, Event, FileNode, FrameInfo, PageTag, ReadAhead,
+        ReplacementPolicy, Replacer, Result, Stats, TagTable,
+    },
+    crate::{
+        aligned::AlignedBuffer,
+        control::ControlFile,
+        store::{FileOptions, FileStore, MemoryStore, PageStore},
+        temp::TempFileManager,
+        PageNum, PAGE_HEADER_SIZE,
+    },
+    common::dsa::segmented_vec::SegmentedVec,
+    def::TableSpaceId,
+    snafu::{ensure, OptionExt, ResultExt},
     std::{
-        cell::RefCell,
-        collections::HashMap,
-        marker::PhantomData,
-        path::PathBuf,
+        cell::UnsafeCell,
+        fmt,
+        ops::{Deref, DerefMut, Range},
+        path::{Path, PathBuf},
         slice,
         sync::{
-            atomic::{AtomicI32, AtomicU32, Ordering},
-            Arc, RwLock,
+            atomic::{AtomicU32, AtomicUsize, Ordering},
+            Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
         },
+        thread,
+        time::{Duration, Instant},
     },
+    wal::{Delta, Lsn, PageOp, PageRecord, Record, Wal, Xid},
 };
 
+/// The directory of the log under the data directory.
+pub const WAL_DIR: &str = "wal";
+
 pub(super) type BufferId = usize;
 
+/// How long shrinking the pool waits for the buffers removed to be unpinned.
+const SHRINK_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub struct BufferManager {
-    storage_manager: StorageManager,
+    store: Arc<dyn PageStore>,
+    read_ahead: ReadAhead,
+    wal: Wal,
+    /// `None` for databases kept in memory
+    temp_files: Option<TempFileManager>,
+
+    tag_table: TagTable,
+    /// every descriptor ever made, including those of buffers removed by shrinking the pool
+    descriptors: SegmentedVec<BufferDescriptor>,
+    /// the buffers in the pool are the first `capacity` descriptors
+    capacity: AtomicUsize,
+    /// the buffers which hold no page, the next one to use is at the end
+    free_buffers: Mutex<Vec<BufferId>>,
+    /// serializes the resizing of the pool
+    resize_lock: Mutex<()>,
+
+    /// the size of pages, as chosen for the data directory
+    page_size: usize,
+    /// whether pages are checksummed, as chosen for the data directory
+    checksums: bool,
+
+    replacer: Mutex<Box<dyn Replacer>>,
+    /// serializes the extension of files, so that concurrent sessions never allocate
+    /// the same page
+    extension_lock: Mutex<()>,
+    /// held by a statement from its start until it commits or is undone, so that undoing
+    /// the bytes it changed never undoes those of another statement
+    statement_lock: Mutex<()>,
 
-    tag_table: RefCell<HashMap<PageTag, usize>>,
-    descriptors: Vec<Arc<RefCell<BufferDescriptor>>>,
-    /// index of first free descriptor, -1 if there is no free descriptor
-    first_free: AtomicI32,
+    /// the buffer the background writer looks at next
+    writer_hand: AtomicUsize,
 
-    buffers: Vec<u8>,
-    buffer_size: usize,
+    stats: Stats,
+}
 
-    replacer: RefCell<Replacer>,
-    // last_page_ids: HashMap<FileNode, PageId>,
+// SAFETY: the content of a buffer is only accessed while holding its content lock, and
+// the buffer is never reused while it is pinned. Frames are only replaced under the
+// content lock in exclusive mode.
+unsafe impl Sync for BufferManager {}
+
+/// The buffer returned by `reuse_page`.
+enum Frame<'a> {
+    /// the page has been loaded by another session meanwhile
+    Cached(BufferRef<'a>),
+    /// the buffer is reserved for the page, its content stays locked until the page is loaded
+    Reserved(BufferRef<'a>, BufferWriteGuard<'a>),
 }
 
 impl BufferManager {
-    pub fn new(capacity: usize, buffer_size: usize, data_dir: PathBuf) -> Self {
-        let descriptors = (0..capacity)
-            .into_iter()
-            .map(|i| {
-                let mut free_next = i as i32 + 1;
-                if free_next == capacity as i32 {
-                    free_next = -1;
-                }
+    pub fn new(capacity: usize, data_dir: PathBuf) -> Result<Self> {
+        Self::with_options(capacity, data_dir, FileOptions::default())
+    }
 
-                Arc::new(RefCell::new(BufferDescriptor {
-                    page_tag: None,
-                    buffer_id: i,
-                    next_free: free_next,
-                    state: AtomicU32::new(0),
-                    content_lock: RwLock::new(()),
-                }))
-            })
-            .collect();
+    /// Builds the buffer pool over the data directory, whose files are accessed and synced
+    /// as `options` say.
+    pub fn with_options(capacity: usize, data_dir: PathBuf, options: FileOptions) -> Result<Self> {
+        let control = ControlFile::open(&data_dir).context(error::ControlSnafu)?;
+        let wal = Wal::open(&data_dir.join(WAL_DIR))
+            .context(error::WalSnafu)?
+            .with_sync_policy(options.sync_policy);
+        let temp_files =
+            TempFileManager::open(&data_dir, control.page_size).context(error::TempSnafu)?;
+        let store = FileStore::new(data_dir, control.page_size)
+            .with_io_mode(options.io_mode)
+            .with_sync_policy(options.sync_policy);
+
+        let mut manager = Self::with_store(capacity, Box::new(store), wal, &control);
+        manager.temp_files = Some(temp_files);
+
+        Ok(manager)
+    }
 
-        let replacer = Replacer::new(capacity);
+    /// Builds the buffer pool of a database kept in memory, which needs no data directory.
+    pub fn in_memory(capacity: usize, control: &ControlFile) -> Self {
+        Self::with_store(
+            capacity,
+            Box::new(MemoryStore::new()),
+            Wal::in_memory(),
+            control,
+        )
+    }
 
-        Self {
-            storage_manager: StorageManager::new(data_dir),
+    /// Builds the buffer pool over the pages kept in `store`, and the changes of which are
+    /// logged to `wal`.
+    pub fn with_store(
+        capacity: usize,
+        store: Box<dyn PageStore>,
+        wal: Wal,
+        control: &ControlFile,
+    ) -> Self {
+        let store: Arc<dyn PageStore> = Arc::from(store);
+
+        let manager = Self {
+            read_ahead: ReadAhead::new(store.clone(), control.page_size),
+            store,
+            wal,
+            temp_files: None,
+
+            tag_table: TagTable::new(capacity),
+            descriptors: SegmentedVec::new(),
+            capacity: AtomicUsize::new(0),
+            free_buffers: Mutex::new(vec![]),
+            resize_lock: Mutex::new(()),
+            page_size: control.page_size,
+            checksums: control.checksums,
+
+            replacer: Mutex::new(ReplacementPolicy::default().replacer(capacity)),
+            extension_lock: Mutex::new(()),
+            statement_lock: Mutex::new(()),
+
+            writer_hand: AtomicUsize::new(0),
+
+            stats: Stats::default(),
+        };
+        manager.grow(0..capacity);
+
+        manager
+    }
 
-            tag_table: RefCell::new(HashMap::with_capacity(capacity)),
-            descriptors,
-            first_free: AtomicI32::new(0),
-            buffers: vec![0; buffer_size * capacity],
-            buffer_size,
+    /// Replaces the pages by `policy` instead of the default one.
+    pub fn with_replacement_policy(mut self, policy: ReplacementPolicy) -> Self {
+        self.replacer = Mutex::new(policy.replacer(self.capacity()));
+        self
+    }
 
-            replacer: RefCell::new(replacer),
+    /// The number of buffers in the pool.
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::SeqCst)
+    }
+
+    /// Grows or shrinks the pool to `capacity` buffers while it is in use. The pages in
+    /// the buffers removed are evicted, shrinking fails and leaves the pool as it was if
+    /// some of them stay pinned.
+    pub fn resize(&self, capacity: usize) -> Result<()> {
+        ensure!(capacity > 0, error::InvalidCapacitySnafu { capacity });
+
+        let _resize = self.resize_lock.lock().unwrap();
+        let old_capacity = self.capacity();
+
+        if capacity > old_capacity {
+            self.grow(old_capacity..capacity);
+        } else if capacity < old_capacity {
+            self.shrink(capacity..old_capacity)?;
         }
+
+        Ok(())
     }
 
-    fn reuse_page(&self, tag: &PageTag) -> Result<(BufferId, BufferRef)> {
-        let first_free = self.first_free.load(Ordering::SeqCst);
-        let has_free_buffer = first_free >= 0;
+    /// Adds the buffers to the pool. Buffers left in the pool by a shrink which gave up
+    /// keep their pages.
+    fn grow(&self, ids: Range<BufferId>) {
+        self.descriptors.grow(ids.end, BufferDescriptor::new);
+
+        let mut free = vec![];
+        let mut kept = vec![];
+        for id in ids.clone() {
+            let desc = &self.descriptors[id];
+            let _content = desc.content_lock.write().unwrap();
+
+            // SAFETY: the content lock is held in exclusive mode
+            let frame = unsafe { &mut *desc.frame.get() };
+            match frame {
+                Some(_) => kept.push(id),
+                None => {
+                    *frame = Some(AlignedBuffer::new(self.page_size));
+                    free.push(id);
+                }
+            }
+        }
 
-        let id = if has_free_buffer {
-            let id = first_free as usize;
-            let desc = self.descriptors[id].clone();
-            // TODO: cas
-            self.first_free
-                .store(desc.borrow().next_free, Ordering::SeqCst);
+        let mut replacer = self.replacer.lock().unwrap();
+        replacer.resize(ids.end);
+        self.capacity.store(ids.end, Ordering::SeqCst);
+
+        // the replacer has not been told about them while they were being removed
+        kept.into_iter()
+            .filter(|&id| self.descriptors[id].pin_count() == 0)
+            .for_each(|id| replacer.unpin(id));
+        drop(replacer);
+
+        // lower buffers are used first, so that the higher ones are cheap to remove
+        self.free_buffers
+            .lock()
+            .unwrap()
+            .splice(0..0, free.into_iter().rev());
+    }
 
-            id
-        } else {
-            let id = self
-                .replacer
-                .borrow_mut()
-                .victim()
-                .ok_or(error::NoMoreBufferSnafu.build())?;
+    /// Removes the buffers from the pool, the pool is left as it was if they can't be.
+    fn shrink(&self, ids: Range<BufferId>) -> Result<()> {
+        // no buffer removed is handed out from now on
+        self.capacity.store(ids.start, Ordering::SeqCst);
+        self.free_buffers
+            .lock()
+            .unwrap()
+            .retain(|id| !ids.contains(id));
+
+        let result = self.remove_buffers(ids.clone());
+        match result {
+            Ok(()) => self.replacer.lock().unwrap().resize(ids.start),
+            // the buffers removed so far are put back
+            Err(_) => self.grow(ids),
+        }
 
-            self.flush_page(id)?;
+        result
+    }
 
-            id
-        };
+    /// Evicts the pages in the buffers and frees their frames, the pinned ones are tried
+    /// again until `SHRINK_TIMEOUT` has passed.
+    fn remove_buffers(&self, ids: Range<BufferId>) -> Result<()> {
+        let deadline = Instant::now() + SHRINK_TIMEOUT;
+        let mut pinned = ids.collect::<Vec<_>>();
+
+        loop {
+            let mut still_pinned = vec![];
+            for id in pinned {
+                if !self.remove_buffer(id)? {
+                    still_pinned.push(id);
+                }
+            }
+            pinned = still_pinned;
+
+            if pinned.is_empty() {
+                return Ok(());
+            }
+            ensure!(
+                Instant::now() < deadline,
+                error::BuffersInUseSnafu {
+                    count: pinned.len()
+                }
+            );
 
-        let mut desc = self.descriptors.get(id).unwrap().borrow_mut();
+            thread::yield_now();
+        }
+    }
 
-        let mut tag_table = self.tag_table.borrow_mut();
-        if !has_free_buffer {
-            tag_table.remove(desc.page_tag.as_ref().unwrap());
+    /// Evicts the page in the buffer and frees its frame, returns `false` if the buffer
+    /// is pinned.
+    fn remove_buffer(&self, id: BufferId) -> Result<bool> {
+        let desc = &self.descriptors[id];
+        if !desc.try_pin_unused() {
+            return Ok(false);
+        }
+        self.replacer.lock().unwrap().pin(id);
+
+        let content = desc.content_lock.write().unwrap();
+        let mut page_tag = desc.page_tag.lock().unwrap();
+
+        if let Some(tag) = page_tag.as_ref() {
+            let mut partition = self.tag_table.lock(tag);
+
+            // pinned by another session before the partition was locked
+            if desc.pin_count() > 1 {
+                desc.unpin();
+                return Ok(false);
+            }
+
+            // SAFETY: the content lock is held in exclusive mode
+            let data = unsafe { slice::from_raw_parts(self.frame(id), self.page_size) };
+            if desc.is_dirty()
+                && let Err(e) = self.write_page(tag, data)
+            {
+                desc.unpin();
+                return Err(e);
+            }
+            self.stats.record(&tag.file_node, Event::Eviction);
+
+            partition.remove(tag);
         }
-        tag_table.insert(tag.clone(), id);
 
-        desc.register(tag.clone());
-        self.replacer.borrow_mut().pin(id);
+        *page_tag = None;
+        desc.clear_flags();
 
-        let buffer_ref = self.get_buffer(id);
-        buffer_ref.reset();
+        // SAFETY: the content lock is held in exclusive mode
+        unsafe { *desc.frame.get() = None };
 
-        Ok((id, buffer_ref))
+        drop((page_tag, content));
+        desc.unpin();
+
+        Ok(true)
+    }
+
+    pub fn wal(&self) -> &Wal {
+        &self.wal
+    }
+
+    /// Holds out the other statements which change pages, until the guard is dropped.
+    pub fn lock_statement(&self) -> MutexGuard<'_, ()> {
+        self.statement_lock.lock().unwrap()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// The scratch files of queries, `None` if the database is kept in memory.
+    pub fn temp_files(&self) -> Option<&TempFileManager> {
+        self.temp_files.as_ref()
+    }
+
+    /// Keeps the files of the tablespace at `location`, which must be empty.
+    pub fn create_tablespace(&self, space_id: TableSpaceId, location: &Path) -> Result<()> {
+        self.store
+            .create_tablespace(&FileNode::tablespace_dir(space_id), location)
+            .context(error::IoSnafu)
+    }
+
+    /// Fails if the tablespace still holds any file.
+    pub fn drop_tablespace(&self, space_id: TableSpaceId) -> Result<()> {
+        self.store
+            .drop_tablespace(&FileNode::tablespace_dir(space_id))
+            .context(error::IoSnafu)
+    }
+
+    /// The counters of the whole buffer pool.
+    pub fn stats(&self) -> BufferStats {
+        self.stats.total()
+    }
+
+    /// The counters of every file the buffer pool has held pages of.
+    pub fn file_stats(&self) -> Vec<(FileNode, BufferStats)> {
+        self.stats.files()
+    }
+
+    /// Takes a look at every frame of the buffer pool. Frames are looked at one by one
+    /// without stopping other sessions, so the snapshot is not consistent across frames.
+    pub fn frames(&self) -> Vec<FrameInfo> {
+        self.descriptors
+            .iter()
+            .take(self.capacity())
+            .map(|desc| FrameInfo {
+                buffer_id: desc.buffer_id,
+                page_tag: desc.page_tag.lock().unwrap().clone(),
+                is_dirty: desc.is_dirty(),
+                pin_count: desc.pin_count(),
+            })
+            .collect()
     }
 
-    pub fn new_page(&self, file_node: &FileNode /* page_size: u8 */) -> Result<BufferRef> {
-        let page_size = DEFAULT_PAGE_SIZE;
+    fn pop_free_buffer(&self) -> Option<BufferId> {
+        self.free_buffers.lock().unwrap().pop()
+    }
+
+    /// Takes a buffer for the page, from the ring if one is given and its current buffer
+    /// is not in use, and from the whole pool otherwise.
+    fn reuse_page(&self, tag: &PageTag, mut ring: Option<&mut BufferRing>) -> Result<Frame<'_>> {
+        let mut ring_buffer = ring.as_ref().and_then(|ring| ring.current());
+
+        loop {
+            let id = match ring_buffer.take().or_else(|| self.pop_free_buffer()) {
+                Some(id) => id,
+                None => self
+                    .replacer
+                    .lock()
+                    .unwrap()
+                    .victim()
+                    .context(error::NoMoreBufferSnafu)?,
+            };
+
+            let desc = &self.descriptors[id];
+            // the victim may have been pinned again after it was chosen
+            if !desc.try_pin_unused() {
+                continue;
+            }
+            // removed by a shrink of the pool after it was chosen
+            if id >= self.capacity() {
+                desc.unpin();
+                continue;
+            }
+            // a buffer of the ring is still a candidate of the replacer
+            if ring.is_some() {
+                self.update_replacer(id, |replacer| replacer.pin(id));
+            }
+            let page = BufferRef::new(self, id);
+
+            let content = self.lock_content_exclusive(id, tag.clone());
+            let mut page_tag = desc.page_tag.lock().unwrap();
+            let (mut partition, mut old_partition) =
+                self.tag_table.lock_pair(tag, page_tag.as_ref());
+
+            if let Some(&cached_id) = partition.get(tag) {
+                self.pin(cached_id);
+                return Ok(Frame::Cached(BufferRef::new(self, cached_id)));
+            }
+
+            if let Some(old_tag) = page_tag.as_ref() {
+                // pinned by another session before the partition was locked
+                if desc.pin_count() > 1 {
+                    continue;
+                }
+
+                if desc.is_dirty() {
+                    self.write_page(old_tag, content.data)?;
+                }
+                self.stats.record(&old_tag.file_node, Event::Eviction);
+
+                old_partition
+                    .as_mut()
+                    .unwrap_or(&mut partition)
+                    .remove(old_tag);
+            }
+
+            partition.insert(tag.clone(), id);
+            *page_tag = Some(tag.clone());
+            desc.clear_flags();
+
+            drop((partition, old_partition, page_tag));
+
+            content.data.fill(0);
+
+            if let Some(ring) = ring.as_mut() {
+                ring.advance(id);
+            }
+
+            return Ok(Frame::Reserved(page, content));
+        }
+    }
+
+    pub fn new_page(&self, file_node: &FileNode) -> Result<BufferRef> {
+        let _extension = self.extension_lock.lock().unwrap();
 
         let path = file_node.file_path();
-        let page_num = self
-            .storage_manager
-            .page_count(&path, page_size)
-            .context(error::IoSnafu)? as u32;
-
-        self.storage_manager
-            .write(
-                &path,
-                page_num as u64 * page_size as u64,
-                &vec![0; page_size],
-            )
-            .context(error::IoSnafu)?;
+        let page_num = self.store.page_count(&path).context(error::IoSnafu)? as u32;
 
         let tag = PageTag {
             file_node: *file_node,
             page_num,
         };
 
-        let (id, page) = self.reuse_page(&tag)?;
-        self.tag_table.borrow_mut().insert(tag, id);
+        // take a buffer before extending the file, so that no page is left behind
+        // when the buffer pool is exhausted
+        let (page, content) = loop {
+            match self.reuse_page(&tag, None)? {
+                Frame::Reserved(page, content) => break (page, content),
+                // a concurrent read beyond the end of file, wait for it to fail
+                Frame::Cached(page) => page.wait_for_load(),
+            }
+        };
+
+        self.read_ahead.discard(&tag);
+        let result = self.store.write(&path, page_num, content.data);
+
+        if result.is_err() {
+            self.forget_page(page.id, &tag);
+        }
+
+        result.context(error::IoSnafu)?;
+        page.desc().set_valid();
 
         Ok(page)
     }
 
     pub fn fetch_page(&self, tag: PageTag) -> Result<BufferRef> {
-        if let Some(id) = self.get_buffer_id(&tag) {
-            let page = self.get_buffer(id);
-            self.replacer.borrow_mut().pin(id);
+        self.fetch(tag, true, None)
+    }
+
+    /// Starts reading the page in the background if it is not in the pool, so that
+    /// fetching it later doesn't wait for the whole read.
+    pub fn prefetch_page(&self, tag: PageTag) {
+        if self.tag_table.lock(&tag).contains_key(&tag) {
+            return;
+        }
+
+        self.read_ahead.start(&tag);
+    }
+
+    /// Fetches the page into a buffer of the ring if it is not in the pool, used by large
+    /// sequential scans.
+    pub fn fetch_page_with_ring(&self, tag: PageTag, ring: &mut BufferRing) -> Result<BufferRef> {
+        self.fetch(tag, true, Some(ring))
+    }
 
-            return Ok(page);
+    /// Fetches the page, its checksum is only verified if `verify` is set and the page is
+    /// read from the store.
+    fn fetch(
+        &self,
+        tag: PageTag,
+        verify: bool,
+        mut ring: Option<&mut BufferRing>,
+    ) -> Result<BufferRef> {
+        loop {
+            let page = match self.lookup(&tag) {
+                Some(page) => page,
+                None => match self.reuse_page(&tag, ring.as_deref_mut())? {
+                    Frame::Cached(page) => page,
+                    Frame::Reserved(page, content) => {
+                        self.stats.record(&tag.file_node, Event::Miss);
+                        let result = self.read_page(&tag, content.data, verify);
+
+                        if result.is_err() {
+                            self.forget_page(page.id, &tag);
+                        }
+
+                        result?;
+                        page.desc().set_valid();
+
+                        return Ok(page);
+                    }
+                },
+            };
+
+            page.wait_for_load();
+
+            // otherwise the page failed to load, and has been detached from the buffer
+            if page.desc().is_valid() {
+                self.stats.record(&tag.file_node, Event::Hit);
+                return Ok(page);
+            }
         }
+    }
 
-        let (id, mut page) = self.reuse_page(&tag)?;
+    /// Finds the page in the buffer pool and pins it.
+    fn lookup(&self, tag: &PageTag) -> Option<BufferRef<'_>> {
+        let partition = self.tag_table.lock(tag);
+        let id = *partition.get(tag)?;
 
-        self.storage_manager
-            .read(
-                &tag.file_node.file_path(),
-                tag.page_num as u64 * DEFAULT_PAGE_SIZE as u64,
-                page.as_slice_mut(),
-            )
-            .context(error::IoSnafu)?;
+        self.pin(id);
 
-        self.tag_table.borrow_mut().insert(tag, id);
+        Some(BufferRef::new(self, id))
+    }
 
-        Ok(page)
+    /// Detaches the page from the buffer, used when the buffer holds no valid page
+    /// so that it must not be found by the tag.
+    fn forget_page(&self, id: BufferId, tag: &PageTag) {
+        let mut page_tag = self.descriptors[id].page_tag.lock().unwrap();
+
+        self.tag_table.lock(tag).remove(tag);
+        *page_tag = None;
     }
 
     fn flush_page(&self, id: BufferId) -> Result<()> {
-        let desc = self.descriptors.get(id).unwrap().borrow();
+        let desc = &self.descriptors[id];
         if !desc.is_dirty() {
             return Ok(());
         }
 
-        let page_tag = desc.page_tag.as_ref().unwrap();
+        let _content = desc.content_lock.read().unwrap();
+        let page_tag = desc.page_tag.lock().unwrap();
 
-        let data = self.buffer_slice(id);
+        let Some(page_tag) = page_tag.as_ref() else {
+            return Ok(());
+        };
 
-        self.storage_manager
-            .write(
-                &page_tag.file_node.file_path(),
-                page_tag.page_num as u64 * DEFAULT_PAGE_SIZE as u64,
-                data,
-            )
-            .context(error::IoSnafu)
+        // the page can't change while the content lock is held, it is dirtied again only by
+        // changes made after it is written
+        if !desc.is_dirty() {
+            return Ok(());
+        }
+        desc.set_dirty(false);
+
+        // SAFETY: the content lock is held
+        let data = unsafe { slice::from_raw_parts(self.frame(id), self.page_size) };
+
+        let result = self.write_page(page_tag, data);
+        if result.is_err() {
+            desc.set_dirty(true);
+        }
+
+        result
+    }
+
+    /// Writes the page out, after the log is flushed up to the LSN of the page.
+    fn write_page(&self, page_tag: &PageTag, data: &[u8]) -> Result<()> {
+        self.wal.flush(page_lsn(data)).context(error::WalSnafu)?;
+
+        // the checksum is stamped on a copy, as the buffer may be shared with readers
+        let copy;
+        let data = if self.checksums {
+            let checksum = page_checksum(page_tag.page_num, data);
+            copy = {
+                let mut copy = AlignedBuffer::from_slice(data);
+                copy[CHECKSUM_RANGE].copy_from_slice(&checksum.to_le_bytes());
+                copy
+            };
+            &copy[..]
+        } else {
+            data
+        };
+
+        self.read_ahead.discard(page_tag);
+        self.store
+            .write(&page_tag.file_node.file_path(), page_tag.page_num, data)
+            .context(error::IoSnafu)?;
+        self.stats.record(&page_tag.file_node, Event::Write);
+
+        Ok(())
+    }
+
+    /// Reads the page into the buffer, unless it has been read ahead, and verifies its
+    /// checksum if `verify` is set.
+    fn read_page(&self, page_tag: &PageTag, data: &mut [u8], verify: bool) -> Result<()> {
+        if !self.read_ahead.take(page_tag, data) {
+            self.store
+                .read(&page_tag.file_node.file_path(), page_tag.page_num, data)
+                .context(error::IoSnafu)?;
+        }
+
+        // pages never written since the file was extended are all zeros
+        if verify && self.checksums && data.iter().any(|&b| b != 0) {
+            let checksum = u32::from_le_bytes(data[CHECKSUM_RANGE].try_into().unwrap());
+            ensure!(
+                checksum == page_checksum(page_tag.page_num, data),
+                error::ChecksumMismatchSnafu {
+                    page_tag: page_tag.clone()
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Logs the change made to the page since `before` was taken, and stamps the page
+    /// with the LSN of the record. Nothing is logged if the page is unchanged.
+    ///
+    /// The page is dirtied before the record is appended, so that a checkpoint starting
+    /// after the record never takes the page for clean and skips it.
+    pub fn log_page(
+        &self,
+        xid: Xid,
+        op: PageOp,
+        page: &mut BufferWriteGuard,
+        before: &[u8],
+    ) -> Result<()> {
+        let deltas = Delta::diff(before, page);
+        if deltas.is_empty() {
+            return Ok(());
+        }
 
-        // TODO: unset dirty flag
+        let record = Record::Page(PageRecord {
+            xid,
+            op,
+            page: (&page.tag).into(),
+            deltas,
+        });
+        self.descriptors[page.id].set_dirty(true);
+        let lsn = self.wal.append(&record).context(error::WalSnafu)?;
+        page.set_lsn(lsn);
+
+        Ok(())
     }
 
     pub fn flush_pages(&self) -> Result<()> {
         self.descriptors
-            .iter().try_for_each(|desc| {
-                let buf_id = desc.borrow().buffer_id;
-                self.flush_page(buf_id)
-            })
+            .iter()
+            .try_for_each(|desc| self.flush_page(desc.buffer_id))
     }
 
-    pub(super) fn get_buffer_id(&self, page_tag: &PageTag) -> Option<BufferId> {
-        self.tag_table.borrow().get(page_tag).map(ToOwned::to_owned)
+    pub fn dirty_page_count(&self) -> usize {
+        self.descriptors
+            .iter()
+            .filter(|desc| desc.is_dirty())
+            .count()
     }
 
-    pub(super) fn get_buffer(&self, id: BufferId) -> BufferRef {
-        let desc = self.descriptors.get(id).unwrap();
+    /// Writes out every dirty page, syncs the files and records a checkpoint, so that
+    /// recovery starts from here instead of the beginning of the log.
+    pub fn checkpoint(&self) -> Result<()> {
+        let checkpoint = self.wal.start_checkpoint();
 
-        BufferRef::new(
-            desc.clone(),
-            unsafe {
-                NonNull::new_unchecked(self.buffers.as_ptr().add(id * self.buffer_size).cast_mut())
-            },
-            self.buffer_size,
-        )
+        self.flush_pages()?;
+        self.store.sync_all().context(error::IoSnafu)?;
+
+        self.wal
+            .finish_checkpoint(&checkpoint)
+            .context(error::WalSnafu)
+    }
+
+    /// Writes out up to `max_pages` dirty pages which are not in use, so that sessions
+    /// rarely have to write a page before reusing its buffer. Every call continues from
+    /// where the last one stopped, returns the count of pages written.
+    pub fn write_dirty_pages(&self, max_pages: usize) -> Result<usize> {
+        let capacity = self.capacity();
+        let mut written = 0;
+
+        for _ in 0..capacity {
+            if written >= max_pages {
+                break;
+            }
+
+            let id = self.writer_hand.fetch_add(1, Ordering::SeqCst) % capacity;
+            let desc = &self.descriptors[id];
+            if desc.pin_count() > 0 || !desc.is_dirty() {
+                continue;
+            }
+
+            self.flush_page(id)?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    fn pin(&self, id: BufferId) {
+        if self.descriptors[id].pin() == 1 {
+            self.update_replacer(id, |replacer| replacer.pin(id));
+        }
+    }
+
+    fn unpin(&self, id: BufferId) {
+        if self.descriptors[id].unpin() == 0 {
+            self.update_replacer(id, |replacer| replacer.unpin(id));
+        }
+    }
+
+    /// Tells the replacer about the buffer, unless it has been removed by a shrink of the
+    /// pool, which the replacer has forgotten or is about to.
+    fn update_replacer(&self, id: BufferId, f: impl FnOnce(&mut dyn Replacer)) {
+        let mut replacer = self.replacer.lock().unwrap();
+        if id < self.capacity() {
+            f(replacer.as_mut());
+        }
+    }
+
+    /// The frame of the buffer, whose content lock must be held.
+    fn frame(&self, id: BufferId) -> *mut u8 {
+        // SAFETY: frames are only replaced under the content lock in exclusive mode, and
+        // a buffer in use has a frame
+        let frame = unsafe { &*self.descriptors[id].frame.get() };
+        frame.as_ref().unwrap().as_mut_ptr()
+    }
+
+    fn lock_content_shared(&self, id: BufferId, tag: PageTag) -> BufferReadGuard<'_> {
+        let lock = self.descriptors[id].content_lock.read().unwrap();
+
+        BufferReadGuard {
+            tag,
+            // SAFETY: the content lock is held in shared mode
+            data: unsafe { slice::from_raw_parts(self.frame(id), self.page_size) },
+            _lock: lock,
+        }
     }
 
-    fn buffer_slice(&self, id: BufferId) -> &[u8] {
-        &self.buffers[id * self.buffer_size..(id + 1) * self.buffer_size]
+    fn lock_content_exclusive(&self, id: BufferId, tag: PageTag) -> BufferWriteGuard<'_> {
+        let lock = self.descriptors[id].content_lock.write().unwrap();
+
+        BufferWriteGuard {
+            id,
+            tag,
+            // SAFETY: the content lock is held in exclusive mode
+            data: unsafe { slice::from_raw_parts_mut(self.frame(id), self.page_size) },
+            _lock: lock,
+        }
     }
 }
 
+const LSN_RANGE: Range<usize> = 0..8;
+const CHECKSUM_RANGE: Range<usize> = 8..12;
+
+fn page_lsn(page: &[u8]) -> Lsn {
+    Lsn::from_le_bytes(page[LSN_RANGE].try_into().unwrap())
+}
+
+/// The checksum covers the page number as well, so that a page written to a wrong place
+/// is detected.
+fn page_checksum(page_num: PageNum, page: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&page_num.to_le_bytes());
+    hasher.update(&page[..CHECKSUM_RANGE.start]);
+    hasher.update(&page[CHECKSUM_RANGE.end..]);
+
+    hasher.finalize()
+}
+
+/// | flags:16 | pin count:16 |
+const PIN_COUNT_MASK: u32 = 0xFFFF;
+const DIRTY_FLAG: u32 = 1 << 16;
+/// set once the page is loaded into the buffer
+const VALID_FLAG: u32 = 1 << 17;
+
 #[derive(Debug)]
 struct BufferDescriptor {
-    page_tag: Option<PageTag>,
+    page_tag: Mutex<Option<PageTag>>,
     buffer_id: BufferId,
-    next_free: i32,
     state: AtomicU32,
     content_lock: RwLock<()>,
+    /// aligned for direct I/O, `None` while the buffer is not in the pool
+    frame: UnsafeCell<Option<AlignedBuffer>>,
 }
 
 impl BufferDescriptor {
-    fn register(&mut self, tag: PageTag) {
-        self.page_tag = Some(tag)
+    fn new(buffer_id: BufferId) -> Self {
+        Self {
+            page_tag: Mutex::new(None),
+            buffer_id,
+            state: AtomicU32::new(0),
+            content_lock: RwLock::new(()),
+            frame: UnsafeCell::new(None),
+        }
     }
 
     fn is_dirty(&self) -> bool {
         let state = self.state.load(Ordering::SeqCst);
-        state == 1
+        state & DIRTY_FLAG != 0
+    }
+
+    fn set_dirty(&self, dirty: bool) {
+        if dirty {
+            self.state.fetch_or(DIRTY_FLAG, Ordering::SeqCst);
+        } else {
+            self.state.fetch_and(!DIRTY_FLAG, Ordering::SeqCst);
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.state.load(Ordering::SeqCst) & VALID_FLAG != 0
     }
 
-    fn set_dirty(&mut self, dirty: bool) {
-        let state = if dirty { 1 } else { 0 };
-        self.state.swap(state, Ordering::SeqCst);
+    fn set_valid(&self) {
+        self.state.fetch_or(VALID_FLAG, Ordering::SeqCst);
     }
 
-    fn pin(&self, _write: bool) {}
+    fn clear_flags(&self) {
+        self.state.fetch_and(PIN_COUNT_MASK, Ordering::SeqCst);
+    }
+
+    fn pin_count(&self) -> u32 {
+        self.state.load(Ordering::SeqCst) & PIN_COUNT_MASK
+    }
+
+    /// Returns the pin count after pinning.
+    fn pin(&self) -> u32 {
+        let state = self.state.fetch_add(1, Ordering::SeqCst);
+        debug_assert!(state & PIN_COUNT_MASK < PIN_COUNT_MASK);
+
+        (state & PIN_COUNT_MASK) + 1
+    }
+
+    /// Pins the buffer only if nobody else has pinned it.
+    fn try_pin_unused(&self) -> bool {
+        self.state
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                (state & PIN_COUNT_MASK == 0).then_some(state + 1)
+            })
+            .is_ok()
+    }
+
+    /// Returns the pin count after unpinning.
+    fn unpin(&self) -> u32 {
+        let state = self.state.fetch_sub(1, Ordering::SeqCst);
+        debug_assert!(state & PIN_COUNT_MASK > 0);
+
+        (state & PIN_COUNT_MASK) - 1
+    }
 }
 
-#[derive(Debug)]
+/// A pinned buffer, the buffer will not be evicted until all references to it are dropped.
+/// The content of the buffer is accessed through the guards returned by `read` and `write`.
 pub struct BufferRef<'a> {
-    // id: BufferId,
-    desc: Arc<RefCell<BufferDescriptor>>,
-    ptr: NonNull<u8>,
-    page_size: usize,
-    _phantom: PhantomData<&'a ()>,
+    manager: &'a BufferManager,
+    id: BufferId,
 }
 
-impl BufferRef<'_> {
-    fn new(desc: Arc<RefCell<BufferDescriptor>>, ptr: NonNull<u8>, page_size: usize) -> Self {
-        // TODO: pin
+impl<'a> BufferRef<'a> {
+    /// Wraps a buffer which has already been pinned.
+    fn new(manager: &'a BufferManager, id: BufferId) -> Self {
+        Self { manager, id }
+    }
 
-        Self {
-            desc,
-            ptr,
-            page_size,
-            _phantom: PhantomData,
-        }
+    fn desc(&self) -> &'a BufferDescriptor {
+        &self.manager.descriptors[self.id]
+    }
+
+    /// Waits for the session which reserved the buffer to load its page. The load may fail
+    /// and detach the page from the buffer, so neither the tag nor the content is looked at.
+    fn wait_for_load(&self) {
+        drop(self.desc().content_lock.read().unwrap());
+    }
+
+    /// The tag of the page, which a buffer keeps as long as it is pinned once the page is
+    /// loaded.
+    fn page_tag(&self) -> PageTag {
+        self.desc().page_tag.lock().unwrap().clone().unwrap()
     }
 
     pub fn page_num(&self) -> PageNum {
-        self.desc.borrow().page_tag.as_ref().unwrap().page_num
+        self.page_tag().page_num
     }
 
     pub fn set_dirty(&self) {
-        self.desc.borrow_mut().set_dirty(true);
+        self.desc().set_dirty(true);
     }
 
-    pub fn as_slice(&self) -> &[u8] {
-        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.page_size) }
+    /// Locks the content of the buffer in shared mode.
+    pub fn read(&self) -> BufferReadGuard<'_> {
+        self.manager.lock_content_shared(self.id, self.page_tag())
     }
 
-    pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.page_size) }
+    /// Locks the content of the buffer in exclusive mode.
+    pub fn write(&self) -> BufferWriteGuard<'_> {
+        self.manager
+            .lock_content_exclusive(self.id, self.page_tag())
     }
+}
 
-    fn reset(&self) {
-        unsafe { self.ptr.as_ptr().write_bytes(0, self.page_size) }
+impl fmt::Debug for BufferRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferRef")
+            .field("id", &self.id)
+            .field("desc", self.desc())
+            .finish()
     }
 }
 
 impl Drop for BufferRef<'_> {
     fn drop(&mut self) {
-        // TODO: unpin
+        self.manager.unpin(self.id);
+    }
+}
+
+/// Shared access to the content of a buffer, dereferences to the page without its header.
+pub struct BufferReadGuard<'a> {
+    tag: PageTag,
+    data: &'a [u8],
+    _lock: RwLockReadGuard<'a, ()>,
+}
+
+impl BufferReadGuard<'_> {
+    pub fn page_num(&self) -> PageNum {
+        self.tag.page_num
+    }
+
+    pub fn lsn(&self) -> Lsn {
+        page_lsn(self.data)
+    }
+}
+
+impl Deref for BufferReadGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.data[PAGE_HEADER_SIZE..]
+    }
+}
+
+/// Exclusive access to the content of a buffer, dereferences to the page without its header.
+pub struct BufferWriteGuard<'a> {
+    id: BufferId,
+    tag: PageTag,
+    data: &'a mut [u8],
+    _lock: RwLockWriteGuard<'a, ()>,
+}
+
+impl BufferWriteGuard<'_> {
+    pub fn page_num(&self) -> PageNum {
+        self.tag.page_num
+    }
+
+    pub fn lsn(&self) -> Lsn {
+        page_lsn(self.data)
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.data[LSN_RANGE].copy_from_slice(&lsn.to_le_bytes());
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use std::io::Read;
+impl Deref for BufferWriteGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.data[PAGE_HEADER_SIZE..]
+    }
+}
 
-//     use {super::*, crate::buffer::LruReplacer, tempfile::NamedTempFile};
+impl DerefMut for BufferWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data[PAGE_HEADER_SIZE..]
+    }
+}
 
-// #[test]
-// fn it_works() {
-//     let (mut file, path) = NamedTempFile::new().unwrap().into_parts();
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{buffer::Error, DEFAULT_PAGE_SIZE},
+        std::{collections::HashMap, thread},
+        tempfile::tempdir,
+    };
+
+    fn page_tag(page_num: PageNum) -> PageTag {
+        PageTag {
+            file_node: FileNode::new(0, 1, 2),
+            page_num,
+        }
+    }
 
-//     let size = NonZeroUsize::new(10).unwrap();
-//     let replacer = LruReplacer::new(size);
-//     let mut manager = BufferManager::new(size, replacer);
+    #[test]
+    fn page_failed_to_load() -> Result<()> {
+        let manager = BufferManager::in_memory(2, &ControlFile::default());
+        let tag = page_tag(0);
 
-//     let page_id = {
-//         let page = manager.new_page().unwrap();
+        let Frame::Reserved(page, content) = manager.reuse_page(&tag, None)? else {
+            panic!("the pool is empty");
+        };
 
-//         let mut page = page.borrow_mut();
-//         page.id = 0;
+        // found by another session while it is loaded, then detached as the load fails
+        let waiter = manager.lookup(&tag).unwrap();
+        manager.forget_page(page.id, &tag);
+        drop((content, page));
 
-//         let data = page.data_mut();
-//         data.fill(2);
+        waiter.wait_for_load();
+        assert!(!waiter.desc().is_valid());
+        drop(waiter);
 
-//         page.is_dirty = true;
+        // the page is loaded again by the next fetch
+        let page = manager.new_page(&tag.file_node)?;
+        assert_eq!(page.page_num(), 0);
+        drop(page.read());
 
-//         page.id
-//     };
+        Ok(())
+    }
 
-//     manager.flush_page(page_id).unwrap();
+    #[test]
+    fn pinned_buffers_are_not_evicted() -> Result<()> {
+        let manager = BufferManager::in_memory(2, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
 
-//     let mut buf = vec![0u8; PAGE_SIZE];
-//     file.read_exact(&mut buf).unwrap();
+        let p1 = manager.new_page(&file_node)?;
+        p1.write().fill(1);
+        p1.set_dirty();
 
-//     assert_eq!(&buf, &vec![2; PAGE_SIZE]);
-// }
-// }
+        let p2 = manager.new_page(&file_node)?;
+
+        // every frame is pinned
+        assert!(matches!(
+            manager.new_page(&file_node),
+            Err(Error::NoMoreBuffer { .. })
+        ));
+
+        drop(p2);
+
+        let p3 = manager.new_page(&file_node)?;
+        assert_eq!(p3.page_num(), 2);
+
+        // `p1` is still pinned, so it must not have been reused by `p3`
+        assert!(p1.read().iter().all(|&b| b == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn evicted_pages_are_written_back() -> Result<()> {
+        let manager = BufferManager::in_memory(2, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+
+        for i in 0..8 {
+            let page = manager.new_page(&file_node)?;
+            page.write().fill(i as u8 + 1);
+            page.set_dirty();
+        }
+
+        for i in 0..8 {
+            let page = manager.fetch_page(page_tag(i))?;
+            assert!(page.read().iter().all(|&b| b == i as u8 + 1));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn flushed_pages_are_clean() -> Result<()> {
+        let manager = BufferManager::in_memory(4, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+
+        let page = manager.new_page(&file_node)?;
+        page.write().fill(1);
+        page.set_dirty();
+        assert_eq!(manager.dirty_page_count(), 1);
+
+        manager.flush_pages()?;
+        assert_eq!(manager.dirty_page_count(), 0);
+
+        page.write().fill(2);
+        page.set_dirty();
+        assert_eq!(manager.write_dirty_pages(4)?, 0, "pinned pages are skipped");
+
+        drop(page);
+        assert_eq!(manager.write_dirty_pages(4)?, 1);
+        assert_eq!(manager.dirty_page_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn page_refs_share_a_frame() -> Result<()> {
+        let manager = BufferManager::in_memory(1, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+
+        let page = manager.new_page(&file_node)?;
+        let same_page = manager.fetch_page(page_tag(page.page_num()))?;
+        drop(page);
+
+        // the frame is still pinned by `same_page`
+        assert!(matches!(
+            manager.new_page(&file_node),
+            Err(Error::NoMoreBuffer { .. })
+        ));
+
+        drop(same_page);
+        manager.new_page(&file_node)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn stats_and_frames() -> Result<()> {
+        let manager = BufferManager::in_memory(1, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+        let other_file_node = FileNode::new(0, 1, 3);
+
+        manager.new_page(&file_node)?.set_dirty();
+        manager.fetch_page(page_tag(0))?;
+
+        // the page is written out to make room for a page of the other file, then read
+        // back in its place
+        manager.new_page(&other_file_node)?;
+        let page = manager.fetch_page(page_tag(0))?;
+
+        assert_eq!(
+            manager.stats(),
+            BufferStats {
+                hits: 1,
+                misses: 1,
+                evictions: 2,
+                writes: 1,
+            }
+        );
+
+        let file_stats = manager.file_stats().into_iter().collect::<HashMap<_, _>>();
+        assert_eq!(
+            file_stats[&file_node],
+            BufferStats {
+                hits: 1,
+                misses: 1,
+                evictions: 1,
+                writes: 1,
+            }
+        );
+        assert_eq!(file_stats[&other_file_node].evictions, 1);
+
+        assert_eq!(
+            manager.frames(),
+            vec![FrameInfo {
+                buffer_id: 0,
+                page_tag: Some(page_tag(0)),
+                is_dirty: false,
+                pin_count: 1,
+            }]
+        );
+
+        page.set_dirty();
+        drop(page);
+        assert!(manager.frames()[0].is_dirty);
+        assert_eq!(manager.frames()[0].pin_count, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn grow_and_shrink() -> Result<()> {
+        let manager = BufferManager::in_memory(2, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+
+        manager.resize(4)?;
+        let mut pages = (0..4)
+            .map(|_| manager.new_page(&file_node))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(manager.frames().len(), 4);
+
+        for page in &pages {
+            page.write().fill(page.page_num() as u8 + 1);
+            page.set_dirty();
+        }
+
+        // the last page is in a buffer to remove, and still pinned
+        let last_page = pages.pop().unwrap();
+        drop(pages);
+        assert!(matches!(
+            manager.resize(2),
+            Err(Error::BuffersInUse { count: 1, .. })
+        ));
+        assert_eq!(manager.capacity(), 4);
+
+        drop(last_page);
+        manager.resize(2)?;
+        assert_eq!(manager.frames().len(), 2);
+
+        // the pages evicted have been written out
+        for page_num in 0..4 {
+            let page = manager.fetch_page(page_tag(page_num))?;
+            assert!(page.read().iter().all(|&b| b == page_num as u8 + 1));
+        }
+
+        assert!(matches!(
+            manager.resize(0),
+            Err(Error::InvalidCapacity { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resize_while_in_use() -> Result<()> {
+        const SESSIONS: u32 = 4;
+        const PAGES: u32 = 64;
+
+        let manager = BufferManager::in_memory(8, &ControlFile::default());
+
+        thread::scope(|s| {
+            let sessions = (0..SESSIONS)
+                .map(|session| {
+                    let manager = &manager;
+
+                    s.spawn(move || -> Result<()> {
+                        let file_node = FileNode::new(0, 1, session);
+
+                        for i in 0..PAGES {
+                            let page = manager.new_page(&file_node)?;
+                            page.write().fill((session * PAGES + i) as u8);
+                            page.set_dirty();
+                        }
+
+                        for i in 0..PAGES {
+                            let page = manager.fetch_page(PageTag {
+                                file_node,
+                                page_num: i,
+                            })?;
+                            assert!(page
+                                .read()
+                                .iter()
+                                .all(|&b| b == (session * PAGES + i) as u8));
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for capacity in [16, 8, 12, 8].into_iter().cycle() {
+                if sessions.iter().all(|session| session.is_finished()) {
+                    break;
+                }
+                manager.resize(capacity).unwrap();
+            }
+
+            sessions
+                .into_iter()
+                .try_for_each(|session| session.join().unwrap())
+        })?;
+
+        manager.resize(8)?;
+        assert_eq!(manager.frames().len(), 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn scans_through_a_ring_keep_other_pages() -> Result<()> {
+        const HOT_PAGES: u32 = 4;
+        const SCANNED_PAGES: u32 = 32;
+
+        for policy in [
+            ReplacementPolicy::Lru,
+            ReplacementPolicy::Clock,
+            ReplacementPolicy::LruK(2),
+        ] {
+            let manager = BufferManager::in_memory(8, &ControlFile::default())
+                .with_replacement_policy(policy);
+            let file_node = page_tag(0).file_node;
+            let scanned_file_node = FileNode::new(0, 1, 3);
+
+            for _ in 0..SCANNED_PAGES {
+                manager.new_page(&scanned_file_node)?;
+            }
+            for _ in 0..HOT_PAGES {
+                manager.new_page(&file_node)?;
+            }
+
+            let mut ring = BufferRing::new(2);
+            for page_num in 0..SCANNED_PAGES {
+                let page = manager.fetch_page_with_ring(
+                    PageTag {
+                        file_node: scanned_file_node,
+                        page_num,
+                    },
+                    &mut ring,
+                )?;
+                assert!(page.read().iter().all(|&b| b == 0));
+            }
+
+            let misses = manager.stats().misses;
+            for page_num in 0..HOT_PAGES {
+                manager.fetch_page(page_tag(page_num))?;
+            }
+            assert_eq!(manager.stats().misses, misses, "{:?}", policy);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn buffer_manager_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<BufferManager>();
+        assert_send_sync::<BufferRef>();
+    }
+
+    #[test]
+    fn concurrent_sessions() -> Result<()> {
+        const SESSIONS: u32 = 4;
+        const PAGES: u32 = 32;
+
+        let manager = BufferManager::in_memory(8, &ControlFile::default());
+
+        thread::scope(|s| {
+            for session in 0..SESSIONS {
+                let manager = &manager;
+
+                s.spawn(move || -> Result<()> {
+                    let file_node = FileNode::new(0, 1, session);
+
+                    for i in 0..PAGES {
+                        let page = manager.new_page(&file_node)?;
+                        page.write().fill((session * PAGES + i) as u8);
+                        page.set_dirty();
+                    }
+
+                    for i in 0..PAGES {
+                        let page = manager.fetch_page(PageTag {
+                            file_node,
+                            page_num: i,
+                        })?;
+                        assert!(page
+                            .read()
+                            .iter()
+                            .all(|&b| b == (session * PAGES + i) as u8));
+                    }
+
+                    Ok(())
+                });
+            }
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_writes_to_a_page() -> Result<()> {
+        const SESSIONS: usize = 4;
+        const INCREMENTS: usize = 1000;
+        const OTHER_PAGES: u32 = 16;
+
+        let manager = BufferManager::in_memory(8, &ControlFile::default());
+        let file_node = page_tag(0).file_node;
+
+        for _ in 0..=OTHER_PAGES {
+            manager.new_page(&file_node)?;
+        }
+
+        thread::scope(|s| {
+            for _ in 0..SESSIONS {
+                s.spawn(|| {
+                    for i in 0..INCREMENTS {
+                        let page = manager.fetch_page(page_tag(0)).unwrap();
+                        let mut content = page.write();
+                        let counter = u64::from_le_bytes(content[..8].try_into().unwrap());
+                        content[..8].copy_from_slice(&(counter + 1).to_le_bytes());
+                        page.set_dirty();
+                        drop(content);
+                        drop(page);
+
+                        // push the page out of the pool now and then
+                        manager
+                            .fetch_page(page_tag(1 + i as u32 % OTHER_PAGES))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let page = manager.fetch_page(page_tag(0))?;
+        let counter = u64::from_le_bytes(page.read()[..8].try_into().unwrap());
+        assert_eq!(counter, (SESSIONS * INCREMENTS) as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn log_is_flushed_before_page() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+        let manager = BufferManager::new(1, dir.path().to_path_buf())?;
+        let file_node = page_tag(0).file_node;
+
+        let page_ref = manager.new_page(&file_node)?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+        page[..4].fill(1);
+        manager.log_page(1, PageOp::LeafInsert, &mut page, &before)?;
+        page_ref.set_dirty();
+
+        let lsn = page.lsn();
+        assert!(lsn > manager.wal().flushed_lsn());
+
+        drop(page);
+        drop(page_ref);
+
+        // evicts the page
+        manager.new_page(&file_node)?;
+        assert!(manager.wal().flushed_lsn() >= lsn);
+
+        let page = manager.fetch_page(page_tag(0))?;
+        assert_eq!(page.read().lsn(), lsn);
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_pages_are_detected() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile {
+            checksums: true,
+            ..Default::default()
+        }
+        .write(dir.path())
+        .context(error::ControlSnafu)?;
+        let file_node = page_tag(0).file_node;
+
+        {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            for i in 0..2 {
+                let page = manager.new_page(&file_node)?;
+                page.write().fill(i + 1);
+                page.set_dirty();
+            }
+            manager.flush_pages()?;
+        }
+
+        // a byte flipped on disk
+        let path = dir.path().join(file_node.file_path());
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[DEFAULT_PAGE_SIZE + 100] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+
+        let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+        assert!(manager
+            .fetch_page(page_tag(0))?
+            .read()
+            .iter()
+            .all(|&b| b == 1));
+        assert!(matches!(
+            manager.fetch_page(page_tag(1)),
+            Err(Error::ChecksumMismatch { page_tag: tag, .. }) if tag == page_tag(1)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use {
+    super::{BufferManager, BufferRef},
+    crate::buffer::{error, PageTag, Result},
+    snafu::ResultExt,
+    std::collections::HashMap,
+    wal::{Checkpoint, Lsn, PageOp, PageRecord, Record, Xid},
+};
+
+impl BufferManager {
+    /// Brings the data files back to a consistent state after a crash. Changes logged after
+    /// the last checkpoint are redone, then the changes of statements which never finished
+    /// are undone. The log is read from the last checkpoint.
+    ///
+    /// Every change after the checkpoint is redone whatever the LSN of its page, as a page
+    /// torn by a crash in the middle of its write may carry the LSN of changes it misses.
+    /// Changes only set bytes to the values they had afterwards, so redoing them again
+    /// in order leaves the page as it was before the crash.
+    ///
+    /// Undoing is logged as well, so a crash during recovery is recovered by the next run.
+    pub fn recover(&self) -> Result<()> {
+        let Checkpoint { redo, undo, .. } = self
+            .wal
+            .last_checkpoint()
+            .context(error::WalSnafu)?
+            .unwrap_or(Checkpoint {
+                redo: 0,
+                undo: 0,
+                next_xid: 0,
+            });
+
+        let mut unfinished: HashMap<Xid, Vec<(Lsn, PageRecord)>> = HashMap::new();
+
+        for item in self.wal.reader(undo).context(error::WalSnafu)? {
+            let (lsn, record) = item.context(error::WalSnafu)?;
+
+            match record {
+                Record::Begin { xid } => {
+                    unfinished.entry(xid).or_default();
+                }
+                Record::Commit { xid } | Record::Abort { xid } => {
+                    unfinished.remove(&xid);
+                }
+                Record::Page(record) => {
+                    // changes before the checkpoint have been written out
+                    if lsn > redo {
+                        self.redo(lsn, &record)?;
+                    }
+                    unfinished
+                        .entry(record.xid)
+                        .or_default()
+                        .push((lsn, record));
+                }
+            }
+        }
+
+        let mut records = unfinished
+            .iter_mut()
+            .flat_map(|(_, records)| records.drain(..))
+            .collect::<Vec<_>>();
+        records.sort_unstable_by_key(|&(lsn, _)| lsn);
+
+        for (_, record) in records.iter().rev() {
+            self.undo(record)?;
+        }
+
+        let mut lsn = self.wal.flushed_lsn();
+        for &xid in unfinished.keys() {
+            lsn = self
+                .wal
+                .append(&Record::Abort { xid })
+                .context(error::WalSnafu)?;
+        }
+        self.wal.flush(lsn).context(error::WalSnafu)?;
+
+        self.checkpoint()
+    }
+
+    /// Undoes the changes of a statement which has failed, and finishes it. The statement
+    /// must have held out the other ones since it started, as its changes are undone
+    /// byte by byte.
+    pub fn abort(&self, xid: Xid) -> Result<()> {
+        let records = self.wal.page_records(xid).context(error::WalSnafu)?;
+        for record in records.iter().rev() {
+            self.undo(record)?;
+        }
+
+        self.wal.abort(xid).context(error::WalSnafu)
+    }
+
+    fn redo(&self, lsn: Lsn, record: &PageRecord) -> Result<()> {
+        let page_ref = self.fetch_or_extend(&(&record.page).into())?;
+        let mut page = page_ref.write();
+
+        record.deltas.iter().for_each(|delta| delta.redo(&mut page));
+        page.set_lsn(lsn);
+        page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    fn undo(&self, record: &PageRecord) -> Result<()> {
+        let page_ref = self.fetch_page((&record.page).into())?;
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+
+        record
+            .deltas
+            .iter()
+            .rev()
+            .for_each(|delta| delta.undo(&mut page));
+        self.log_page(record.xid, PageOp::Undo, &mut page, &before)?;
+        page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    /// Fetches the page, the file is extended first if the page was never written out.
+    /// The checksum is not verified, as the page may be torn, and is repaired by redoing.
+    fn fetch_or_extend(&self, tag: &PageTag) -> Result<BufferRef<'_>> {
+        let path = tag.file_node.file_path();
+
+        while self.store.page_count(&path).context(error::IoSnafu)? <= tag.page_num as usize {
+            self.new_page(&tag.file_node)?;
+        }
+
+        self.fetch(tag.clone(), false, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            buffer::{BufferManager, FileNode, WAL_DIR},
+            control::ControlFile,
+            store::{Fault, FaultyStore, FileStore},
+        },
+        std::{sync::Arc, thread, time::Duration},
+        tempfile::tempdir,
+        wal::Wal,
+    };
+
+    fn write_page(manager: &BufferManager, xid: Xid, page_num: u32, byte: u8) -> Result<()> {
+        let file_node = FileNode::new(0, 1, 2);
+        let page_ref =
+            if page_num < manager.store.page_count(&file_node.file_path()).unwrap() as u32 {
+                manager.fetch_page(PageTag {
+                    file_node,
+                    page_num,
+                })?
+            } else {
+                manager.new_page(&file_node)?
+            };
+
+        let mut page = page_ref.write();
+        let before = page.to_vec();
+        page.fill(byte);
+        manager.log_page(xid, PageOp::LeafInsert, &mut page, &before)?;
+        page_ref.set_dirty();
+
+        Ok(())
+    }
+
+    fn read_page(manager: &BufferManager, page_num: u32) -> Result<Vec<u8>> {
+        let page = manager.fetch_page(PageTag {
+            file_node: FileNode::new(0, 1, 2),
+            page_num,
+        })?;
+        let content = page.read().to_vec();
+
+        Ok(content)
+    }
+
+    #[test]
+    fn committed_changes_are_redone() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+
+        {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 1)?;
+            write_page(&manager, xid, 1, 2)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+
+            // crash before the pages are written out
+        }
+
+        let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+        manager.recover()?;
+
+        assert!(read_page(&manager, 0)?.iter().all(|&b| b == 1));
+        assert!(read_page(&manager, 1)?.iter().all(|&b| b == 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unfinished_changes_are_undone() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+
+        {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 1)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 2)?;
+            write_page(&manager, xid, 1, 3)?;
+
+            // crash after the unfinished changes are written out
+            manager.flush_pages()?;
+        }
+
+        for _ in 0..2 {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            manager.recover()?;
+
+            assert!(read_page(&manager, 0)?.iter().all(|&b| b == 1));
+            assert!(read_page(&manager, 1)?.iter().all(|&b| b == 0));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn aborted_changes_stay_undone() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+
+        {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 1)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 2)?;
+            write_page(&manager, xid, 1, 3)?;
+            manager.abort(xid)?;
+
+            assert!(read_page(&manager, 0)?.iter().all(|&b| b == 1));
+            assert!(read_page(&manager, 1)?.iter().all(|&b| b == 0));
+
+            // a later statement changes a page the aborted one had changed
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 1, 4)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+        }
+
+        let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+        manager.recover()?;
+
+        assert!(read_page(&manager, 0)?.iter().all(|&b| b == 1));
+        assert!(read_page(&manager, 1)?.iter().all(|&b| b == 4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn checkpoint_during_write() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+
+        {
+            let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 1)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+            manager.checkpoint()?;
+
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            let page_ref = manager.fetch_page(PageTag {
+                file_node: FileNode::new(0, 1, 2),
+                page_num: 0,
+            })?;
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+            page.fill(2);
+            manager.log_page(xid, PageOp::LeafInsert, &mut page, &before)?;
+
+            // the checkpoint starts after the change is logged, but before the writer is
+            // done with the page
+            thread::scope(|s| {
+                let checkpoint = s.spawn(|| manager.checkpoint());
+                thread::sleep(Duration::from_millis(100));
+
+                page_ref.set_dirty();
+                drop(page);
+                checkpoint.join().unwrap()
+            })?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+
+            // crash before the page is written out again
+        }
+
+        let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+        manager.recover()?;
+
+        assert!(read_page(&manager, 0)?.iter().all(|&b| b == 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn torn_pages_are_repaired() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let control = ControlFile {
+            checksums: true,
+            ..Default::default()
+        };
+        control.write(dir.path()).context(error::ControlSnafu)?;
+
+        {
+            // the page is extended and written by the checkpoint, then torn by the next write
+            let store = Arc::new(FaultyStore::new(
+                FileStore::new(dir.path().to_path_buf(), control.page_size),
+                [(2, Fault::ShortWrite(100))],
+            ));
+            let wal = Wal::open(&dir.path().join(WAL_DIR)).context(error::WalSnafu)?;
+            let manager = BufferManager::with_store(4, Box::new(store.clone()), wal, &control);
+
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 1)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+            manager.checkpoint()?;
+
+            let xid = manager.wal().begin().context(error::WalSnafu)?;
+            write_page(&manager, xid, 0, 2)?;
+            manager.wal().commit(xid).context(error::WalSnafu)?;
+
+            assert!(manager.flush_pages().is_err());
+            store.crash();
+        }
+
+        let manager = BufferManager::new(4, dir.path().to_path_buf())?;
+        manager.recover()?;
+
+        assert!(read_page(&manager, 0)?.iter().all(|&b| b == 2));
+
+        Ok(())
+    }
+}
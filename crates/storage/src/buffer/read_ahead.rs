@@ -0,0 +1,153 @@
+use {
+    super::PageTag,
+    crate::{aligned::AlignedBuffer, store::PageStore},
+    std::{
+        collections::HashMap,
+        io,
+        sync::{
+            mpsc::{self, Receiver, Sender},
+            Arc, Mutex,
+        },
+        thread::{self, JoinHandle},
+    },
+};
+
+/// The most pages read ahead and not yet fetched.
+const MAX_PENDING_PAGES: usize = 64;
+
+type Reply = io::Result<AlignedBuffer>;
+
+/// Reads pages on a thread of its own before sessions fetch them. The pages read ahead
+/// are kept aside, and enter the buffer pool when they are fetched.
+pub(super) struct ReadAhead {
+    store: Arc<dyn PageStore>,
+    page_size: usize,
+    /// the reads not yet taken by a fetch
+    pending: Mutex<HashMap<PageTag, Receiver<Reply>>>,
+    /// started by the first read ahead
+    worker: Mutex<Option<Worker>>,
+}
+
+struct Worker {
+    requests: Sender<(PageTag, Sender<Reply>)>,
+    handle: JoinHandle<()>,
+}
+
+impl ReadAhead {
+    pub fn new(store: Arc<dyn PageStore>, page_size: usize) -> Self {
+        Self {
+            store,
+            page_size,
+            pending: Default::default(),
+            worker: Default::default(),
+        }
+    }
+
+    /// Starts reading the page, unless it is being read already or too many pages are
+    /// waiting to be fetched.
+    pub fn start(&self, tag: &PageTag) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= MAX_PENDING_PAGES || pending.contains_key(tag) {
+            return;
+        }
+
+        let mut worker = self.worker.lock().unwrap();
+        let worker = worker.get_or_insert_with(|| self.spawn_worker());
+
+        let (reply, receiver) = mpsc::channel();
+        if worker.requests.send((tag.clone(), reply)).is_ok() {
+            pending.insert(tag.clone(), receiver);
+        }
+    }
+
+    /// Copies the page read ahead into `data`, waiting for the read to finish. Returns
+    /// `false` if the page has not been read ahead, or could not be read.
+    pub fn take(&self, tag: &PageTag, data: &mut [u8]) -> bool {
+        let Some(receiver) = self.pending.lock().unwrap().remove(tag) else {
+            return false;
+        };
+
+        match receiver.recv() {
+            Ok(Ok(page)) => {
+                data.copy_from_slice(&page);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops the page read ahead, as it is about to be written and the copy would be stale.
+    pub fn discard(&self, tag: &PageTag) {
+        self.pending.lock().unwrap().remove(tag);
+    }
+
+    fn spawn_worker(&self) -> Worker {
+        let (requests, received) = mpsc::channel::<(PageTag, Sender<Reply>)>();
+        let store = self.store.clone();
+        let page_size = self.page_size;
+
+        let handle = thread::Builder::new()
+            .name("read ahead".to_string())
+            .spawn(move || {
+                for (tag, reply) in received {
+                    let mut data = AlignedBuffer::new(page_size);
+                    let result = store
+                        .read(&tag.file_node.file_path(), tag.page_num, &mut data)
+                        .map(|_| data);
+
+                    // the fetch may have stopped waiting
+                    reply.send(result).ok();
+                }
+            })
+            .unwrap();
+
+        Worker { requests, handle }
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        if let Some(Worker { requests, handle }) = self.worker.get_mut().unwrap().take() {
+            drop(requests);
+            handle.join().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{buffer::FileNode, store::MemoryStore},
+    };
+
+    #[test]
+    fn pages_read_ahead_are_taken_once() {
+        const PAGE_SIZE: usize = 64;
+
+        let store = Arc::new(MemoryStore::new());
+        let tag = PageTag {
+            file_node: FileNode::new(0, 1, 2),
+            page_num: 0,
+        };
+        let path = tag.file_node.file_path();
+        store.write(&path, 0, &[1; PAGE_SIZE]).unwrap();
+
+        let read_ahead = ReadAhead::new(store.clone(), PAGE_SIZE);
+        let mut data = [0; PAGE_SIZE];
+        assert!(!read_ahead.take(&tag, &mut data));
+
+        read_ahead.start(&tag);
+        assert!(read_ahead.take(&tag, &mut data));
+        assert_eq!(data, [1; PAGE_SIZE]);
+        assert!(!read_ahead.take(&tag, &mut data));
+
+        read_ahead.start(&tag);
+        read_ahead.discard(&tag);
+        assert!(!read_ahead.take(&tag, &mut data));
+
+        // pages past the end are left to the fetch
+        read_ahead.start(&PageTag { page_num: 1, ..tag });
+        assert!(!read_ahead.take(&PageTag { page_num: 1, ..tag }, &mut data));
+    }
+}
@@ -1,24 +1,91 @@
-use {super::BufferId, lru::LruCache, std::num::NonZeroUsize};
+mod clock;
+mod lru;
+mod lru_k;
 
-pub struct Replacer(LruCache<BufferId, ()>);
+pub use self::{clock::Clock, lru::Lru, lru_k::LruK};
+use super::BufferId;
 
-impl Replacer {
-    pub fn new(capacity: usize) -> Self {
-        let capacity = NonZeroUsize::new(capacity).unwrap();
-        Self(LruCache::new(capacity))
-    }
+/// Chooses the buffers to evict. Only unpinned buffers are candidates, the buffer
+/// manager tells the replacer when a buffer is pinned by its first user, and when it is
+/// unpinned by its last one.
+pub trait Replacer: Send {
+    /// The buffer is in use, and must not be evicted.
+    fn pin(&mut self, buffer_id: BufferId);
+
+    /// The buffer is no longer in use, and may be evicted.
+    fn unpin(&mut self, buffer_id: BufferId);
+
+    /// Takes a buffer to evict out of the candidates, `None` if every buffer is in use.
+    fn victim(&mut self) -> Option<BufferId>;
+
+    /// The pool has been resized to `capacity` buffers, the buffers beyond it are
+    /// forgotten.
+    fn resize(&mut self, capacity: usize);
 }
 
-impl Replacer {
-    pub fn pin(&mut self, buffer_id: BufferId) {
-        self.0.pop(&buffer_id);
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ReplacementPolicy {
+    /// evicts the buffer unpinned the longest time ago
+    #[default]
+    Lru,
+    /// the clock sweep
+    Clock,
+    /// evicts the buffer whose K-th most recent use is the oldest
+    LruK(usize),
+}
+
+impl ReplacementPolicy {
+    pub(super) fn replacer(&self, capacity: usize) -> Box<dyn Replacer> {
+        match *self {
+            Self::Lru => Box::new(Lru::new(capacity)),
+            Self::Clock => Box::new(Clock::new(capacity)),
+            Self::LruK(k) => Box::new(LruK::new(capacity, k)),
+        }
     }
+}
 
-    pub fn unpin(&mut self, buffer_id: BufferId) {
-        self.0.put(buffer_id, ());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICIES: [ReplacementPolicy; 3] = [
+        ReplacementPolicy::Lru,
+        ReplacementPolicy::Clock,
+        ReplacementPolicy::LruK(2),
+    ];
+
+    #[test]
+    fn pinned_buffers_are_never_victims() {
+        for policy in POLICIES {
+            let mut replacer = policy.replacer(4);
+            (0..4).for_each(|id| replacer.unpin(id));
+            replacer.pin(1);
+            replacer.pin(2);
+
+            let mut victims = vec![replacer.victim().unwrap(), replacer.victim().unwrap()];
+            victims.sort();
+            assert_eq!(victims, [0, 3], "{:?}", policy);
+            assert_eq!(replacer.victim(), None, "{:?}", policy);
+
+            replacer.unpin(2);
+            assert_eq!(replacer.victim(), Some(2), "{:?}", policy);
+        }
     }
 
-    pub fn victim(&mut self) -> Option<BufferId> {
-        self.0.pop_lru().map(|(k, _)| k)
+    #[test]
+    fn scans_spare_hot_buffers() {
+        for policy in POLICIES {
+            let mut replacer = policy.replacer(4);
+
+            // a buffer used over and over, then a scan through the others
+            (0..4).for_each(|_| replacer.unpin(0));
+            (1..4).for_each(|id| replacer.unpin(id));
+
+            let expected = match policy {
+                ReplacementPolicy::Lru => 0,
+                _ => 1,
+            };
+            assert_eq!(replacer.victim(), Some(expected), "{:?}", policy);
+        }
     }
 }
@@ -0,0 +1,84 @@
+use super::{BufferId, Replacer};
+
+/// The most uses a buffer is credited with, so that a page used heavily long ago
+/// doesn't stay forever.
+const MAX_USAGE_COUNT: u8 = 5;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    is_evictable: bool,
+    /// raised when the buffer is used, lowered when the hand passes over it
+    usage_count: u8,
+}
+
+/// Sweeps the buffers in a circle, and evicts the first one whose usage count has
+/// dropped to zero. A buffer used once is spared a single round only, so pages read by
+/// a scan leave before pages used over and over.
+pub struct Clock {
+    slots: Vec<Slot>,
+    hand: usize,
+    evictable_count: usize,
+}
+
+impl Clock {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: vec![Slot::default(); capacity],
+            hand: 0,
+            evictable_count: 0,
+        }
+    }
+}
+
+impl Replacer for Clock {
+    fn pin(&mut self, buffer_id: BufferId) {
+        let slot = &mut self.slots[buffer_id];
+        if slot.is_evictable {
+            slot.is_evictable = false;
+            self.evictable_count -= 1;
+        }
+    }
+
+    fn unpin(&mut self, buffer_id: BufferId) {
+        let slot = &mut self.slots[buffer_id];
+        if !slot.is_evictable {
+            slot.is_evictable = true;
+            self.evictable_count += 1;
+        }
+        slot.usage_count = (slot.usage_count + 1).min(MAX_USAGE_COUNT);
+    }
+
+    fn victim(&mut self) -> Option<BufferId> {
+        if self.evictable_count == 0 {
+            return None;
+        }
+
+        // ends within `MAX_USAGE_COUNT` + 1 rounds
+        loop {
+            let buffer_id = self.hand;
+            self.hand = (self.hand + 1) % self.slots.len();
+
+            let slot = &mut self.slots[buffer_id];
+            if !slot.is_evictable {
+                continue;
+            }
+
+            if slot.usage_count > 0 {
+                slot.usage_count -= 1;
+            } else {
+                slot.is_evictable = false;
+                self.evictable_count -= 1;
+                return Some(buffer_id);
+            }
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        self.slots.resize(capacity, Slot::default());
+        self.evictable_count = self.slots.iter().filter(|slot| slot.is_evictable).count();
+
+        if self.hand >= capacity {
+            self.hand = 0;
+        }
+    }
+}
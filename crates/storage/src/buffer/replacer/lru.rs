@@ -0,0 +1,43 @@
+use {
+    super::{BufferId, Replacer},
+    lru::LruCache,
+    std::num::NonZeroUsize,
+};
+
+/// Evicts the buffer unpinned the longest time ago.
+pub struct Lru(LruCache<BufferId, ()>);
+
+impl Lru {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap();
+        Self(LruCache::new(capacity))
+    }
+}
+
+impl Replacer for Lru {
+    fn pin(&mut self, buffer_id: BufferId) {
+        self.0.pop(&buffer_id);
+    }
+
+    fn unpin(&mut self, buffer_id: BufferId) {
+        self.0.put(buffer_id, ());
+    }
+
+    fn victim(&mut self) -> Option<BufferId> {
+        self.0.pop_lru().map(|(k, _)| k)
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        let removed = self
+            .0
+            .iter()
+            .map(|(&buffer_id, _)| buffer_id)
+            .filter(|&buffer_id| buffer_id >= capacity)
+            .collect::<Vec<_>>();
+        removed.iter().for_each(|buffer_id| {
+            self.0.pop(buffer_id);
+        });
+
+        self.0.resize(NonZeroUsize::new(capacity).unwrap());
+    }
+}
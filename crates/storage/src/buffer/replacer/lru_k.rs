@@ -0,0 +1,65 @@
+use {
+    super::{BufferId, Replacer},
+    std::collections::{HashMap, HashSet, VecDeque},
+};
+
+/// Evicts the buffer whose K-th most recent use is the oldest. Buffers used fewer than
+/// K times go first, oldest first, so pages read once by a scan never push out pages
+/// used over and over.
+pub struct LruK {
+    k: usize,
+    /// counts the uses, stands for the time
+    now: u64,
+    /// the times of the last K uses of every buffer, oldest first
+    history: HashMap<BufferId, VecDeque<u64>>,
+    evictable: HashSet<BufferId>,
+}
+
+impl LruK {
+    pub fn new(capacity: usize, k: usize) -> Self {
+        assert!(k > 0);
+
+        Self {
+            k,
+            now: 0,
+            history: HashMap::with_capacity(capacity),
+            evictable: HashSet::with_capacity(capacity),
+        }
+    }
+}
+
+impl Replacer for LruK {
+    fn pin(&mut self, buffer_id: BufferId) {
+        self.evictable.remove(&buffer_id);
+    }
+
+    fn unpin(&mut self, buffer_id: BufferId) {
+        self.now += 1;
+
+        let uses = self.history.entry(buffer_id).or_default();
+        uses.push_back(self.now);
+        if uses.len() > self.k {
+            uses.pop_front();
+        }
+
+        self.evictable.insert(buffer_id);
+    }
+
+    fn victim(&mut self) -> Option<BufferId> {
+        let buffer_id = self.evictable.iter().copied().min_by_key(|buffer_id| {
+            let uses = &self.history[buffer_id];
+            (uses.len() >= self.k, uses[0])
+        })?;
+
+        // the history goes with the page
+        self.evictable.remove(&buffer_id);
+        self.history.remove(&buffer_id);
+
+        Some(buffer_id)
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        self.history.retain(|&buffer_id, _| buffer_id < capacity);
+        self.evictable.retain(|&buffer_id| buffer_id < capacity);
+    }
+}
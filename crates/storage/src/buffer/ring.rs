@@ -0,0 +1,32 @@
+use super::BufferId;
+
+/// A small ring of buffers which a large sequential scan cycles through, instead of
+/// taking buffers from the whole pool. The pages the scan reads push out each other
+/// rather than the working set of other sessions.
+#[derive(Debug)]
+pub struct BufferRing {
+    slots: Vec<Option<BufferId>>,
+    next: usize,
+}
+
+impl BufferRing {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0);
+
+        Self {
+            slots: vec![None; size],
+            next: 0,
+        }
+    }
+
+    /// The buffer to be reused next, `None` until the ring is filled.
+    pub(super) fn current(&self) -> Option<BufferId> {
+        self.slots[self.next]
+    }
+
+    /// Puts the buffer in the current slot, and moves on to the next one.
+    pub(super) fn advance(&mut self, buffer_id: BufferId) {
+        self.slots[self.next] = Some(buffer_id);
+        self.next = (self.next + 1) % self.slots.len();
+    }
+}
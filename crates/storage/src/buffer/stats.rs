@@ -0,0 +1,113 @@
+use {
+    super::{BufferId, FileNode, PageTag},
+    common::pub_fields_struct,
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            RwLock,
+        },
+    },
+};
+
+pub_fields_struct! {
+    /// Counts of what the buffer pool has done, for the whole pool or for the pages of a file.
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+    struct BufferStats {
+        /// fetches of pages found in the pool
+        hits: u64,
+        /// fetches of pages read from the store
+        misses: u64,
+        /// pages pushed out of the pool to make room for other pages
+        evictions: u64,
+        /// dirty pages written to the store
+        writes: u64,
+    }
+
+    /// The state of a frame of the buffer pool at the time it is looked at.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    struct FrameInfo {
+        buffer_id: BufferId,
+        /// the page held in the frame, `None` if the frame is free
+        page_tag: Option<PageTag>,
+        is_dirty: bool,
+        pin_count: u32,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Event {
+    Hit,
+    Miss,
+    Eviction,
+    Write,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl Counters {
+    fn add(&self, event: Event) {
+        let counter = match event {
+            Event::Hit => &self.hits,
+            Event::Miss => &self.misses,
+            Event::Eviction => &self.evictions,
+            Event::Write => &self.writes,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> BufferStats {
+        BufferStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters of the buffer pool, kept for the whole pool and for every file the pool
+/// has held pages of.
+#[derive(Debug, Default)]
+pub(super) struct Stats {
+    total: Counters,
+    files: RwLock<HashMap<FileNode, Counters>>,
+}
+
+impl Stats {
+    pub fn record(&self, file_node: &FileNode, event: Event) {
+        self.total.add(event);
+
+        if let Some(counters) = self.files.read().unwrap().get(file_node) {
+            counters.add(event);
+            return;
+        }
+
+        self.files
+            .write()
+            .unwrap()
+            .entry(*file_node)
+            .or_default()
+            .add(event);
+    }
+
+    pub fn total(&self) -> BufferStats {
+        self.total.load()
+    }
+
+    pub fn files(&self) -> Vec<(FileNode, BufferStats)> {
+        self.files
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(file_node, counters)| (*file_node, counters.load()))
+            .collect()
+    }
+}
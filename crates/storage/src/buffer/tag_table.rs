@@ -0,0 +1,64 @@
+use {
+    super::{BufferId, PageTag},
+    std::{
+        collections::{hash_map::DefaultHasher, HashMap},
+        hash::{Hash, Hasher},
+        sync::{Mutex, MutexGuard},
+    },
+};
+
+const PARTITION_COUNT: usize = 16;
+
+pub(super) type Partition<'a> = MutexGuard<'a, HashMap<PageTag, BufferId>>;
+
+/// Maps page tags to the buffers holding the pages. The table is split into partitions
+/// latched separately, so that lookups of unrelated pages don't contend with each other.
+pub(super) struct TagTable {
+    partitions: Vec<Mutex<HashMap<PageTag, BufferId>>>,
+}
+
+impl TagTable {
+    pub fn new(capacity: usize) -> Self {
+        let partitions = (0..PARTITION_COUNT)
+            .map(|_| Mutex::new(HashMap::with_capacity(capacity / PARTITION_COUNT + 1)))
+            .collect();
+
+        Self { partitions }
+    }
+
+    fn partition_index(tag: &PageTag) -> usize {
+        let mut hasher = DefaultHasher::new();
+        tag.hash(&mut hasher);
+        hasher.finish() as usize % PARTITION_COUNT
+    }
+
+    pub fn lock(&self, tag: &PageTag) -> Partition<'_> {
+        self.partitions[Self::partition_index(tag)].lock().unwrap()
+    }
+
+    /// Locks the partitions of `tag` and `other_tag`. Partitions are always locked in the
+    /// same order, and the second partition is `None` if it is the same as the first one.
+    pub fn lock_pair(
+        &self,
+        tag: &PageTag,
+        other_tag: Option<&PageTag>,
+    ) -> (Partition<'_>, Option<Partition<'_>>) {
+        let index = Self::partition_index(tag);
+        let other_index = other_tag.map(Self::partition_index);
+
+        match other_index {
+            Some(other_index) if other_index < index => {
+                let other = self.partitions[other_index].lock().unwrap();
+                (self.partitions[index].lock().unwrap(), Some(other))
+            }
+            Some(other_index) if other_index > index => {
+                let partition = self.partitions[index].lock().unwrap();
+                (
+                    partition,
+                    Some(self.partitions[other_index].lock().unwrap()),
+                )
+            }
+            _ => (self.partitions[index].lock().unwrap(), None),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use {
+    super::{BufferManager, Result},
+    common::pub_fields_struct,
+    std::{
+        sync::{
+            mpsc::{self, RecvTimeoutError, Sender},
+            Arc,
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    },
+};
+
+pub_fields_struct! {
+    #[derive(Debug, Clone, Copy)]
+    struct BackgroundWriterConfig {
+        /// the time to sleep between rounds
+        interval: Duration,
+        /// the most pages written in a round
+        max_pages: usize,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct CheckpointerConfig {
+        /// the time between checkpoints
+        interval: Duration,
+    }
+}
+
+impl Default for BackgroundWriterConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            max_pages: 100,
+        }
+    }
+}
+
+impl Default for CheckpointerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A thread which runs a task periodically until it is stopped, or until the task fails.
+struct Worker {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl Worker {
+    fn spawn(
+        name: &str,
+        interval: Duration,
+        mut task: impl FnMut() -> Result<()> + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || loop {
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => task()?,
+                    _ => return Ok(()),
+                }
+            })
+            .unwrap();
+
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops the thread, returns the error the task failed with if any.
+    fn stop(&mut self) -> Result<()> {
+        drop(self.stop.take());
+
+        match self.handle.take() {
+            Some(handle) => handle.join().unwrap(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Trickles dirty pages to disk in the background, the thread is stopped once this is
+/// dropped.
+pub struct BackgroundWriter(Worker);
+
+impl BackgroundWriter {
+    pub fn start(manager: Arc<BufferManager>, config: BackgroundWriterConfig) -> Self {
+        Self(Worker::spawn("bgwriter", config.interval, move || {
+            manager.write_dirty_pages(config.max_pages).map(|_| ())
+        }))
+    }
+
+    /// Stops the thread, returns the error it has failed with if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.0.stop()
+    }
+}
+
+/// Takes checkpoints periodically in the background, the thread is stopped once this is
+/// dropped.
+pub struct Checkpointer(Worker);
+
+impl Checkpointer {
+    pub fn start(manager: Arc<BufferManager>, config: CheckpointerConfig) -> Self {
+        Self(Worker::spawn("checkpointer", config.interval, move || {
+            manager.checkpoint()
+        }))
+    }
+
+    /// Stops the thread, returns the error it has failed with if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.0.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            buffer::{error, FileNode, PageTag},
+            control::ControlFile,
+        },
+        snafu::ResultExt,
+        tempfile::tempdir,
+    };
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    fn dirty_pages(manager: &BufferManager, count: usize) -> Result<()> {
+        let xid = manager.wal().begin().unwrap();
+        for _ in 0..count {
+            let page_ref = manager.new_page(&FileNode::new(0, 1, 2))?;
+            let mut page = page_ref.write();
+            let before = page.to_vec();
+            page.fill(1);
+            manager.log_page(xid, wal::PageOp::LeafInsert, &mut page, &before)?;
+            page_ref.set_dirty();
+        }
+        manager.wal().commit(xid).unwrap();
+
+        Ok(())
+    }
+
+    fn wait_for(mut condition: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            thread::sleep(INTERVAL);
+        }
+
+        panic!("timed out");
+    }
+
+    #[test]
+    fn background_writer_cleans_pages() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+        let manager = Arc::new(BufferManager::new(16, dir.path().to_path_buf())?);
+
+        dirty_pages(&manager, 10)?;
+
+        let writer = BackgroundWriter::start(
+            manager.clone(),
+            BackgroundWriterConfig {
+                interval: INTERVAL,
+                max_pages: 3,
+            },
+        );
+        wait_for(|| manager.dirty_page_count() == 0);
+        writer.stop()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn checkpointer_records_checkpoints() -> Result<()> {
+        let dir = tempdir().unwrap();
+        ControlFile::default()
+            .write(dir.path())
+            .context(error::ControlSnafu)?;
+        let manager = Arc::new(BufferManager::new(16, dir.path().to_path_buf())?);
+
+        dirty_pages(&manager, 4)?;
+        let end = manager.wal().flushed_lsn();
+
+        let checkpointer =
+            Checkpointer::start(manager.clone(), CheckpointerConfig { interval: INTERVAL });
+        wait_for(|| {
+            let checkpoint = manager.wal().last_checkpoint().unwrap();
+            checkpoint.is_some_and(|checkpoint| checkpoint.redo >= end)
+        });
+        checkpointer.stop()?;
+
+        assert_eq!(manager.dirty_page_count(), 0);
+
+        // nothing to redo after the checkpoint
+        drop(manager);
+        let manager = BufferManager::new(16, dir.path().to_path_buf())?;
+        manager.recover()?;
+
+        let page = manager.fetch_page(PageTag {
+            file_node: FileNode::new(0, 1, 2),
+            page_num: 3,
+        })?;
+        assert!(page.read().iter().all(|&b| b == 1));
+
+        Ok(())
+    }
+}
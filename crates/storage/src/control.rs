@@ -0,0 +1,175 @@
+use {
+    crate::{is_valid_page_size, DEFAULT_PAGE_SIZE},
+    common::{durability, pub_fields_struct},
+    snafu::{prelude::*, Backtrace},
+    std::{
+        fs::{self, File},
+        io::{self, ErrorKind, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+const CONTROL_FILE_NAME: &str = "control";
+/// The control file is written here first, and renamed over the old one once durable.
+const TEMP_CONTROL_FILE_NAME: &str = "control.tmp";
+
+const MAGIC: u32 = 0x0DB0_C0F1;
+const VERSION: u32 = 1;
+
+const CHECKSUMS_FLAG: u32 = 1;
+
+/// | magic:32 | version:32 | flags:32 | page size:32 | crc:32 |
+const CONTROL_FILE_SIZE: usize = 20;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    Io {
+        backtrace: Backtrace,
+        source: io::Error,
+    },
+
+    #[snafu(display("no control file in {}, it is not an initialized data directory", data_dir.display()))]
+    MissingControlFile {
+        backtrace: Backtrace,
+        data_dir: PathBuf,
+    },
+
+    #[snafu(display("control file is corrupted or of an unknown version"))]
+    InvalidControlFile { backtrace: Backtrace },
+
+    #[snafu(display("invalid page size {}", page_size))]
+    InvalidPageSize {
+        backtrace: Backtrace,
+        page_size: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub_fields_struct! {
+    /// The settings of a data directory, they are chosen when the directory is initialized
+    /// and never change afterwards.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct ControlFile {
+        /// whether pages are checksummed when written, and verified when read
+        checksums: bool,
+        page_size: usize,
+    }
+}
+
+impl Default for ControlFile {
+    fn default() -> Self {
+        Self {
+            checksums: false,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl ControlFile {
+    /// Reads the control file of the data directory, `None` if there is none.
+    pub fn read(data_dir: &Path) -> Result<Option<Self>> {
+        let bytes = match fs::read(data_dir.join(CONTROL_FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context(IoSnafu),
+        };
+
+        ensure!(bytes.len() == CONTROL_FILE_SIZE, InvalidControlFileSnafu);
+
+        let field = |i: usize| u32::from_le_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+        let (magic, version, flags, page_size, crc) =
+            (field(0), field(1), field(2), field(3) as usize, field(4));
+
+        ensure!(
+            magic == MAGIC
+                && version == VERSION
+                && crc == crc32fast::hash(&bytes[..16])
+                && is_valid_page_size(page_size),
+            InvalidControlFileSnafu
+        );
+
+        Ok(Some(Self {
+            checksums: flags & CHECKSUMS_FLAG != 0,
+            page_size,
+        }))
+    }
+
+    /// Writes the control file of the data directory, replacing the old one at once so that
+    /// a crash leaves either of them in place.
+    pub fn write(&self, data_dir: &Path) -> Result<()> {
+        ensure!(
+            is_valid_page_size(self.page_size),
+            InvalidPageSizeSnafu {
+                page_size: self.page_size
+            }
+        );
+
+        let mut flags = 0;
+        if self.checksums {
+            flags |= CHECKSUMS_FLAG;
+        }
+
+        let mut bytes = Vec::with_capacity(CONTROL_FILE_SIZE);
+        for n in [MAGIC, VERSION, flags, self.page_size as u32] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+
+        durability::create_dirs_synced(data_dir).context(IoSnafu)?;
+        let temp_path = data_dir.join(TEMP_CONTROL_FILE_NAME);
+        let mut file = File::create(&temp_path).context(IoSnafu)?;
+        file.write_all(&bytes).context(IoSnafu)?;
+        file.sync_all().context(IoSnafu)?;
+
+        fs::rename(&temp_path, data_dir.join(CONTROL_FILE_NAME)).context(IoSnafu)?;
+        durability::sync_dir(data_dir).context(IoSnafu)
+    }
+
+    /// Reads the control file of an initialized data directory, fails if there is none.
+    /// The control file is only written when the directory is initialized.
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        Self::read(data_dir)?.context(MissingControlFileSnafu { data_dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::tempdir};
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let dir = tempdir().unwrap();
+        assert_eq!(ControlFile::read(dir.path())?, None);
+        assert!(matches!(
+            ControlFile::open(dir.path()),
+            Err(Error::MissingControlFile { .. })
+        ));
+
+        let control = ControlFile {
+            checksums: true,
+            page_size: 1 << 14,
+        };
+        control.write(dir.path())?;
+        assert_eq!(ControlFile::read(dir.path())?, Some(control));
+        assert_eq!(ControlFile::open(dir.path())?, control);
+
+        // rewritten in place of the old one, leaving no temporary file
+        let control = ControlFile {
+            checksums: false,
+            page_size: 1 << 12,
+        };
+        control.write(dir.path())?;
+        assert_eq!(ControlFile::open(dir.path())?, control);
+        assert!(!dir.path().join(TEMP_CONTROL_FILE_NAME).exists());
+
+        fs::write(dir.path().join(CONTROL_FILE_NAME), [0; CONTROL_FILE_SIZE]).unwrap();
+        assert!(matches!(
+            ControlFile::read(dir.path()),
+            Err(Error::InvalidControlFile { .. })
+        ));
+
+        Ok(())
+    }
+}
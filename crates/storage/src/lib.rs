@@ -1,8 +1,30 @@
 #![feature(let_chains)]
 
+mod aligned;
 pub mod buffer;
-mod manager;
+pub mod control;
+pub mod store;
+pub mod temp;
 
 pub type PageNum = u32;
 
 pub const DEFAULT_PAGE_SIZE: usize = 1 << 12;
+pub const MIN_PAGE_SIZE: usize = 1 << 12;
+/// Offsets within a page are of 15 bits.
+pub const MAX_PAGE_SIZE: usize = 1 << 15;
+
+/// Buffers read and written with direct I/O are aligned to this, which is a multiple of
+/// the block size of common devices.
+pub const IO_ALIGNMENT: usize = 1 << 12;
+
+/// Relation files are split into segments of this size, named `N`, `N.1`, `N.2` and so on.
+pub const SEGMENT_SIZE: usize = 1 << 30;
+
+/// Page sizes are powers of two between `MIN_PAGE_SIZE` and `MAX_PAGE_SIZE`.
+pub fn is_valid_page_size(page_size: usize) -> bool {
+    page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
+}
+
+/// Every page starts with a header maintained by the buffer manager.
+/// | lsn:64 | checksum:32 | unused:32 |
+pub const PAGE_HEADER_SIZE: usize = 16;
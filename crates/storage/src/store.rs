@@ -0,0 +1,66 @@
+mod faulty;
+mod file;
+mod memory;
+
+pub use {
+    self::{
+        faulty::{Fault, FaultyStore},
+        file::{FileOptions, FileStore, IoMode},
+        memory::MemoryStore,
+    },
+    common::durability::SyncPolicy,
+};
+use {
+    crate::PageNum,
+    std::{io::Result, path::Path, sync::Arc},
+};
+
+/// Where the pages of relations are kept. Relations are named by their paths relative to
+/// the data directory, and pages are read and written whole.
+pub trait PageStore: Send + Sync {
+    /// Fails if the page has never been written.
+    fn read(&self, file_path: &Path, page_num: PageNum, data: &mut [u8]) -> Result<()>;
+
+    /// Writes the page, the relation is extended if the page is past its end.
+    fn write(&self, file_path: &Path, page_num: PageNum, data: &[u8]) -> Result<()>;
+
+    /// Makes every write done so far durable.
+    fn sync_all(&self) -> Result<()>;
+
+    /// Returns the number of pages in the relation, the relation is created if it
+    /// doesn't exist.
+    fn page_count(&self, file_path: &Path) -> Result<usize>;
+
+    /// Makes `dir` lead to `location`, so that the relations under `dir` are kept there.
+    fn create_tablespace(&self, dir: &Path, location: &Path) -> Result<()>;
+
+    /// Undoes `create_tablespace`, fails if any relation is left under `dir`.
+    fn drop_tablespace(&self, dir: &Path) -> Result<()>;
+}
+
+/// A store shared with the code testing it.
+impl<S: PageStore + ?Sized> PageStore for Arc<S> {
+    fn read(&self, file_path: &Path, page_num: PageNum, data: &mut [u8]) -> Result<()> {
+        (**self).read(file_path, page_num, data)
+    }
+
+    fn write(&self, file_path: &Path, page_num: PageNum, data: &[u8]) -> Result<()> {
+        (**self).write(file_path, page_num, data)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        (**self).sync_all()
+    }
+
+    fn page_count(&self, file_path: &Path) -> Result<usize> {
+        (**self).page_count(file_path)
+    }
+
+    fn create_tablespace(&self, dir: &Path, location: &Path) -> Result<()> {
+        (**self).create_tablespace(dir, location)
+    }
+
+    fn drop_tablespace(&self, dir: &Path) -> Result<()> {
+        (**self).drop_tablespace(dir)
+    }
+}
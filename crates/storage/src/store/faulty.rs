@@ -0,0 +1,212 @@
+use {
+    super::PageStore,
+    crate::PageNum,
+    std::{
+        collections::HashMap,
+        io::{Error, ErrorKind, Result},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// The error of failed accesses, as returned by a failing disk.
+const EIO: i32 = 5;
+
+/// A fault injected into a write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// the write fails, and the page is left as it was
+    Fail,
+    /// only the first bytes of the page are written, then the write fails
+    ShortWrite(usize),
+    /// the write succeeds, but only reaches the store at the next sync, after the writes
+    /// issued meanwhile; it is lost if the store crashes before then
+    Delay,
+}
+
+/// Wraps a store and injects faults into its writes, for testing how the database copes
+/// with I/O errors and crashes. Writes are numbered from 0 in the order they are issued,
+/// and the schedule maps the numbers of writes to the faults injected into them.
+pub struct FaultyStore<S> {
+    inner: S,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    schedule: HashMap<usize, Fault>,
+    /// the number of writes issued so far
+    writes: usize,
+    /// pages written so far, in the order the writes are issued
+    written: Vec<(PathBuf, PageNum)>,
+    /// writes held back until the next sync
+    delayed: Vec<(PathBuf, PageNum, Vec<u8>)>,
+    crashed: bool,
+}
+
+impl<S: PageStore> FaultyStore<S> {
+    pub fn new(inner: S, schedule: impl IntoIterator<Item = (usize, Fault)>) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State {
+                schedule: schedule.into_iter().collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Returns the pages written so far, in the order the writes are issued. The writes
+    /// of a run without faults tell which write to inject a fault into.
+    pub fn written(&self) -> Vec<(PathBuf, PageNum)> {
+        self.state.lock().unwrap().written.clone()
+    }
+
+    /// Simulates a crash, the writes held back are lost and every later access fails.
+    pub fn crash(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.delayed.clear();
+        state.crashed = true;
+    }
+
+    fn check_crashed(state: &State) -> Result<()> {
+        match state.crashed {
+            true => Err(Error::from_raw_os_error(EIO)),
+            false => Ok(()),
+        }
+    }
+}
+
+impl<S: PageStore> PageStore for FaultyStore<S> {
+    fn read(&self, file_path: &Path, page_num: PageNum, data: &mut [u8]) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        Self::check_crashed(&state)?;
+
+        // a write held back is already seen by reads, like one sitting in the page cache
+        let delayed = state
+            .delayed
+            .iter()
+            .rev()
+            .find(|(path, num, _)| path == file_path && *num == page_num);
+
+        match delayed {
+            Some((_, _, page)) => {
+                data.copy_from_slice(page);
+                Ok(())
+            }
+            None => self.inner.read(file_path, page_num, data),
+        }
+    }
+
+    fn write(&self, file_path: &Path, page_num: PageNum, data: &[u8]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        Self::check_crashed(&state)?;
+
+        let write_num = state.writes;
+        let fault = state.schedule.remove(&write_num);
+        state.writes += 1;
+        state.written.push((file_path.to_path_buf(), page_num));
+
+        match fault {
+            None => self.inner.write(file_path, page_num, data),
+            Some(Fault::Fail) => Err(Error::from_raw_os_error(EIO)),
+            Some(Fault::ShortWrite(len)) => {
+                // the rest of the page is left as it was
+                let mut page = vec![0; data.len()];
+                if page_num < self.inner.page_count(file_path)? as PageNum {
+                    self.inner.read(file_path, page_num, &mut page)?;
+                }
+                let len = len.min(data.len());
+                page[..len].copy_from_slice(&data[..len]);
+                self.inner.write(file_path, page_num, &page)?;
+
+                Err(Error::new(ErrorKind::WriteZero, "injected short write"))
+            }
+            Some(Fault::Delay) => {
+                state
+                    .delayed
+                    .push((file_path.to_path_buf(), page_num, data.to_vec()));
+                Ok(())
+            }
+        }
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        Self::check_crashed(&state)?;
+
+        for (path, page_num, data) in state.delayed.drain(..) {
+            self.inner.write(&path, page_num, &data)?;
+        }
+
+        self.inner.sync_all()
+    }
+
+    fn page_count(&self, file_path: &Path) -> Result<usize> {
+        let state = self.state.lock().unwrap();
+        Self::check_crashed(&state)?;
+
+        let count = self.inner.page_count(file_path)?;
+        let delayed = state
+            .delayed
+            .iter()
+            .filter(|(path, ..)| path == file_path)
+            .map(|&(_, page_num, _)| page_num as usize + 1);
+
+        Ok(delayed.fold(count, usize::max))
+    }
+
+    fn create_tablespace(&self, dir: &Path, location: &Path) -> Result<()> {
+        Self::check_crashed(&self.state.lock().unwrap())?;
+        self.inner.create_tablespace(dir, location)
+    }
+
+    fn drop_tablespace(&self, dir: &Path) -> Result<()> {
+        Self::check_crashed(&self.state.lock().unwrap())?;
+        self.inner.drop_tablespace(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{store::MemoryStore, DEFAULT_PAGE_SIZE},
+    };
+
+    #[test]
+    fn scheduled_faults() -> Result<()> {
+        let file_name = PathBuf::from("1");
+        let store = FaultyStore::new(
+            MemoryStore::new(),
+            [
+                (1, Fault::Fail),
+                (2, Fault::ShortWrite(100)),
+                (4, Fault::Delay),
+            ],
+        );
+        let mut data = [0; DEFAULT_PAGE_SIZE];
+
+        store.write(&file_name, 0, &[1; DEFAULT_PAGE_SIZE])?;
+        assert!(store.write(&file_name, 0, &[2; DEFAULT_PAGE_SIZE]).is_err());
+        assert!(store.write(&file_name, 0, &[3; DEFAULT_PAGE_SIZE]).is_err());
+
+        store.read(&file_name, 0, &mut data)?;
+        assert!(data[..100].iter().all(|&b| b == 3));
+        assert!(data[100..].iter().all(|&b| b == 1));
+
+        store.write(&file_name, 1, &[4; DEFAULT_PAGE_SIZE])?;
+        store.write(&file_name, 2, &[5; DEFAULT_PAGE_SIZE])?;
+        assert_eq!(store.page_count(&file_name)?, 3);
+
+        // the write held back is seen by reads, but is lost by a crash
+        store.read(&file_name, 2, &mut data)?;
+        assert_eq!(data, [5; DEFAULT_PAGE_SIZE]);
+
+        store.crash();
+        assert!(store.read(&file_name, 0, &mut data).is_err());
+        assert_eq!(store.inner.page_count(&file_name)?, 2);
+        assert_eq!(store.written().len(), 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use {
+    super::PageStore,
+    crate::{aligned::AlignedBuffer, PageNum, SEGMENT_SIZE},
+    common::{
+        durability::{self, SyncPolicy},
+        pub_fields_struct,
+    },
+    std::{
+        collections::HashMap,
+        fs::{self, File, OpenOptions},
+        io::{Error, ErrorKind, Result},
+        os::unix::{
+            fs::{symlink, FileExt, OpenOptionsExt},
+            prelude::MetadataExt,
+        },
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    },
+};
+
+const O_DIRECT: i32 = 0o0040000;
+const EINVAL: i32 = 22;
+
+/// How the files of relations are read and written. Writes are made durable by
+/// `fdatasync` in every mode, when the sync policy asks for it.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum IoMode {
+    /// direct I/O, or buffered I/O on filesystems which reject direct I/O, such as tmpfs
+    #[default]
+    Auto,
+    /// direct I/O only, opening a file fails on filesystems which reject it
+    Direct,
+    /// through the page cache of the kernel
+    Buffered,
+}
+
+pub_fields_struct! {
+    /// How a `FileStore` and the log next to it use the filesystem.
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+    struct FileOptions {
+        io_mode: IoMode,
+        sync_policy: SyncPolicy,
+    }
+}
+
+/// Keeps relations in files under the data directory, every segment but the last one of
+/// a relation is full.
+pub struct FileStore {
+    data_dir: PathBuf,
+    page_size: usize,
+    pages_per_segment: PageNum,
+    io_mode: IoMode,
+    sync_policy: SyncPolicy,
+    opened_files: Mutex<HashMap<PathBuf, Arc<OpenedFile>>>,
+}
+
+struct OpenedFile {
+    file: File,
+    is_direct: bool,
+}
+
+impl FileStore {
+    pub fn new(data_dir: PathBuf, page_size: usize) -> Self {
+        Self::with_segment_size(data_dir, page_size, SEGMENT_SIZE)
+    }
+
+    fn with_segment_size(data_dir: PathBuf, page_size: usize, segment_size: usize) -> Self {
+        // both are powers of two, so that no page straddles two segments
+        debug_assert!(segment_size.is_power_of_two() && segment_size >= page_size);
+
+        Self {
+            data_dir,
+            page_size,
+            pages_per_segment: (segment_size / page_size) as PageNum,
+            io_mode: IoMode::default(),
+            sync_policy: SyncPolicy::default(),
+            opened_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_io_mode(mut self, io_mode: IoMode) -> Self {
+        self.io_mode = io_mode;
+        self
+    }
+
+    pub fn with_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    fn open_file(&self, path: &Path, create: bool) -> Result<Arc<OpenedFile>> {
+        let path = self.data_dir.join(path);
+        let mut files = self.opened_files.lock().unwrap();
+
+        if let Some(file) = files.get(&path) {
+            return Ok(file.clone());
+        }
+
+        // a file created is only durable once the directories leading to it are synced
+        let is_new = create && !path.exists();
+        if is_new && let Some(parent) = path.parent() {
+            self.create_dirs(parent)?;
+        }
+
+        let open = |is_direct| {
+            OpenOptions::new()
+                .create(create)
+                .read(true)
+                .write(true)
+                .custom_flags(if is_direct { O_DIRECT } else { 0 })
+                .open(&path)
+                .map(|file| OpenedFile { file, is_direct })
+        };
+
+        let file = match self.io_mode {
+            IoMode::Direct => open(true)?,
+            IoMode::Buffered => open(false)?,
+            IoMode::Auto => match open(true) {
+                Err(e) if e.raw_os_error() == Some(EINVAL) => open(false)?,
+                result => result?,
+            },
+        };
+
+        if is_new && let Some(parent) = path.parent() {
+            self.sync_dir(parent)?;
+        }
+
+        Ok(files.entry(path).or_insert(Arc::new(file)).clone())
+    }
+
+    fn create_dirs(&self, path: &Path) -> Result<()> {
+        match self.sync_policy.syncs() {
+            true => durability::create_dirs_synced(path),
+            false => fs::create_dir_all(path),
+        }
+    }
+
+    fn sync_dir(&self, path: &Path) -> Result<()> {
+        match self.sync_policy.syncs() {
+            true => durability::sync_dir(path),
+            false => Ok(()),
+        }
+    }
+
+    fn segment_path(file_path: &Path, segment: PageNum) -> PathBuf {
+        match segment {
+            0 => file_path.to_path_buf(),
+            _ => {
+                let mut path = file_path.as_os_str().to_owned();
+                path.push(format!(".{}", segment));
+                path.into()
+            }
+        }
+    }
+
+    /// Returns the path of the segment holding the page, and the offset of the page in it.
+    fn locate(&self, file_path: &Path, page_num: PageNum) -> (PathBuf, u64) {
+        let segment = page_num / self.pages_per_segment;
+        let offset = (page_num % self.pages_per_segment) as u64 * self.page_size as u64;
+
+        (Self::segment_path(file_path, segment), offset)
+    }
+}
+
+impl PageStore for FileStore {
+    fn read(&self, file_path: &Path, page_num: PageNum, data: &mut [u8]) -> Result<()> {
+        let (path, offset) = self.locate(file_path, page_num);
+
+        let opened = self.open_file(&path, false)?;
+        if !opened.is_direct || AlignedBuffer::is_aligned(data) {
+            return opened.file.read_exact_at(data, offset);
+        }
+
+        // direct I/O goes through an aligned copy of a buffer not allocated for it
+        let mut aligned = AlignedBuffer::new(data.len());
+        opened.file.read_exact_at(&mut aligned, offset)?;
+        data.copy_from_slice(&aligned);
+
+        Ok(())
+    }
+
+    // a new segment is created by writing its first page
+    fn write(&self, file_path: &Path, page_num: PageNum, data: &[u8]) -> Result<()> {
+        let (path, offset) = self.locate(file_path, page_num);
+
+        let opened = self.open_file(&path, offset == 0)?;
+        if !opened.is_direct || AlignedBuffer::is_aligned(data) {
+            opened.file.write_all_at(data, offset)?;
+        } else {
+            opened
+                .file
+                .write_all_at(&AlignedBuffer::from_slice(data), offset)?;
+        }
+
+        match self.sync_policy.syncs_every_write() {
+            true => opened.file.sync_data(),
+            false => Ok(()),
+        }
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        if !self.sync_policy.syncs() {
+            return Ok(());
+        }
+
+        let files = self.opened_files.lock().unwrap().clone();
+
+        files
+            .values()
+            .try_for_each(|opened| opened.file.sync_data())
+    }
+
+    fn page_count(&self, file_path: &Path) -> Result<usize> {
+        let mut count = 0;
+
+        for segment in 0.. {
+            let path = Self::segment_path(file_path, segment);
+            let file = match self.open_file(&path, segment == 0) {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound => break,
+                Err(e) => return Err(e),
+            };
+
+            let pages = file.file.metadata()?.size() as usize / self.page_size;
+            count += pages;
+
+            if pages < self.pages_per_segment as usize {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Links `dir` to `location`, which is created if it doesn't exist and must be empty.
+    fn create_tablespace(&self, dir: &Path, location: &Path) -> Result<()> {
+        if !location.is_absolute() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the location of a tablespace must be an absolute path",
+            ));
+        }
+
+        self.create_dirs(location)?;
+        if fs::read_dir(location)?.next().is_some() {
+            return Err(Error::from(ErrorKind::DirectoryNotEmpty));
+        }
+
+        let link = self.data_dir.join(dir);
+        let parent = link.parent().unwrap();
+        self.create_dirs(parent)?;
+
+        // a link left by a tablespace whose creation has never committed
+        match fs::remove_file(&link) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            result => result?,
+        }
+        symlink(location, &link)?;
+
+        self.sync_dir(parent)
+    }
+
+    /// Removes the link, and the empty directories of databases at its location.
+    fn drop_tablespace(&self, dir: &Path) -> Result<()> {
+        let link = self.data_dir.join(dir);
+
+        for entry in fs::read_dir(&link)? {
+            fs::remove_dir(entry?.path())?;
+        }
+        fs::remove_file(&link)?;
+
+        self.sync_dir(link.parent().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::DEFAULT_PAGE_SIZE, tempfile::tempdir};
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let dir = tempdir()?;
+        let file_name = PathBuf::from("1");
+
+        let store = FileStore::new(dir.path().to_path_buf(), DEFAULT_PAGE_SIZE);
+
+        let data_w = [123; DEFAULT_PAGE_SIZE];
+        let mut data_r = [0; DEFAULT_PAGE_SIZE];
+
+        store.write(&file_name, 0, &data_w)?;
+        store.read(&file_name, 0, &mut data_r)?;
+
+        assert_eq!(data_w, data_r);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tablespaces() -> Result<()> {
+        let dir = tempdir()?;
+        let location = tempdir()?;
+        let store = FileStore::new(dir.path().to_path_buf(), DEFAULT_PAGE_SIZE);
+
+        let space_dir = Path::new("tablespace/2");
+        let file_name = space_dir.join("1/3");
+        assert!(store
+            .create_tablespace(space_dir, Path::new("relative"))
+            .is_err());
+        store.create_tablespace(space_dir, location.path())?;
+
+        // the relations of the tablespace are kept at its location
+        store.write(&file_name, 0, &[1; DEFAULT_PAGE_SIZE])?;
+        assert!(location.path().join("1/3").exists());
+
+        // a location in use by another tablespace is not empty
+        assert!(store
+            .create_tablespace(Path::new("tablespace/3"), location.path())
+            .is_err());
+        assert!(store.drop_tablespace(space_dir).is_err());
+
+        fs::remove_file(location.path().join("1/3"))?;
+        store.drop_tablespace(space_dir)?;
+        assert!(!dir.path().join(space_dir).exists());
+        assert!(fs::read_dir(location.path())?.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_modes() -> Result<()> {
+        let dir = tempdir()?;
+
+        for (i, io_mode) in [IoMode::Auto, IoMode::Direct, IoMode::Buffered]
+            .into_iter()
+            .enumerate()
+        {
+            let file_name = PathBuf::from(i.to_string());
+            let store =
+                FileStore::new(dir.path().to_path_buf(), DEFAULT_PAGE_SIZE).with_io_mode(io_mode);
+
+            // a buffer which is not aligned, and an aligned one
+            let data_w = vec![i as u8 + 1; DEFAULT_PAGE_SIZE + 1];
+            match store.write(&file_name, 0, &data_w[1..]) {
+                Err(e) if io_mode == IoMode::Direct && e.raw_os_error() == Some(EINVAL) => continue,
+                result => result?,
+            }
+            store.write(&file_name, 1, &AlignedBuffer::from_slice(&data_w[1..]))?;
+
+            let mut data_r = vec![0; DEFAULT_PAGE_SIZE + 1];
+            let mut aligned = AlignedBuffer::new(DEFAULT_PAGE_SIZE);
+            store.read(&file_name, 0, &mut data_r[1..])?;
+            store.read(&file_name, 1, &mut aligned)?;
+            store.sync_all()?;
+
+            assert_eq!(data_r[1..], data_w[1..], "{:?}", io_mode);
+            assert_eq!(aligned[..], data_w[1..], "{:?}", io_mode);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sync_policies() -> Result<()> {
+        let dir = tempdir()?;
+
+        for sync_policy in [SyncPolicy::Always, SyncPolicy::Commit, SyncPolicy::Never] {
+            // relations are created with the directories leading to them
+            let file_name = PathBuf::from(format!("base/{:?}/1", sync_policy));
+            let store = FileStore::new(dir.path().to_path_buf(), DEFAULT_PAGE_SIZE)
+                .with_sync_policy(sync_policy);
+
+            let data_w = AlignedBuffer::from_slice(&[7; DEFAULT_PAGE_SIZE]);
+            let mut data_r = AlignedBuffer::new(DEFAULT_PAGE_SIZE);
+            store.write(&file_name, 0, &data_w)?;
+            store.sync_all()?;
+            store.read(&file_name, 0, &mut data_r)?;
+
+            assert_eq!(data_r[..], data_w[..], "{:?}", sync_policy);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn pages_span_segments() -> Result<()> {
+        let dir = tempdir()?;
+        let file_name = PathBuf::from("1");
+
+        let store = FileStore::with_segment_size(
+            dir.path().to_path_buf(),
+            DEFAULT_PAGE_SIZE,
+            4 * DEFAULT_PAGE_SIZE,
+        );
+
+        assert_eq!(store.page_count(&file_name)?, 0);
+
+        for page_num in 0..10 {
+            store.write(&file_name, page_num, &[page_num as u8; DEFAULT_PAGE_SIZE])?;
+            assert_eq!(store.page_count(&file_name)?, page_num as usize + 1);
+        }
+
+        for (segment, pages) in [("1", 4), ("1.1", 4), ("1.2", 2)] {
+            let size = fs::metadata(dir.path().join(segment))?.len();
+            assert_eq!(size, (pages * DEFAULT_PAGE_SIZE) as u64);
+        }
+
+        let mut data = [0; DEFAULT_PAGE_SIZE];
+        for page_num in 0..10 {
+            store.read(&file_name, page_num, &mut data)?;
+            assert_eq!(data, [page_num as u8; DEFAULT_PAGE_SIZE]);
+        }
+
+        assert!(store.read(&file_name, 12, &mut data).is_err());
+
+        Ok(())
+    }
+}
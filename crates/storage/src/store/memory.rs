@@ -0,0 +1,101 @@
+use {
+    super::PageStore,
+    crate::PageNum,
+    std::{
+        collections::HashMap,
+        io::{Error, ErrorKind, Result},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// Keeps relations in memory, for databases which need no data directory.
+/// Nothing survives the store being dropped.
+#[derive(Default)]
+pub struct MemoryStore {
+    relations: Mutex<HashMap<PathBuf, Vec<Box<[u8]>>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PageStore for MemoryStore {
+    fn read(&self, file_path: &Path, page_num: PageNum, data: &mut [u8]) -> Result<()> {
+        let relations = self.relations.lock().unwrap();
+        let page = relations
+            .get(file_path)
+            .and_then(|pages| pages.get(page_num as usize))
+            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+
+        data.copy_from_slice(page);
+
+        Ok(())
+    }
+
+    fn write(&self, file_path: &Path, page_num: PageNum, data: &[u8]) -> Result<()> {
+        let mut relations = self.relations.lock().unwrap();
+        let pages = relations.entry(file_path.to_path_buf()).or_default();
+
+        // pages skipped over read as zeroes, like holes in a file
+        let page_num = page_num as usize;
+        if pages.len() <= page_num {
+            pages.resize(page_num + 1, vec![0; data.len()].into());
+        }
+        pages[page_num] = data.into();
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn page_count(&self, file_path: &Path) -> Result<usize> {
+        let mut relations = self.relations.lock().unwrap();
+
+        Ok(relations.entry(file_path.to_path_buf()).or_default().len())
+    }
+
+    // relations are only named by their paths, there is no directory to link
+    fn create_tablespace(&self, _dir: &Path, _location: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn drop_tablespace(&self, dir: &Path) -> Result<()> {
+        let relations = self.relations.lock().unwrap();
+
+        match relations.keys().any(|path| path.starts_with(dir)) {
+            true => Err(Error::from(ErrorKind::DirectoryNotEmpty)),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::DEFAULT_PAGE_SIZE};
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let store = MemoryStore::new();
+        let file_name = PathBuf::from("1");
+
+        assert_eq!(store.page_count(&file_name)?, 0);
+
+        let mut data = [0; DEFAULT_PAGE_SIZE];
+        assert!(store.read(&file_name, 0, &mut data).is_err());
+
+        store.write(&file_name, 2, &[2; DEFAULT_PAGE_SIZE])?;
+        assert_eq!(store.page_count(&file_name)?, 3);
+
+        store.read(&file_name, 1, &mut data)?;
+        assert_eq!(data, [0; DEFAULT_PAGE_SIZE]);
+        store.read(&file_name, 2, &mut data)?;
+        assert_eq!(data, [2; DEFAULT_PAGE_SIZE]);
+
+        Ok(())
+    }
+}
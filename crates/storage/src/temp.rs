@@ -0,0 +1,286 @@
+use {
+    crate::PageNum,
+    snafu::{prelude::*, Backtrace},
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, ErrorKind},
+        os::unix::fs::FileExt,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// The directory of temporary files under the data directory, emptied at startup.
+pub const TEMP_DIR: &str = "tmp";
+
+/// The bytes a query may take in temporary files, unless another quota is set.
+pub const DEFAULT_TEMP_QUOTA: u64 = 1 << 30;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    Io {
+        backtrace: Backtrace,
+        source: io::Error,
+    },
+
+    #[snafu(display("temporary files of the query exceed the quota of {} bytes", quota))]
+    QuotaExceeded { backtrace: Backtrace, quota: u64 },
+
+    #[snafu(display("page {} is beyond the end of the temporary file", page_num))]
+    PageOutOfRange {
+        backtrace: Backtrace,
+        page_num: PageNum,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Gives out scratch files to queries which run over memory, such as sorts and hash
+/// joins. The files are never shared, so they are read and written around the buffer
+/// pool, and never synced, as nothing in them outlives the server.
+pub struct TempFileManager {
+    dir: PathBuf,
+    page_size: usize,
+    /// the bytes every space may take
+    quota: u64,
+    next_space_id: AtomicU64,
+}
+
+impl TempFileManager {
+    /// Opens the directory of temporary files, removing the files left by a server which
+    /// has not been shut down cleanly.
+    pub fn open(data_dir: &Path, page_size: usize) -> Result<Self> {
+        let dir = data_dir.join(TEMP_DIR);
+
+        match fs::remove_dir_all(&dir) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            result => result.context(IoSnafu)?,
+        }
+        fs::create_dir_all(&dir).context(IoSnafu)?;
+
+        Ok(Self {
+            dir,
+            page_size,
+            quota: DEFAULT_TEMP_QUOTA,
+            next_space_id: AtomicU64::new(0),
+        })
+    }
+
+    pub fn with_quota(mut self, quota: u64) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Makes a space for the files of a query, which are removed along with the space.
+    pub fn space(&self) -> Result<TempSpace> {
+        let id = self.next_space_id.fetch_add(1, Ordering::SeqCst);
+        let dir = self.dir.join(id.to_string());
+        fs::create_dir(&dir).context(IoSnafu)?;
+
+        Ok(TempSpace {
+            dir,
+            page_size: self.page_size,
+            quota: self.quota,
+            used: AtomicU64::new(0),
+            next_file_id: AtomicU64::new(0),
+        })
+    }
+}
+
+/// The temporary files of a query, which together may not take more than the quota.
+pub struct TempSpace {
+    dir: PathBuf,
+    page_size: usize,
+    quota: u64,
+    /// the bytes taken by the files alive
+    used: AtomicU64,
+    next_file_id: AtomicU64,
+}
+
+impl TempSpace {
+    pub fn create_file(&self) -> Result<TempFile<'_>> {
+        let id = self.next_file_id.fetch_add(1, Ordering::SeqCst);
+        let path = self.dir.join(id.to_string());
+
+        let file = OpenOptions::new()
+            .create_new(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .context(IoSnafu)?;
+
+        Ok(TempFile {
+            space: self,
+            path,
+            file,
+            page_count: 0,
+        })
+    }
+
+    /// The bytes taken by the files of the space.
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    fn reserve(&self, bytes: u64) -> Result<()> {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                (used + bytes <= self.quota).then_some(used + bytes)
+            })
+            .map(|_| ())
+            .map_err(|_| QuotaExceededSnafu { quota: self.quota }.build())
+    }
+
+    fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+impl Drop for TempSpace {
+    fn drop(&mut self) {
+        // whatever is left is removed at the next startup
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// A scratch file of pages, which grows as pages are written past its end.
+pub struct TempFile<'a> {
+    space: &'a TempSpace,
+    path: PathBuf,
+    file: File,
+    page_count: PageNum,
+}
+
+impl TempFile<'_> {
+    pub fn page_count(&self) -> PageNum {
+        self.page_count
+    }
+
+    pub fn read_page(&self, page_num: PageNum, data: &mut [u8]) -> Result<()> {
+        debug_assert_eq!(data.len(), self.space.page_size);
+        ensure!(page_num < self.page_count, PageOutOfRangeSnafu { page_num });
+
+        self.file
+            .read_exact_at(data, self.offset(page_num))
+            .context(IoSnafu)
+    }
+
+    /// Writes the page, which is either in the file or right after its end.
+    pub fn write_page(&mut self, page_num: PageNum, data: &[u8]) -> Result<()> {
+        debug_assert_eq!(data.len(), self.space.page_size);
+        ensure!(
+            page_num <= self.page_count,
+            PageOutOfRangeSnafu { page_num }
+        );
+
+        let is_new = page_num == self.page_count;
+        if is_new {
+            self.space.reserve(self.space.page_size as u64)?;
+        }
+
+        if let Err(e) = self.file.write_all_at(data, self.offset(page_num)) {
+            if is_new {
+                self.space.release(self.space.page_size as u64);
+            }
+            return Err(e).context(IoSnafu);
+        }
+
+        if is_new {
+            self.page_count += 1;
+        }
+
+        Ok(())
+    }
+
+    fn offset(&self, page_num: PageNum) -> u64 {
+        page_num as u64 * self.space.page_size as u64
+    }
+}
+
+impl Drop for TempFile<'_> {
+    fn drop(&mut self) {
+        self.space
+            .release(self.page_count as u64 * self.space.page_size as u64);
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::DEFAULT_PAGE_SIZE, tempfile::tempdir};
+
+    #[test]
+    fn files_are_removed() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        // left by a crash
+        let leftover = dir.path().join(TEMP_DIR).join("0");
+        fs::create_dir_all(&leftover).unwrap();
+        fs::write(leftover.join("0"), [0; DEFAULT_PAGE_SIZE]).unwrap();
+
+        let manager = TempFileManager::open(dir.path(), DEFAULT_PAGE_SIZE)?;
+        assert!(!leftover.exists());
+
+        let space = manager.space()?;
+        let mut file = space.create_file()?;
+        file.write_page(0, &[1; DEFAULT_PAGE_SIZE])?;
+        let path = file.path.clone();
+        assert!(path.exists());
+
+        drop(file);
+        assert!(!path.exists());
+
+        space
+            .create_file()?
+            .write_page(0, &[1; DEFAULT_PAGE_SIZE])?;
+        let space_dir = space.dir.clone();
+        drop(space);
+        assert!(!space_dir.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pages_within_quota() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let manager = TempFileManager::open(dir.path(), DEFAULT_PAGE_SIZE)?
+            .with_quota(3 * DEFAULT_PAGE_SIZE as u64);
+
+        let space = manager.space()?;
+        let mut file = space.create_file()?;
+        let mut other_file = space.create_file()?;
+
+        for page_num in 0..2 {
+            file.write_page(page_num, &[page_num as u8 + 1; DEFAULT_PAGE_SIZE])?;
+        }
+        other_file.write_page(0, &[9; DEFAULT_PAGE_SIZE])?;
+        assert_eq!(space.used(), 3 * DEFAULT_PAGE_SIZE as u64);
+
+        // pages rewritten take no more space
+        file.write_page(0, &[3; DEFAULT_PAGE_SIZE])?;
+        assert!(matches!(
+            other_file.write_page(1, &[9; DEFAULT_PAGE_SIZE]),
+            Err(Error::QuotaExceeded { .. })
+        ));
+        assert!(matches!(
+            file.write_page(3, &[9; DEFAULT_PAGE_SIZE]),
+            Err(Error::PageOutOfRange { page_num: 3, .. })
+        ));
+
+        let mut data = [0; DEFAULT_PAGE_SIZE];
+        file.read_page(0, &mut data)?;
+        assert_eq!(data, [3; DEFAULT_PAGE_SIZE]);
+        file.read_page(1, &mut data)?;
+        assert_eq!(data, [2; DEFAULT_PAGE_SIZE]);
+        assert!(file.read_page(2, &mut data).is_err());
+
+        // a file dropped gives its space back, and a query has a quota of its own
+        drop(file);
+        assert_eq!(space.used(), DEFAULT_PAGE_SIZE as u64);
+        other_file.write_page(1, &[9; DEFAULT_PAGE_SIZE])?;
+        assert_eq!(manager.space()?.used(), 0);
+
+        Ok(())
+    }
+}
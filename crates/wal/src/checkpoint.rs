@@ -0,0 +1,75 @@
+use {
+    super::{error, Lsn, Result, Xid},
+    common::{durability, pub_fields_struct},
+    snafu::{ensure, ResultExt},
+    std::{
+        fs::{self, File},
+        io::{ErrorKind, Write},
+        path::Path,
+    },
+};
+
+const CHECKPOINT_FILE_NAME: &str = "checkpoint";
+
+/// | redo:64 | undo:64 | next xid:64 | crc:32 |
+const CHECKPOINT_SIZE: usize = 28;
+
+pub_fields_struct! {
+    /// A consistent point of the data files. Every change logged before `redo` has been
+    /// written out, and every statement unfinished at the checkpoint has logged its changes
+    /// after `undo`, so recovery never needs to read the log before `undo`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Checkpoint {
+        redo: Lsn,
+        undo: Lsn,
+        /// xids below it may have been given to statements whose records are removed
+        next_xid: Xid,
+    }
+}
+
+impl Checkpoint {
+    /// Reads the last checkpoint from the log directory, `None` if there has been none.
+    pub(super) fn read(dir: &Path) -> Result<Option<Self>> {
+        let bytes = match fs::read(dir.join(CHECKPOINT_FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context(error::IoSnafu),
+        };
+
+        ensure!(
+            bytes.len() == CHECKPOINT_SIZE
+                && crc32fast::hash(&bytes[..24]).to_le_bytes() == bytes[24..],
+            error::InvalidCheckpointSnafu
+        );
+
+        Ok(Some(Self {
+            redo: Lsn::from_le_bytes(bytes[..8].try_into().unwrap()),
+            undo: Lsn::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            next_xid: Xid::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        }))
+    }
+
+    /// Replaces the checkpoint recorded in `dir`, durably if `sync` is set.
+    pub(super) fn write(&self, dir: &Path, sync: bool) -> Result<()> {
+        let mut bytes = Vec::with_capacity(CHECKPOINT_SIZE);
+        bytes.extend_from_slice(&self.redo.to_le_bytes());
+        bytes.extend_from_slice(&self.undo.to_le_bytes());
+        bytes.extend_from_slice(&self.next_xid.to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+        let temp_path = path.with_extension("tmp");
+
+        let mut file = File::create(&temp_path).context(error::IoSnafu)?;
+        file.write_all(&bytes).context(error::IoSnafu)?;
+        if sync {
+            file.sync_all().context(error::IoSnafu)?;
+        }
+
+        fs::rename(&temp_path, &path).context(error::IoSnafu)?;
+        match sync {
+            true => durability::sync_dir(dir).context(error::IoSnafu),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use {
+    snafu::prelude::*,
+    std::{backtrace::Backtrace, io},
+};
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(super)))]
+pub enum Error {
+    Io {
+        backtrace: Backtrace,
+        source: io::Error,
+    },
+
+    #[snafu(display("checkpoint file is corrupted"))]
+    InvalidCheckpoint { backtrace: Backtrace },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
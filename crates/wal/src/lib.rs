@@ -0,0 +1,20 @@
+mod checkpoint;
+mod error;
+mod log;
+mod reader;
+mod record;
+
+pub use self::{
+    checkpoint::Checkpoint,
+    error::{Error, Result},
+    log::Wal,
+    reader::Reader,
+    record::{Delta, PageId, PageOp, PageRecord, Record},
+};
+
+/// Log sequence number, the position in the log right after a record.
+/// `0` means nothing has been logged.
+pub type Lsn = u64;
+
+/// Identifies the statement a record belongs to.
+pub type Xid = u64;
@@ -0,0 +1,618 @@
+use {
+    super::{
+        checkpoint::Checkpoint,
+        error,
+        reader::Reader,
+        record::{PageRecord, Record},
+        Lsn, Result, Xid,
+    },
+    common::durability::{self, SyncPolicy},
+    snafu::ResultExt,
+    std::{
+        collections::HashMap,
+        fs::{self, File, OpenOptions},
+        os::unix::fs::FileExt,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Mutex, MutexGuard,
+        },
+    },
+};
+
+/// The log goes on in a new segment once the current one grows beyond this size.
+const SEGMENT_SIZE: u64 = 1 << 24;
+
+/// The log buffer is written out once it grows beyond this size.
+const BUFFER_SIZE: usize = 1 << 16;
+
+pub struct Wal {
+    /// `None` if the log is never written out
+    file: Option<LogFile>,
+    /// whether flushes and checkpoints are synced
+    sync_policy: SyncPolicy,
+    segment_size: u64,
+
+    buffer: Mutex<Buffer>,
+    /// the log is durable up to this LSN
+    flushed_lsn: AtomicU64,
+
+    next_xid: AtomicU64,
+    /// unfinished statements, with the LSNs of their `Begin` records
+    active: Mutex<HashMap<Xid, Lsn>>,
+    /// the page records of unfinished statements, kept to undo them when there is no file
+    /// to read them back from
+    kept: Mutex<HashMap<Xid, Vec<PageRecord>>>,
+}
+
+/// The log is kept in segments named after the LSN they start at, and a record never
+/// spans two segments. Segments before the last checkpoint are removed.
+struct LogFile {
+    dir: PathBuf,
+    /// the segment written to
+    segment: Mutex<Segment>,
+}
+
+struct Segment {
+    start: Lsn,
+    file: File,
+}
+
+struct Buffer {
+    data: Vec<u8>,
+    /// the LSN of the first byte in `data`
+    start: Lsn,
+}
+
+impl Wal {
+    pub fn open(dir: &Path) -> Result<Self> {
+        durability::create_dirs_synced(dir).context(error::IoSnafu)?;
+
+        let starts = segment_starts(dir)?;
+        let (first, last) = match (starts.first(), starts.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => (0, 0),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(segment_path(dir, last))
+            .context(error::IoSnafu)?;
+        if starts.is_empty() {
+            durability::sync_dir(dir).context(error::IoSnafu)?;
+        }
+
+        // find the end of the log, a record torn by a crash is cut off
+        let (mut end, mut last_xid) = (first, 0);
+        for item in Reader::new(open_segments(dir, &starts)?, first)? {
+            let (lsn, record) = item?;
+            end = lsn;
+            last_xid = last_xid.max(record.xid());
+        }
+        file.set_len(end.saturating_sub(last))
+            .context(error::IoSnafu)?;
+
+        // the records of the last statements may be in removed segments
+        let next_xid = match Checkpoint::read(dir)? {
+            Some(checkpoint) => checkpoint.next_xid.max(last_xid + 1),
+            None => last_xid + 1,
+        };
+
+        let file = LogFile {
+            dir: dir.to_path_buf(),
+            segment: Mutex::new(Segment { start: last, file }),
+        };
+
+        Ok(Self::with_file(Some(file), end, next_xid))
+    }
+
+    /// Opens a log which is never written out, for databases kept in memory. Records are
+    /// still given LSNs, but there is nothing to read back and no checkpoint is recorded.
+    pub fn in_memory() -> Self {
+        Self::with_file(None, 0, 1)
+    }
+
+    fn with_file(file: Option<LogFile>, end: Lsn, next_xid: Xid) -> Self {
+        Self {
+            file,
+            sync_policy: SyncPolicy::default(),
+            segment_size: SEGMENT_SIZE,
+
+            buffer: Mutex::new(Buffer {
+                data: Vec::with_capacity(BUFFER_SIZE),
+                start: end,
+            }),
+            flushed_lsn: AtomicU64::new(end),
+
+            next_xid: AtomicU64::new(next_xid),
+            active: Mutex::new(HashMap::new()),
+            kept: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Syncs the log as `sync_policy` says, `SyncPolicy::Never` leaves committed
+    /// statements in the page cache of the kernel.
+    pub fn with_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    #[cfg(test)]
+    fn with_segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    /// Reads the records written out to the log files, starting at `from`, or at the first
+    /// record kept if the segments before it have been removed.
+    pub fn reader(&self, from: Lsn) -> Result<Reader> {
+        let Some(log) = &self.file else {
+            return Ok(Reader::empty());
+        };
+
+        // segments ending before `from` are left out
+        let starts = segment_starts(&log.dir)?;
+        let skipped = starts.iter().skip(1).take_while(|&&start| start <= from);
+        let starts = &starts[skipped.count()..];
+
+        Reader::new(open_segments(&log.dir, starts)?, from)
+    }
+
+    /// Appends the record to the log buffer, returns its LSN.
+    pub fn append(&self, record: &Record) -> Result<Lsn> {
+        let mut payload = vec![];
+        record.encode(&mut payload);
+
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer
+            .data
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buffer
+            .data
+            .extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        buffer.data.extend_from_slice(&payload);
+
+        let lsn = buffer.start + buffer.data.len() as Lsn;
+
+        match record {
+            Record::Begin { xid } => {
+                self.active.lock().unwrap().insert(*xid, lsn);
+            }
+            Record::Commit { xid } | Record::Abort { xid } => {
+                self.active.lock().unwrap().remove(xid);
+                self.kept.lock().unwrap().remove(xid);
+            }
+            Record::Page(record) if self.file.is_none() => {
+                self.kept
+                    .lock()
+                    .unwrap()
+                    .entry(record.xid)
+                    .or_default()
+                    .push(record.clone());
+            }
+            Record::Page(_) => {}
+        }
+
+        if buffer.data.len() >= BUFFER_SIZE {
+            self.write_buffer(&mut buffer)?;
+        }
+
+        Ok(lsn)
+    }
+
+    /// Makes the log durable up to `lsn`.
+    pub fn flush(&self, lsn: Lsn) -> Result<()> {
+        if self.flushed_lsn() >= lsn {
+            return Ok(());
+        }
+
+        let mut buffer = self.buffer.lock().unwrap();
+        if self.flushed_lsn() >= lsn {
+            return Ok(());
+        }
+
+        self.write_buffer(&mut buffer)?;
+        match &self.file {
+            Some(log) if self.sync_policy.syncs() => {
+                let segment = log.segment.lock().unwrap();
+                segment.file.sync_data().context(error::IoSnafu)?;
+            }
+            _ => {}
+        }
+
+        self.flushed_lsn.store(buffer.start, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    fn write_buffer(&self, buffer: &mut MutexGuard<Buffer>) -> Result<()> {
+        if let Some(log) = &self.file {
+            let mut segment = log.segment.lock().unwrap();
+            if buffer.start - segment.start >= self.segment_size && !buffer.data.is_empty() {
+                log.switch_segment(&mut segment, buffer.start, self.sync_policy.syncs())?;
+            }
+
+            segment
+                .file
+                .write_all_at(&buffer.data, buffer.start - segment.start)
+                .context(error::IoSnafu)?;
+        }
+
+        buffer.start += buffer.data.len() as Lsn;
+        buffer.data.clear();
+
+        Ok(())
+    }
+
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.flushed_lsn.load(Ordering::SeqCst)
+    }
+
+    /// Starts a checkpoint at the end of the log. The checkpoint is only recorded by
+    /// `finish_checkpoint`, after every page changed before it has been written out.
+    pub fn start_checkpoint(&self) -> Checkpoint {
+        let buffer = self.buffer.lock().unwrap();
+        let redo = buffer.start + buffer.data.len() as Lsn;
+        let undo = self
+            .active
+            .lock()
+            .unwrap()
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(redo);
+
+        let next_xid = self.next_xid.load(Ordering::SeqCst);
+
+        Checkpoint {
+            redo,
+            undo,
+            next_xid,
+        }
+    }
+
+    /// Records the checkpoint, then removes the segments which recovery no longer reads.
+    pub fn finish_checkpoint(&self, checkpoint: &Checkpoint) -> Result<()> {
+        self.flush(checkpoint.redo)?;
+
+        match &self.file {
+            Some(log) => {
+                checkpoint.write(&log.dir, self.sync_policy.syncs())?;
+                log.remove_segments(checkpoint.redo.min(checkpoint.undo))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the last checkpoint recorded, `None` if there has been none.
+    pub fn last_checkpoint(&self) -> Result<Option<Checkpoint>> {
+        match &self.file {
+            Some(log) => Checkpoint::read(&log.dir),
+            None => Ok(None),
+        }
+    }
+
+    /// Starts a statement.
+    pub fn begin(&self) -> Result<Xid> {
+        let xid = self.next_xid.fetch_add(1, Ordering::SeqCst);
+        self.append(&Record::Begin { xid })?;
+
+        Ok(xid)
+    }
+
+    /// Finishes a statement, its changes are durable once this returns.
+    pub fn commit(&self, xid: Xid) -> Result<()> {
+        let lsn = self.append(&Record::Commit { xid })?;
+        self.flush(lsn)
+    }
+
+    /// Finishes a statement whose changes have been undone. The record is not flushed, as
+    /// recovery undoes the statement again if it is lost.
+    pub fn abort(&self, xid: Xid) -> Result<()> {
+        self.append(&Record::Abort { xid }).map(drop)
+    }
+
+    /// Returns the page records of the unfinished statement, in the order they were
+    /// logged. The log buffer is written out to read them back, but not synced.
+    pub fn page_records(&self, xid: Xid) -> Result<Vec<PageRecord>> {
+        if self.file.is_none() {
+            let kept = self.kept.lock().unwrap();
+            return Ok(kept.get(&xid).cloned().unwrap_or_default());
+        }
+
+        let Some(&begin) = self.active.lock().unwrap().get(&xid) else {
+            return Ok(vec![]);
+        };
+        self.write_buffer(&mut self.buffer.lock().unwrap())?;
+
+        let mut records = vec![];
+        for item in self.reader(begin)? {
+            match item?.1 {
+                Record::Page(record) if record.xid == xid => records.push(record),
+                _ => {}
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+impl LogFile {
+    /// Goes on in a new segment starting at `start`. The rest of the old segment is synced
+    /// first, as flushes only sync the segment written to.
+    fn switch_segment(&self, segment: &mut Segment, start: Lsn, sync: bool) -> Result<()> {
+        if sync {
+            segment.file.sync_data().context(error::IoSnafu)?;
+        }
+
+        let file = OpenOptions::new()
+            .create_new(true)
+            .read(true)
+            .write(true)
+            .open(segment_path(&self.dir, start))
+            .context(error::IoSnafu)?;
+        if sync {
+            durability::sync_dir(&self.dir).context(error::IoSnafu)?;
+        }
+
+        *segment = Segment { start, file };
+
+        Ok(())
+    }
+
+    /// Removes the segments holding no record at or after `lsn`, but never the one
+    /// written to.
+    fn remove_segments(&self, lsn: Lsn) -> Result<()> {
+        let starts = segment_starts(&self.dir)?;
+        for pair in starts.windows(2) {
+            if pair[1] > lsn {
+                break;
+            }
+            fs::remove_file(segment_path(&self.dir, pair[0])).context(error::IoSnafu)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, start: Lsn) -> PathBuf {
+    dir.join(format!("{:016X}", start))
+}
+
+/// The LSNs the segments in the directory start at, in ascending order.
+fn segment_starts(dir: &Path) -> Result<Vec<Lsn>> {
+    let mut starts = vec![];
+    for entry in fs::read_dir(dir).context(error::IoSnafu)? {
+        let name = entry.context(error::IoSnafu)?.file_name();
+        let name = name.to_string_lossy();
+        if name.len() != 16 {
+            continue;
+        }
+        if let Ok(start) = Lsn::from_str_radix(&name, 16) {
+            starts.push(start);
+        }
+    }
+    starts.sort_unstable();
+
+    Ok(starts)
+}
+
+fn open_segments(dir: &Path, starts: &[Lsn]) -> Result<Vec<(Lsn, File)>> {
+    starts
+        .iter()
+        .map(|&start| {
+            let file = File::open(segment_path(dir, start)).context(error::IoSnafu)?;
+            Ok((start, file))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            reader::RECORD_HEADER_SIZE,
+            record::{Delta, PageId, PageOp},
+        },
+        tempfile::tempdir,
+    };
+
+    #[test]
+    fn reopen() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        let xid = {
+            let wal = Wal::open(dir.path())?;
+            let xid = wal.begin()?;
+            wal.commit(xid)?;
+
+            // never flushed
+            wal.begin()?;
+
+            xid
+        };
+
+        let wal = Wal::open(dir.path())?;
+        let records = wal
+            .reader(0)?
+            .map(|item| item.map(|(_, record)| record))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(records, [Record::Begin { xid }, Record::Commit { xid }]);
+        assert_eq!(wal.begin()?, xid + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_are_written_without_sync() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        let xid = {
+            let wal = Wal::open(dir.path())?.with_sync_policy(SyncPolicy::Never);
+            let xid = wal.begin()?;
+            wal.commit(xid)?;
+            wal.finish_checkpoint(&wal.start_checkpoint())?;
+
+            xid
+        };
+
+        // the kernel still has what a crash of the machine would lose
+        let wal = Wal::open(dir.path())?;
+        assert_eq!(wal.reader(0)?.count(), 2);
+        assert!(wal.last_checkpoint()?.is_some());
+        assert_eq!(wal.begin()?, xid + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn torn_record_is_cut_off() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        let end = {
+            let wal = Wal::open(dir.path())?;
+            let xid = wal.begin()?;
+            wal.commit(xid)?;
+
+            wal.flushed_lsn()
+        };
+
+        // a record partly written before a crash
+        let path = segment_path(dir.path(), 0);
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all_at(&[42; RECORD_HEADER_SIZE + 3], end)
+            .unwrap();
+
+        let wal = Wal::open(dir.path())?;
+        assert_eq!(wal.flushed_lsn(), end);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), end);
+
+        let xid = wal.begin()?;
+        wal.commit(xid)?;
+        assert_eq!(wal.reader(0)?.count(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn checkpoint() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        {
+            let wal = Wal::open(dir.path())?;
+            assert_eq!(wal.last_checkpoint()?, None);
+
+            let xid = wal.begin()?;
+            wal.commit(xid)?;
+
+            let unfinished = wal.begin()?;
+            let begin_lsn = wal.flushed_lsn() + (RECORD_HEADER_SIZE + 9) as Lsn;
+            let xid = wal.begin()?;
+            wal.commit(xid)?;
+
+            let checkpoint = wal.start_checkpoint();
+            assert_eq!(checkpoint.redo, wal.flushed_lsn());
+            assert_eq!(checkpoint.undo, begin_lsn);
+            wal.finish_checkpoint(&checkpoint)?;
+
+            wal.commit(unfinished)?;
+            let checkpoint = wal.start_checkpoint();
+            assert_eq!(checkpoint.undo, checkpoint.redo);
+        }
+
+        let wal = Wal::open(dir.path())?;
+        let checkpoint = wal.last_checkpoint()?.unwrap();
+        assert!(checkpoint.undo < checkpoint.redo);
+
+        Ok(())
+    }
+
+    #[test]
+    fn segments_before_checkpoint_are_removed() -> Result<()> {
+        let dir = tempdir().unwrap();
+
+        let last_xid = {
+            let wal = Wal::open(dir.path())?.with_segment_size(64);
+            for _ in 0..10 {
+                let xid = wal.begin()?;
+                wal.commit(xid)?;
+            }
+
+            let unfinished = wal.begin()?;
+            for _ in 0..10 {
+                let xid = wal.begin()?;
+                wal.commit(xid)?;
+            }
+            let count = segment_starts(dir.path())?.len();
+            assert!(count > 4);
+
+            // the statement unfinished at the checkpoint is still to be read
+            let checkpoint = wal.start_checkpoint();
+            wal.finish_checkpoint(&checkpoint)?;
+            let starts = segment_starts(dir.path())?;
+            assert!(starts.len() < count);
+            assert!(starts[0] <= checkpoint.undo && checkpoint.undo < starts[1]);
+            assert_eq!(
+                wal.reader(0)?.next().transpose()?,
+                Some((checkpoint.undo, Record::Begin { xid: unfinished }))
+            );
+
+            wal.commit(unfinished)?;
+            wal.finish_checkpoint(&wal.start_checkpoint())?;
+            assert_eq!(segment_starts(dir.path())?.len(), 1);
+
+            unfinished + 10
+        };
+
+        let wal = Wal::open(dir.path())?;
+        assert!(wal.last_checkpoint()?.is_some());
+        assert_eq!(wal.begin()?, last_xid + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn page_records_of_unfinished_statements() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let page_record = |xid, page_num| PageRecord {
+            xid,
+            op: PageOp::LeafInsert,
+            page: PageId {
+                space_id: 0,
+                database_id: 1,
+                table_id: 2,
+                page_num,
+            },
+            deltas: vec![Delta {
+                offset: 0,
+                before: vec![0],
+                after: vec![page_num as u8],
+            }],
+        };
+
+        for wal in [Wal::open(dir.path())?, Wal::in_memory()] {
+            let (xid, other) = (wal.begin()?, wal.begin()?);
+            for page_num in 0..3 {
+                wal.append(&Record::Page(page_record(xid, page_num)))?;
+                wal.append(&Record::Page(page_record(other, page_num)))?;
+            }
+            wal.commit(other)?;
+
+            assert_eq!(
+                wal.page_records(xid)?,
+                (0..3)
+                    .map(|page_num| page_record(xid, page_num))
+                    .collect::<Vec<_>>()
+            );
+
+            wal.abort(xid)?;
+            assert_eq!(wal.page_records(xid)?, []);
+        }
+
+        Ok(())
+    }
+}
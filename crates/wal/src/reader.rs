@@ -0,0 +1,91 @@
+use {
+    super::{error, record::Record, Lsn, Result},
+    snafu::ResultExt,
+    std::{
+        fs::File,
+        io::{self, BufReader, Read, Seek, SeekFrom},
+    },
+};
+
+/// | length:32 | crc:32 |
+pub(super) const RECORD_HEADER_SIZE: usize = 8;
+
+/// Reads records from the log in order, together with their LSNs.
+/// The iteration stops at the end of the log, or at the first record torn by a crash.
+pub struct Reader {
+    file: BufReader<Box<dyn Read + Send>>,
+    lsn: Lsn,
+}
+
+impl Reader {
+    /// Reads the segments one after another, from `from` in the first one, or from its
+    /// start if `from` is before it. Each segment starts where the one before it ends.
+    pub(super) fn new(segments: Vec<(Lsn, File)>, from: Lsn) -> Result<Self> {
+        let mut segments = segments.into_iter();
+        let Some((start, mut first)) = segments.next() else {
+            return Ok(Self::empty());
+        };
+
+        let from = from.max(start);
+        first
+            .seek(SeekFrom::Start(from - start))
+            .context(error::IoSnafu)?;
+
+        let file = segments.fold(
+            Box::new(first) as Box<dyn Read + Send>,
+            |file, (_, next)| Box::new(file.chain(next)),
+        );
+
+        Ok(Self {
+            file: BufReader::new(file),
+            lsn: from,
+        })
+    }
+
+    /// Returns a reader of a log which has nothing written out.
+    pub(super) fn empty() -> Self {
+        Self {
+            file: BufReader::new(Box::new(io::empty())),
+            lsn: 0,
+        }
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        let mut header = [0; RECORD_HEADER_SIZE];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+
+        let mut payload = vec![0; len];
+        if !self.read_or_eof(&mut payload)? || crc32fast::hash(&payload) != crc {
+            return Ok(None);
+        }
+
+        self.lsn += (RECORD_HEADER_SIZE + len) as Lsn;
+
+        Ok(Record::decode(&payload))
+    }
+
+    /// Returns `false` if the end of file is reached before `buf` is filled.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        match self.file.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<(Lsn, Record)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record()
+            .context(error::IoSnafu)
+            .map(|record| record.map(|record| (self.lsn, record)))
+            .transpose()
+    }
+}
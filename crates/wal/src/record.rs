@@ -0,0 +1,348 @@
+use {
+    super::Xid,
+    common::pub_fields_struct,
+    def::{DatabaseId, TableId, TableSpaceId},
+};
+
+/// Unchanged bytes between two changed ranges shorter than this are logged
+/// as part of a single delta.
+const MERGE_GAP: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    /// a statement starts
+    Begin {
+        xid: Xid,
+    },
+    /// a statement finishes, and its changes must survive a crash
+    Commit {
+        xid: Xid,
+    },
+    /// the changes of a statement have been undone
+    Abort {
+        xid: Xid,
+    },
+    Page(PageRecord),
+}
+
+/// The B-tree operation a page change is made by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PageOp {
+    LeafInsert = 1,
+    LeafSplit,
+    BranchInsert,
+    BranchSplit,
+    BranchUpdate,
+    NewRoot,
+    MetaUpdate,
+    /// reverts a change made by a statement which never finished
+    Undo,
+    /// puts a page on the free list of a B-tree
+    FreePage,
+    /// takes a page off the free list of a B-tree
+    ReusePage,
+    /// writes a part of a value kept out of its row
+    OverflowWrite,
+    LeafDelete,
+    LeafMerge,
+    LeafRedistribute,
+    BranchMerge,
+    BranchRedistribute,
+    BranchDelete,
+    LeafUpdate,
+    /// writes a node of a B-tree built bottom-up from sorted entries
+    BulkLoad,
+}
+
+pub_fields_struct! {
+    #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+    struct PageId {
+        space_id: TableSpaceId,
+        database_id: DatabaseId,
+        table_id: TableId,
+        page_num: u32,
+    }
+
+    /// A changed range of a page, with the bytes before and after the change.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Delta {
+        offset: u16,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct PageRecord {
+        xid: Xid,
+        op: PageOp,
+        page: PageId,
+        deltas: Vec<Delta>,
+    }
+}
+
+const BEGIN: u8 = 1;
+const COMMIT: u8 = 2;
+const PAGE: u8 = 3;
+const ABORT: u8 = 4;
+
+impl Record {
+    pub fn xid(&self) -> Xid {
+        match self {
+            Self::Begin { xid } | Self::Commit { xid } | Self::Abort { xid } => *xid,
+            Self::Page(record) => record.xid,
+        }
+    }
+
+    pub(super) fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Begin { xid } => {
+                buf.push(BEGIN);
+                buf.extend_from_slice(&xid.to_le_bytes());
+            }
+            Self::Commit { xid } => {
+                buf.push(COMMIT);
+                buf.extend_from_slice(&xid.to_le_bytes());
+            }
+            Self::Abort { xid } => {
+                buf.push(ABORT);
+                buf.extend_from_slice(&xid.to_le_bytes());
+            }
+            Self::Page(record) => {
+                buf.push(PAGE);
+                buf.extend_from_slice(&record.xid.to_le_bytes());
+                buf.push(record.op as u8);
+
+                let page = &record.page;
+                for n in [
+                    page.space_id,
+                    page.database_id,
+                    page.table_id,
+                    page.page_num,
+                ] {
+                    buf.extend_from_slice(&n.to_le_bytes());
+                }
+
+                buf.extend_from_slice(&(record.deltas.len() as u16).to_le_bytes());
+                for delta in &record.deltas {
+                    buf.extend_from_slice(&delta.offset.to_le_bytes());
+                    buf.extend_from_slice(&(delta.after.len() as u16).to_le_bytes());
+                    buf.extend_from_slice(&delta.before);
+                    buf.extend_from_slice(&delta.after);
+                }
+            }
+        }
+    }
+
+    /// Returns `None` if the bytes are not a valid record.
+    pub(super) fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut bytes = Bytes(bytes);
+
+        let record = match bytes.u8()? {
+            BEGIN => Self::Begin { xid: bytes.u64()? },
+            COMMIT => Self::Commit { xid: bytes.u64()? },
+            ABORT => Self::Abort { xid: bytes.u64()? },
+            PAGE => {
+                let xid = bytes.u64()?;
+                let op = PageOp::try_from(bytes.u8()?).ok()?;
+                let page = PageId {
+                    space_id: bytes.u32()?,
+                    database_id: bytes.u32()?,
+                    table_id: bytes.u32()?,
+                    page_num: bytes.u32()?,
+                };
+
+                let deltas = (0..bytes.u16()?)
+                    .map(|_| {
+                        let offset = bytes.u16()?;
+                        let len = bytes.u16()? as usize;
+
+                        Some(Delta {
+                            offset,
+                            before: bytes.take(len)?.to_vec(),
+                            after: bytes.take(len)?.to_vec(),
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+
+                Self::Page(PageRecord {
+                    xid,
+                    op,
+                    page,
+                    deltas,
+                })
+            }
+            _ => return None,
+        };
+
+        bytes.0.is_empty().then_some(record)
+    }
+}
+
+impl TryFrom<u8> for PageOp {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::LeafInsert,
+            2 => Self::LeafSplit,
+            3 => Self::BranchInsert,
+            4 => Self::BranchSplit,
+            5 => Self::BranchUpdate,
+            6 => Self::NewRoot,
+            7 => Self::MetaUpdate,
+            8 => Self::Undo,
+            9 => Self::FreePage,
+            10 => Self::ReusePage,
+            11 => Self::OverflowWrite,
+            12 => Self::LeafDelete,
+            13 => Self::LeafMerge,
+            14 => Self::LeafRedistribute,
+            15 => Self::BranchMerge,
+            16 => Self::BranchRedistribute,
+            17 => Self::BranchDelete,
+            18 => Self::LeafUpdate,
+            19 => Self::BulkLoad,
+            _ => return Err(value),
+        })
+    }
+}
+
+impl Delta {
+    /// Computes the ranges changed from `before` to `after`, both are of the same length.
+    pub fn diff(before: &[u8], after: &[u8]) -> Vec<Self> {
+        debug_assert_eq!(before.len(), after.len());
+
+        let mut deltas: Vec<Self> = vec![];
+        let mut i = 0;
+
+        while i < before.len() {
+            if before[i] == after[i] {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            let mut end = i + 1;
+            while end < before.len() {
+                if before[end] != after[end] {
+                    end += 1;
+                    continue;
+                }
+
+                // look ahead for a change close enough to be merged
+                let gap_end = (end + MERGE_GAP).min(before.len());
+                match (end..gap_end).find(|&j| before[j] != after[j]) {
+                    Some(j) => end = j + 1,
+                    None => break,
+                }
+            }
+
+            deltas.push(Self {
+                offset: start as u16,
+                before: before[start..end].to_vec(),
+                after: after[start..end].to_vec(),
+            });
+            i = end;
+        }
+
+        deltas
+    }
+
+    pub fn redo(&self, page: &mut [u8]) {
+        let offset = self.offset as usize;
+        page[offset..offset + self.after.len()].copy_from_slice(&self.after);
+    }
+
+    pub fn undo(&self, page: &mut [u8]) {
+        let offset = self.offset as usize;
+        page[offset..offset + self.before.len()].copy_from_slice(&self.before);
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_and_apply() {
+        let before = vec![0u8; 128];
+        let mut after = before.clone();
+        after[3] = 1;
+        after[6] = 2;
+        after[64..70].fill(3);
+
+        let deltas = Delta::diff(&before, &after);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].offset, 3);
+        assert_eq!(deltas[0].after, [1, 0, 0, 2]);
+
+        let mut page = before.clone();
+        deltas.iter().for_each(|delta| delta.redo(&mut page));
+        assert_eq!(page, after);
+
+        deltas.iter().rev().for_each(|delta| delta.undo(&mut page));
+        assert_eq!(page, before);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let records = [
+            Record::Begin { xid: 1 },
+            Record::Page(PageRecord {
+                xid: 1,
+                op: PageOp::LeafSplit,
+                page: PageId {
+                    space_id: 0,
+                    database_id: 1,
+                    table_id: 2,
+                    page_num: 3,
+                },
+                deltas: Delta::diff(&[0, 0, 0, 0], &[0, 1, 2, 0]),
+            }),
+            Record::Commit { xid: 1 },
+            Record::Abort { xid: 2 },
+        ];
+
+        for record in records {
+            let mut buf = vec![];
+            record.encode(&mut buf);
+
+            assert_eq!(Record::decode(&buf), Some(record));
+            assert_eq!(Record::decode(&buf[..buf.len() - 1]), None);
+        }
+    }
+}